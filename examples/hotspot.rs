@@ -77,6 +77,8 @@ fn run() -> Result<()> {
         matches.value_of("SSID").unwrap(),
         matches.value_of("PASSWORD"),
         None,
+        None,
+        None,
     )?;
 
     Ok(())
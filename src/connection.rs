@@ -1,12 +1,14 @@
+use std::collections::HashMap;
 use std::rc::Rc;
 use std::fmt;
 use std::net::Ipv4Addr;
 
 use errors::*;
-use dbus_nm::DBusNetworkManager;
+use dbus_nm::{DBusNetworkManager, SettingsMap, VariantMap};
+use settings::{GsmConfig, WirelessPowersave};
 
 use wifi::{AccessPoint, AccessPointCredentials};
-use device::{get_active_connection_devices, Device};
+use device::{get_active_connection_devices, Device, Metered, PathGetter};
 use ssid::{AsSsidSlice, Ssid};
 
 #[derive(Clone)]
@@ -31,6 +33,98 @@ impl Connection {
         &self.settings
     }
 
+    /// The `connection.id` of the profile, as read when the connection was listed or updated.
+    pub fn id(&self) -> &str {
+        &self.settings.id
+    }
+
+    /// The `connection.uuid` of the profile.
+    pub fn uuid(&self) -> &str {
+        &self.settings.uuid
+    }
+
+    /// Reads all settings of the profile, without its secrets.
+    pub fn get_settings_map(&self) -> Result<SettingsMap> {
+        self.dbus_manager.get_connection_settings_map(&self.path)
+    }
+
+    /// Enables or disables automatic activation of the profile.
+    pub fn set_autoconnect(&self, enabled: bool) -> Result<()> {
+        self.dbus_manager.set_autoconnect(&self.path, enabled)
+    }
+
+    /// Sets the autoconnect priority; profiles with a higher priority are preferred.
+    pub fn set_autoconnect_priority(&self, priority: i32) -> Result<()> {
+        self.dbus_manager
+            .set_autoconnect_priority(&self.path, priority)
+    }
+
+    /// Marks traffic over the profile as metered or not, or lets NetworkManager guess with
+    /// `Metered::Unknown`.
+    pub fn set_metered(&self, metered: Metered) -> Result<()> {
+        self.dbus_manager.set_metered(&self.path, metered)
+    }
+
+    /// Forces power saving of a Wi-Fi profile on or off.
+    pub fn set_wifi_powersave(&self, powersave: WirelessPowersave) -> Result<()> {
+        self.dbus_manager.set_wifi_powersave(&self.path, powersave)
+    }
+
+    /// Sets the MTU of a wired or Wi-Fi profile, or `0` to use the default of the device.
+    pub fn set_mtu(&self, mtu: u32) -> Result<()> {
+        self.dbus_manager.set_mtu(&self.path, mtu)
+    }
+
+    /// Pins the profile to the interface `interface` so that it cannot activate on another
+    /// adapter, or unpins it with `None`.
+    pub fn bind_to_interface(&self, interface: Option<&str>) -> Result<()> {
+        self.dbus_manager
+            .bind_to_interface(&self.path, interface)
+    }
+
+    /// The firewalld zone the profile's interface is put into, if one is set.
+    pub fn zone(&self) -> Result<Option<String>> {
+        self.dbus_manager.get_connection_zone(&self.path)
+    }
+
+    /// Puts the profile's interface into the firewalld `zone`, or back into the default zone
+    /// with `None`.
+    pub fn set_zone(&self, zone: Option<&str>) -> Result<()> {
+        self.dbus_manager.set_connection_zone(&self.path, zone)
+    }
+
+    /// Replaces all settings of the profile and saves them to disk.
+    ///
+    /// `settings` must be complete, e.g. as read by `get_settings_map` and then modified, since
+    /// settings left out are reset to their defaults. Values read by `get_settings_map` are
+    /// sent back with the signatures NetworkManager sent them with.
+    pub fn update(&mut self, settings: &SettingsMap) -> Result<()> {
+        self.dbus_manager.update_connection(&self.path, settings)?;
+
+        self.settings = self.dbus_manager.get_connection_settings(&self.path)?;
+
+        Ok(())
+    }
+
+    /// Replaces all settings of the profile like `update`, without saving them to disk.
+    pub fn update_unsaved(&mut self, settings: &SettingsMap) -> Result<()> {
+        self.dbus_manager
+            .update_connection_unsaved(&self.path, settings)?;
+
+        self.settings = self.dbus_manager.get_connection_settings(&self.path)?;
+
+        Ok(())
+    }
+
+    /// Reads the secrets of the `setting_name` setting, e.g. `802-11-wireless-security`.
+    ///
+    /// NetworkManager may ask PolicyKit for authorization and fails with a
+    /// `DBusError::NotAuthorized` error if it is refused.
+    pub fn get_secrets(&self, setting_name: &str) -> Result<SettingsMap> {
+        self.dbus_manager
+            .get_connection_secrets(&self.path, setting_name)
+    }
+
     pub fn get_state(&self) -> Result<ConnectionState> {
         let active_path_option = get_connection_active_path(&self.dbus_manager, &self.path)?;
 
@@ -43,8 +137,15 @@ impl Connection {
         }
     }
 
+    /// Deletes the profile, deactivating it first if it is active.
     pub fn delete(&self) -> Result<()> {
-        self.dbus_manager.delete_connection(&self.path)
+        delete_connection(&self.dbus_manager, &self.path)
+    }
+
+    /// Deletes the profile like `delete`, but fails with `ErrorKind::ConnectionActive` instead
+    /// of deactivating it.
+    pub fn delete_inactive(&self) -> Result<()> {
+        delete_inactive_connection(&self.dbus_manager, &self.path)
     }
 
     /// Activate a Network Manager connection.
@@ -58,6 +159,15 @@ impl Connection {
     /// connections[0].activate().unwrap();
     /// ```
     pub fn activate(&self) -> Result<ConnectionState> {
+        self.activate_with_device("/")
+    }
+
+    /// Activates the connection on `device` rather than letting NetworkManager pick one.
+    pub fn activate_on(&self, device: &Device) -> Result<ConnectionState> {
+        self.activate_with_device(device.path())
+    }
+
+    fn activate_with_device(&self, device_path: &str) -> Result<ConnectionState> {
         let state = self.get_state()?;
 
         match state {
@@ -71,7 +181,8 @@ impl Connection {
                 "Unable to get connection state".into()
             )),
             _ => {
-                self.dbus_manager.activate_connection(&self.path)?;
+                self.dbus_manager
+                    .activate_connection(&self.path, device_path, "/")?;
 
                 wait(
                     self,
@@ -184,6 +295,19 @@ pub struct ConnectionSettings {
     pub uuid: String,
     pub ssid: Ssid,
     pub mode: String,
+    /// The DNS search domains of the IPv4 and IPv6 settings.
+    pub dns_search: Vec<String>,
+}
+
+/// A connection currently being set up, used or torn down by NetworkManager.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ActiveConnection {
+    pub path: String,
+    pub id: String,
+    pub uuid: String,
+    pub kind: String, // `type` is a reserved word, so we are using `kind` instead
+    pub state: ConnectionState,
+    pub devices: Vec<String>,
 }
 
 #[derive(Debug, Clone, Eq, PartialEq)]
@@ -195,6 +319,12 @@ pub enum ConnectionState {
     Deactivated = 4,
 }
 
+impl From<u32> for ConnectionState {
+    fn from(state: u32) -> Self {
+        ConnectionState::from(i64::from(state))
+    }
+}
+
 impl From<i64> for ConnectionState {
     fn from(state: i64) -> Self {
         match state {
@@ -211,6 +341,58 @@ impl From<i64> for ConnectionState {
     }
 }
 
+/// Why an active connection changed its state, as reported by its `StateChanged` signal.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ActiveConnectionStateReason {
+    Unknown,
+    None,
+    UserDisconnected,
+    DeviceDisconnected,
+    ServiceStopped,
+    IpConfigInvalid,
+    ConnectTimeout,
+    ServiceStartTimeout,
+    ServiceStartFailed,
+    NoSecrets,
+    LoginFailed,
+    ConnectionRemoved,
+    DependencyFailed,
+    DeviceRealizeFailed,
+    DeviceRemoved,
+}
+
+impl From<u32> for ActiveConnectionStateReason {
+    fn from(reason: u32) -> Self {
+        ActiveConnectionStateReason::from(i64::from(reason))
+    }
+}
+
+impl From<i64> for ActiveConnectionStateReason {
+    fn from(reason: i64) -> Self {
+        match reason {
+            0 => ActiveConnectionStateReason::Unknown,
+            1 => ActiveConnectionStateReason::None,
+            2 => ActiveConnectionStateReason::UserDisconnected,
+            3 => ActiveConnectionStateReason::DeviceDisconnected,
+            4 => ActiveConnectionStateReason::ServiceStopped,
+            5 => ActiveConnectionStateReason::IpConfigInvalid,
+            6 => ActiveConnectionStateReason::ConnectTimeout,
+            7 => ActiveConnectionStateReason::ServiceStartTimeout,
+            8 => ActiveConnectionStateReason::ServiceStartFailed,
+            9 => ActiveConnectionStateReason::NoSecrets,
+            10 => ActiveConnectionStateReason::LoginFailed,
+            11 => ActiveConnectionStateReason::ConnectionRemoved,
+            12 => ActiveConnectionStateReason::DependencyFailed,
+            13 => ActiveConnectionStateReason::DeviceRealizeFailed,
+            14 => ActiveConnectionStateReason::DeviceRemoved,
+            _ => {
+                warn!("Undefined active connection state reason: {}", reason);
+                ActiveConnectionStateReason::Unknown
+            },
+        }
+    }
+}
+
 pub fn get_connections(dbus_manager: &Rc<DBusNetworkManager>) -> Result<Vec<Connection>> {
     let paths = dbus_manager.list_connections()?;
 
@@ -225,6 +407,64 @@ pub fn get_connections(dbus_manager: &Rc<DBusNetworkManager>) -> Result<Vec<Conn
     Ok(connections)
 }
 
+pub fn get_connection_by_uuid(
+    dbus_manager: &Rc<DBusNetworkManager>,
+    uuid: &str,
+) -> Result<Connection> {
+    let path = dbus_manager.get_connection_by_uuid(uuid)?;
+
+    Connection::init(dbus_manager, &path)
+}
+
+pub fn add_connection(
+    dbus_manager: &Rc<DBusNetworkManager>,
+    settings: &SettingsMap,
+    save: bool,
+) -> Result<Connection> {
+    let path = if save {
+        dbus_manager.add_connection(settings)?
+    } else {
+        dbus_manager.add_connection_unsaved(settings)?
+    };
+
+    Connection::init(dbus_manager, &path)
+}
+
+pub fn add_vpn_connection(
+    dbus_manager: &Rc<DBusNetworkManager>,
+    id: &str,
+    service_type: &str,
+    data: HashMap<String, String>,
+    secrets: HashMap<String, String>,
+) -> Result<Connection> {
+    let path = dbus_manager.add_vpn_connection(id, service_type, data, secrets)?;
+
+    Connection::init(dbus_manager, &path)
+}
+
+/// Deletes the profile at `path`, which NetworkManager deactivates first if it is active.
+///
+/// Fails with `DBusError::NotFound` if the profile does not exist.
+pub fn delete_connection(dbus_manager: &DBusNetworkManager, path: &str) -> Result<()> {
+    dbus_manager.delete_connection(path)
+}
+
+/// Deletes the profile at `path` like `delete_connection`, but fails with
+/// `ErrorKind::ConnectionActive` while the profile is active.
+pub fn delete_inactive_connection(dbus_manager: &DBusNetworkManager, path: &str) -> Result<()> {
+    if get_connection_active_path(dbus_manager, path)?.is_some() {
+        bail!(ErrorKind::ConnectionActive(path.to_string()));
+    }
+
+    delete_connection(dbus_manager, path)
+}
+
+pub fn delete_connection_by_uuid(dbus_manager: &DBusNetworkManager, uuid: &str) -> Result<()> {
+    let path = dbus_manager.get_connection_by_uuid(uuid)?;
+
+    delete_connection(dbus_manager, &path)
+}
+
 pub fn get_active_connections(dbus_manager: &Rc<DBusNetworkManager>) -> Result<Vec<Connection>> {
     let active_paths = dbus_manager.get_active_connections()?;
 
@@ -241,6 +481,20 @@ pub fn get_active_connections(dbus_manager: &Rc<DBusNetworkManager>) -> Result<V
     Ok(connections)
 }
 
+pub fn list_active_connections(
+    dbus_manager: &Rc<DBusNetworkManager>,
+) -> Result<Vec<ActiveConnection>> {
+    let active_paths = dbus_manager.get_active_connections()?;
+
+    let mut active_connections = Vec::with_capacity(active_paths.len());
+
+    for active_path in &active_paths {
+        active_connections.push(dbus_manager.get_active_connection(active_path)?);
+    }
+
+    Ok(active_connections)
+}
+
 pub fn connect_to_access_point(
     dbus_manager: &Rc<DBusNetworkManager>,
     device_path: &str,
@@ -260,18 +514,42 @@ pub fn connect_to_access_point(
     Ok((connection, state))
 }
 
+pub fn connect_to_hidden_ssid<S>(
+    dbus_manager: &Rc<DBusNetworkManager>,
+    device_path: &str,
+    ssid: &S,
+    password: Option<&str>,
+) -> Result<(Connection, ConnectionState)>
+where
+    S: AsSsidSlice + ?Sized,
+{
+    let (path, _) = dbus_manager.connect_to_hidden_ssid(device_path, ssid, password)?;
+
+    let connection = Connection::init(dbus_manager, &path)?;
+
+    let state = wait(
+        &connection,
+        &ConnectionState::Activated,
+        dbus_manager.method_timeout(),
+    )?;
+
+    Ok((connection, state))
+}
+
 pub fn create_hotspot<S>(
     dbus_manager: &Rc<DBusNetworkManager>,
     device_path: &str,
-    interface: &str,
     ssid: &S,
     password: Option<&str>,
     address: Option<Ipv4Addr>,
+    band: Option<&str>,
+    channel: Option<u32>,
 ) -> Result<(Connection, ConnectionState)>
 where
     S: AsSsidSlice + ?Sized,
 {
-    let (path, _) = dbus_manager.create_hotspot(device_path, interface, ssid, password, address)?;
+    let (path, _) =
+        dbus_manager.create_hotspot(device_path, ssid, password, address, band, channel)?;
 
     let connection = Connection::init(dbus_manager, &path)?;
 
@@ -284,6 +562,50 @@ where
     Ok((connection, state))
 }
 
+pub fn create_gsm_connection(
+    dbus_manager: &Rc<DBusNetworkManager>,
+    device_path: &str,
+    config: &GsmConfig,
+) -> Result<(Connection, ConnectionState)> {
+    let (path, _) = dbus_manager.create_gsm_connection(device_path, config)?;
+
+    let connection = Connection::init(dbus_manager, &path)?;
+
+    let state = wait(
+        &connection,
+        &ConnectionState::Activated,
+        dbus_manager.method_timeout(),
+    )?;
+
+    Ok((connection, state))
+}
+
+/// Adds and activates a connection with `options`, also returning the result map of
+/// `AddAndActivateConnection2`, which is empty on Network Manager older than 1.16.
+pub fn add_and_activate_connection(
+    dbus_manager: &Rc<DBusNetworkManager>,
+    device_path: &str,
+    settings: &SettingsMap,
+    options: &VariantMap,
+) -> Result<(Connection, ConnectionState, VariantMap)> {
+    let (path, _, result) = dbus_manager.add_and_activate_connection_with_options(
+        settings,
+        device_path,
+        "/",
+        options,
+    )?;
+
+    let connection = Connection::init(dbus_manager, &path)?;
+
+    let state = wait(
+        &connection,
+        &ConnectionState::Activated,
+        dbus_manager.method_timeout(),
+    )?;
+
+    Ok((connection, state, result))
+}
+
 fn get_connection_active_path(
     dbus_manager: &DBusNetworkManager,
     connection_path: &str,
@@ -346,9 +668,35 @@ fn wait(
 
 #[cfg(test)]
 mod tests {
+    use std::sync::mpsc;
+
+    use dbus::Path;
+    use dbus::arg::Variant;
+    use dbus::tree::MethodErr;
+
+    use mock::MockService;
+
     use super::super::NetworkManager;
     use super::*;
 
+    #[test]
+    fn test_active_connection_state_reason_from_code() {
+        let reasons = [
+            (1_u32, ActiveConnectionStateReason::None),
+            (2, ActiveConnectionStateReason::UserDisconnected),
+            (6, ActiveConnectionStateReason::ConnectTimeout),
+            (9, ActiveConnectionStateReason::NoSecrets),
+            (10, ActiveConnectionStateReason::LoginFailed),
+            (14, ActiveConnectionStateReason::DeviceRemoved),
+            (0, ActiveConnectionStateReason::Unknown),
+            (100, ActiveConnectionStateReason::Unknown),
+        ];
+
+        for &(code, reason) in &reasons {
+            assert_eq!(ActiveConnectionStateReason::from(code), reason, "code {}", code);
+        }
+    }
+
     #[test]
     fn test_connection_enable_disable() {
         let manager = NetworkManager::new();
@@ -395,4 +743,178 @@ mod tests {
             ::std::thread::sleep(::std::time::Duration::from_secs(5));
         }
     }
+
+    const ACTIVE_PROFILE: &str = "/org/freedesktop/NetworkManager/Settings/1";
+    const INACTIVE_PROFILE: &str = "/org/freedesktop/NetworkManager/Settings/2";
+    const INACTIVE_UUID: &str = "0a0e6f42-8c4b-4f3e-b3a1-7d5c2e9f1b64";
+
+    /// Serves an active and an inactive wired profile, reporting the paths deleted via
+    /// `deleted`.
+    fn spawn_profiles(service: &'static str, deleted: mpsc::Sender<String>) -> MockService {
+        MockService::spawn(service, move |f, _| {
+            let active = "/org/freedesktop/NetworkManager/ActiveConnection/1";
+
+            let profile = |path: &'static str| {
+                let deleted = deleted.clone();
+
+                f.object_path(path, ()).add(
+                    f.interface("org.freedesktop.NetworkManager.Settings.Connection", ())
+                        .add_m(f.method("GetSettings", (), |m| {
+                            let mut connection: HashMap<&str, Variant<&str>> = HashMap::new();
+                            connection.insert("type", Variant("802-3-ethernet"));
+
+                            let mut settings = HashMap::new();
+                            settings.insert("connection", connection);
+
+                            Ok(vec![m.msg.method_return().append1(settings)])
+                        }))
+                        .add_m(f.method("Delete", (), move |m| {
+                            deleted.send(path.to_string()).unwrap();
+                            Ok(vec![m.msg.method_return()])
+                        })),
+                )
+            };
+
+            f.tree(())
+                .add(f.object_path("/org/freedesktop/NetworkManager", ()).add(
+                    f.interface("org.freedesktop.NetworkManager", ()).add_p(
+                        f.property::<Vec<Path>, _>("ActiveConnections", ())
+                            .on_get(move |i, _| {
+                                i.append(vec![Path::from(active)]);
+                                Ok(())
+                            }),
+                    ),
+                ))
+                .add(f.object_path(active, ()).add(
+                    f.interface("org.freedesktop.NetworkManager.Connection.Active", ())
+                        .add_p(f.property::<Path, _>("Connection", ()).on_get(|i, _| {
+                            i.append(Path::from(ACTIVE_PROFILE));
+                            Ok(())
+                        })),
+                ))
+                .add(f.object_path("/org/freedesktop/NetworkManager/Settings", ()).add(
+                    f.interface("org.freedesktop.NetworkManager.Settings", ())
+                        .add_m(f.method("GetConnectionByUuid", (), |m| {
+                            if m.msg.read1::<&str>()? == INACTIVE_UUID {
+                                let path = Path::from(INACTIVE_PROFILE);
+                                Ok(vec![m.msg.method_return().append1(path)])
+                            } else {
+                                Err(MethodErr::from((
+                                    "org.freedesktop.NetworkManager.Settings.InvalidConnection",
+                                    "No connection with the UUID was found.",
+                                )))
+                            }
+                        })),
+                ))
+                .add(profile(ACTIVE_PROFILE))
+                .add(profile(INACTIVE_PROFILE))
+        })
+    }
+
+    #[test]
+    fn test_delete_connection_by_uuid() {
+        const SERVICE: &str = "org.freedesktop.NetworkManager.Test.DeleteConnectionByUuid";
+
+        let (deleted_tx, deleted_rx) = mpsc::channel();
+        let _service = spawn_profiles(SERVICE, deleted_tx);
+        let dbus_manager = DBusNetworkManager::with_mock_service(SERVICE);
+
+        delete_connection_by_uuid(&dbus_manager, INACTIVE_UUID).unwrap();
+        assert_eq!(deleted_rx.recv().unwrap(), INACTIVE_PROFILE);
+
+        let unknown_uuid = "ffffffff-ffff-ffff-ffff-ffffffffffff";
+        match *delete_connection_by_uuid(&dbus_manager, unknown_uuid)
+            .unwrap_err()
+            .kind()
+        {
+            ErrorKind::DBusError(DBusError::NotFound(_)) => {},
+            ref other => panic!("expected not found, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_delete_connection_errors() {
+        const SERVICE: &str = "org.freedesktop.NetworkManager.Test.DeleteConnection";
+
+        let (deleted_tx, deleted_rx) = mpsc::channel();
+        let _service = spawn_profiles(SERVICE, deleted_tx);
+        let dbus_manager = DBusNetworkManager::with_mock_service(SERVICE);
+
+        match *delete_inactive_connection(&dbus_manager, ACTIVE_PROFILE)
+            .unwrap_err()
+            .kind()
+        {
+            ErrorKind::ConnectionActive(ref path) => assert_eq!(path, ACTIVE_PROFILE),
+            ref other => panic!("expected connection active, got {:?}", other),
+        }
+        assert!(deleted_rx.try_recv().is_err());
+
+        let gone = "/org/freedesktop/NetworkManager/Settings/3";
+        match *delete_connection(&dbus_manager, gone).unwrap_err().kind() {
+            ErrorKind::DBusError(DBusError::NotFound(_)) => {},
+            ref other => panic!("expected not found, got {:?}", other),
+        }
+        match *delete_inactive_connection(&dbus_manager, gone)
+            .unwrap_err()
+            .kind()
+        {
+            ErrorKind::DBusError(DBusError::NotFound(_)) => {},
+            ref other => panic!("expected not found, got {:?}", other),
+        }
+
+        assert!(deleted_rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_delete_active_connection() {
+        const SERVICE: &str = "org.freedesktop.NetworkManager.Test.DeleteActiveConnection";
+
+        let (deleted_tx, deleted_rx) = mpsc::channel();
+        let _service = spawn_profiles(SERVICE, deleted_tx);
+        let dbus_manager = Rc::new(DBusNetworkManager::with_mock_service(SERVICE));
+
+        let connection = Connection::init(&dbus_manager, ACTIVE_PROFILE).unwrap();
+        match *connection.delete_inactive().unwrap_err().kind() {
+            ErrorKind::ConnectionActive(ref path) => assert_eq!(path, ACTIVE_PROFILE),
+            ref other => panic!("expected connection active, got {:?}", other),
+        }
+
+        connection.delete().unwrap();
+        assert_eq!(deleted_rx.recv().unwrap(), ACTIVE_PROFILE);
+
+        delete_inactive_connection(&dbus_manager, INACTIVE_PROFILE).unwrap();
+        assert_eq!(deleted_rx.recv().unwrap(), INACTIVE_PROFILE);
+    }
+
+    #[test]
+    fn test_connection_id() {
+        const SERVICE: &str = "org.freedesktop.NetworkManager.Test.ConnectionId";
+        const UUID: &str = "5d3e3f28-0c1a-4b6e-9d13-3b8a2f1c7e40";
+
+        let _service = MockService::spawn(SERVICE, |f, _| {
+            f.tree(()).add(
+                f.object_path(INACTIVE_PROFILE, ()).add(
+                    f.interface("org.freedesktop.NetworkManager.Settings.Connection", ())
+                        .add_m(f.method("GetSettings", (), |m| {
+                            let mut connection: HashMap<&str, Variant<&str>> = HashMap::new();
+                            connection.insert("id", Variant("Office Ethernet"));
+                            connection.insert("uuid", Variant(UUID));
+                            connection.insert("type", Variant("802-3-ethernet"));
+
+                            let mut settings = HashMap::new();
+                            settings.insert("connection", connection);
+
+                            Ok(vec![m.msg.method_return().append1(settings)])
+                        })),
+                ),
+            )
+        });
+
+        let dbus_manager = Rc::new(DBusNetworkManager::with_mock_service(SERVICE));
+        let connection = Connection::init(&dbus_manager, INACTIVE_PROFILE).unwrap();
+
+        assert_eq!(connection.id(), "Office Ethernet");
+        assert_eq!(connection.uuid(), UUID);
+        assert_eq!(connection.settings().kind, "802-3-ethernet");
+    }
 }
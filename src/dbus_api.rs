@@ -1,18 +1,87 @@
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, VecDeque};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
 use dbus::Connection as DBusConnection;
-use dbus::{BusType, ConnPath, Message, Path};
-use dbus::arg::{Array, Get, Iter, RefArg, Variant};
+use dbus::{BusType, ConnPath, ConnectionItem, Message, MessageType, Path};
+use dbus::arg::{cast, Array, Get, Iter, RefArg, Variant};
 use dbus::stdintf::OrgFreedesktopDBusProperties;
 
-use errors::*;
+use errors::DBusError;
 
 const DEFAULT_TIMEOUT: u64 = 15;
 const RETRIES_ALLOWED: usize = 10;
+const MAX_TIMEOUT_MILLIS: u64 = 0x7fff_ffff;
+
+const PROPERTIES_INTERFACE: &str = "org.freedesktop.DBus.Properties";
+const PROPERTIES_CHANGED: &str = "PropertiesChanged";
+
+const MAX_PENDING_SIGNALS: usize = 256;
+
+/// How long to sleep between retries of a method call.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Backoff {
+    /// Sleep the same duration before every retry.
+    Fixed(Duration),
+    /// Start with `initial` and double the sleep after every retry, up to `max`.
+    Exponential { initial: Duration, max: Duration },
+}
+
+impl Backoff {
+    fn delay(&self, retry: usize) -> Duration {
+        match *self {
+            Backoff::Fixed(delay) => delay,
+            Backoff::Exponential { initial, max } => {
+                let factor = match 1u32.checked_shl(retry.saturating_sub(1) as u32) {
+                    Some(factor) => factor,
+                    None => return max,
+                };
+                match initial.checked_mul(factor) {
+                    Some(delay) if delay < max => delay,
+                    _ => max,
+                }
+            },
+        }
+    }
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Backoff::Fixed(Duration::from_secs(1))
+    }
+}
+
+/// What to do about a failed method call, as decided by a retry predicate.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RetryDecision {
+    /// Retry after the delay of the configured `Backoff`.
+    Retry,
+    /// Retry after the given delay instead of the backoff delay.
+    RetryAfter(Duration),
+    /// Return the error without retrying.
+    Fail,
+}
+
+type RetryPredicate = Box<Fn(&::dbus::Error) -> RetryDecision>;
+
+/// The outcome of a single attempt of a method call.
+enum Attempt {
+    Done(Result<Message, DBusError>),
+    Retry(Option<Duration>),
+}
 
 pub struct DBusApi {
-    connection: DBusConnection,
+    connection: Rc<DBusConnection>,
     method_timeout: u64,
     base: &'static str,
     method_retry_error_names: &'static [&'static str],
+    max_retries: usize,
+    backoff: Backoff,
+    retry_predicate: Option<RetryPredicate>,
+    dry_run: Cell<bool>,
+    signal_queue: RefCell<SignalQueue>,
+    property_cache: RefCell<Option<PropertyCache>>,
 }
 
 impl DBusApi {
@@ -20,9 +89,52 @@ impl DBusApi {
         base: &'static str,
         method_retry_error_names: &'static [&'static str],
         method_timeout: Option<u64>,
-    ) -> Self {
-        let connection = DBusConnection::get_private(BusType::System).unwrap();
+        max_retries: Option<usize>,
+        backoff: Option<Backoff>,
+    ) -> Result<Self, DBusError> {
+        DBusApi::with_bus_type(
+            BusType::System,
+            base,
+            method_retry_error_names,
+            method_timeout,
+            max_retries,
+            backoff,
+        )
+    }
+
+    pub fn with_bus_type(
+        bus_type: BusType,
+        base: &'static str,
+        method_retry_error_names: &'static [&'static str],
+        method_timeout: Option<u64>,
+        max_retries: Option<usize>,
+        backoff: Option<Backoff>,
+    ) -> Result<Self, DBusError> {
+        let connection = DBusConnection::get_private(bus_type).map_err(|e| {
+            let error = DBusError::from(e).with_context("Opening D-Bus connection failed");
+            error!("{}", error.message());
+            error
+        })?;
+
+        Ok(DBusApi::from_connection(
+            Rc::new(connection),
+            base,
+            method_retry_error_names,
+            method_timeout,
+            max_retries,
+            backoff,
+        ))
+    }
 
+    /// Uses an existing connection, which lets several wrappers share a single socket.
+    pub fn from_connection(
+        connection: Rc<DBusConnection>,
+        base: &'static str,
+        method_retry_error_names: &'static [&'static str],
+        method_timeout: Option<u64>,
+        max_retries: Option<usize>,
+        backoff: Option<Backoff>,
+    ) -> Self {
         let method_timeout = method_timeout.unwrap_or(DEFAULT_TIMEOUT);
 
         DBusApi {
@@ -30,14 +142,51 @@ impl DBusApi {
             method_timeout: method_timeout,
             base: base,
             method_retry_error_names: method_retry_error_names,
+            max_retries: max_retries.unwrap_or(RETRIES_ALLOWED),
+            backoff: backoff.unwrap_or_default(),
+            retry_predicate: None,
+            dry_run: Cell::new(false),
+            signal_queue: RefCell::new(SignalQueue::default()),
+            property_cache: RefCell::new(None),
         }
     }
 
+    /// Decides which failed method calls are retried with `predicate` instead of the list of
+    /// retryable error names. The maximum number of retries still applies.
+    pub fn set_retry_predicate<F>(&mut self, predicate: F)
+    where
+        F: Fn(&::dbus::Error) -> RetryDecision + 'static,
+    {
+        self.retry_predicate = Some(Box::new(predicate));
+    }
+
     pub fn method_timeout(&self) -> u64 {
         self.method_timeout
     }
 
-    pub fn call(&self, path: &str, interface: &str, method: &str) -> Result<Message> {
+    /// Logs method calls and property writes instead of sending them while `dry_run` is set.
+    ///
+    /// Dry-run method calls return a placeholder in place of the reply, which the `extract`
+    /// methods refuse with `DBusError::DryRun`. Property reads and `query` calls are still
+    /// sent.
+    pub fn set_dry_run(&self, dry_run: bool) {
+        self.dry_run.set(dry_run);
+    }
+
+    /// The underlying connection, for match rules and method calls not wrapped by this crate.
+    ///
+    /// The borrow is tied to `self`; callers that need to keep the connection beyond it should
+    /// open it themselves and pass it to `from_connection`.
+    pub fn connection(&self) -> &DBusConnection {
+        &self.connection
+    }
+
+    pub fn call(
+        &self,
+        path: &str,
+        interface: &str,
+        method: &str,
+    ) -> Result<Message, DBusError> {
         self.call_with_args(path, interface, method, &[])
     }
 
@@ -47,12 +196,69 @@ impl DBusApi {
         interface: &str,
         method: &str,
         args: &[&RefArg],
-    ) -> Result<Message> {
-        self.call_with_args_retry(path, interface, method, args)
+    ) -> Result<Message, DBusError> {
+        let timeout = Duration::from_secs(self.method_timeout);
+
+        self.call_with_args_timeout(path, interface, method, args, timeout)
+    }
+
+    /// Like `call_with_args`, but waits at most `timeout` for each reply instead of the
+    /// default method timeout.
+    pub fn call_with_args_timeout(
+        &self,
+        path: &str,
+        interface: &str,
+        method: &str,
+        args: &[&RefArg],
+        timeout: Duration,
+    ) -> Result<Message, DBusError> {
+        if self.dry_run.get() {
+            info!(
+                "Dry run: {}::{} method call on {} with {:?}",
+                interface, method, path, args
+            );
+
+            // An unsent message can not be replied to, so the call itself stands in for the
+            // reply and is recognized by `check_reply`
+            return Message::new_method_call(self.base, path, interface, method)
+                .map_err(DBusError::Other);
+        }
+
+        self.send_call(path, interface, method, args, timeout)
+    }
+
+    /// Like `call_with_args`, for methods that only read state, e.g. `GetDevices`; these are
+    /// sent in dry-run mode too.
+    pub fn query_with_args(
+        &self,
+        path: &str,
+        interface: &str,
+        method: &str,
+        args: &[&RefArg],
+    ) -> Result<Message, DBusError> {
+        let timeout = Duration::from_secs(self.method_timeout);
+
+        self.send_call(path, interface, method, args, timeout)
+    }
+
+    /// Like `query_with_args`, for methods without arguments.
+    pub fn query(&self, path: &str, interface: &str, method: &str) -> Result<Message, DBusError> {
+        self.query_with_args(path, interface, method, &[])
+    }
+
+    fn send_call(
+        &self,
+        path: &str,
+        interface: &str,
+        method: &str,
+        args: &[&RefArg],
+        timeout: Duration,
+    ) -> Result<Message, DBusError> {
+        self.call_with_args_retry(path, interface, method, args, timeout)
             .map_err(|e| {
                 let message = format!("{}::{} method call failed on {}", interface, method, path);
-                error!("{}", message);
-                e.chain_err(|| ErrorKind::DBusAPI(message))
+                error!("{}: {}", message, e.message());
+                e.with_context(&message)
             })
     }
 
@@ -62,30 +268,32 @@ impl DBusApi {
         interface: &str,
         method: &str,
         args: &[&RefArg],
-    ) -> Result<Message> {
-        let mut retries = 0;
-
-        loop {
-            if let Some(result) = self.create_and_send_message(path, interface, method, args) {
-                return result;
-            }
+        timeout: Duration,
+    ) -> Result<Message, DBusError> {
+        #[cfg(feature = "tracing")]
+        let span = ::tracing::debug_span!(
+            "dbus_method_call",
+            path = path,
+            interface = interface,
+            method = method,
+            retries = 0_u64,
+        );
+        #[cfg(feature = "tracing")]
+        let _entered = span.enter();
 
-            retries += 1;
+        retry(self.max_retries, self.backoff, |retries| {
+            if retries > 0 {
+                debug!(
+                    "Retrying {}::{} method call: retry #{}",
+                    interface, method, retries,
+                );
 
-            if retries == RETRIES_ALLOWED {
-                bail!(ErrorKind::DBusAPI(format!(
-                    "Method call failed after {} retries",
-                    RETRIES_ALLOWED
-                )));
+                #[cfg(feature = "tracing")]
+                span.record("retries", retries as u64);
             }
 
-            debug!(
-                "Retrying {}::{} method call: retry #{}",
-                interface, method, retries,
-            );
-
-            ::std::thread::sleep(::std::time::Duration::from_secs(1));
-        }
+            self.create_and_send_message(path, interface, method, args, timeout)
+        })
     }
 
     fn create_and_send_message(
@@ -94,89 +302,206 @@ impl DBusApi {
         interface: &str,
         method: &str,
         args: &[&RefArg],
-    ) -> Option<Result<Message>> {
+        timeout: Duration,
+    ) -> Attempt {
         match Message::new_method_call(self.base, path, interface, method) {
             Ok(mut message) => {
                 if !args.is_empty() {
                     message = message.append_ref(args);
                 }
 
-                self.send_message_checked(message)
+                self.send_message_checked(message, timeout)
             },
-            Err(details) => Some(Err(ErrorKind::DBusAPI(details).into())),
+            Err(details) => Attempt::Done(Err(DBusError::Other(details))),
         }
     }
 
-    fn send_message_checked(&self, message: Message) -> Option<Result<Message>> {
+    fn send_message_checked(&self, message: Message, timeout: Duration) -> Attempt {
         match self.connection
-            .send_with_reply_and_block(message, self.method_timeout as i32 * 1000)
+            .send_with_reply_and_block(message, timeout_millis(timeout))
         {
-            Ok(response) => Some(Ok(response)),
-            Err(e) => {
-                {
-                    let name = e.name();
-                    for error_name in self.method_retry_error_names {
-                        if name == Some(error_name) {
-                            debug!("Should retry D-Bus method call: {}", error_name);
+            Ok(response) => Attempt::Done(Ok(response)),
+            Err(e) => match self.retry_decision(&e) {
+                RetryDecision::Retry => Attempt::Retry(None),
+                RetryDecision::RetryAfter(delay) => Attempt::Retry(Some(delay)),
+                RetryDecision::Fail => Attempt::Done(Err(DBusError::from(e))),
+            },
+        }
+    }
 
-                            return None;
-                        }
-                    }
-                }
+    fn retry_decision(&self, error: &::dbus::Error) -> RetryDecision {
+        if let Some(ref predicate) = self.retry_predicate {
+            return predicate(error);
+        }
 
-                Some(Err(Error::from(e)))
-            },
+        if should_retry(error.name(), self.method_retry_error_names) {
+            RetryDecision::Retry
+        } else {
+            RetryDecision::Fail
         }
     }
 
-    pub fn property<T>(&self, path: &str, interface: &str, name: &str) -> Result<T>
+    pub fn property<T>(&self, path: &str, interface: &str, name: &str) -> Result<T, DBusError>
     where
         DBusApi: VariantTo<T>,
     {
-        let property_error = |details: &str, err: bool| {
-            let message = format!(
-                "Get {}::{} property failed on {}: {}",
-                interface, name, path, details
+        #[cfg(feature = "tracing")]
+        let _entered = ::tracing::debug_span!(
+            "dbus_property",
+            path = path,
+            interface = interface,
+            name = name,
+        ).entered();
+
+        let variant = self.variant_property(path, interface, name)?;
+        property_value(&variant, path, interface, name)
+    }
+
+    /// Serves repeated reads through `cached_property` from a cache for `ttl`.
+    ///
+    /// Cached values are dropped as soon as the service reports a change of them with
+    /// `PropertiesChanged`, so a value is never served after a change was received.
+    pub fn enable_property_cache(&self, ttl: Duration) -> Result<(), DBusError> {
+        if self.property_cache.borrow().is_none() {
+            let rule = format!(
+                "type='signal', sender='{}', interface='{}', member='{}'",
+                self.base, PROPERTIES_INTERFACE, PROPERTIES_CHANGED
             );
-            if err {
-                error!("{}", message);
-            } else {
-                debug!("{}", message);
+
+            self.connection.add_match(&rule).map_err(|e| {
+                DBusError::from(e).with_context("Subscribing to property changes failed")
+            })?;
+        }
+
+        *self.property_cache.borrow_mut() = Some(PropertyCache::new(ttl));
+
+        Ok(())
+    }
+
+    /// Reads a property, only dispatching a `Get` if the property cache has no fresh value.
+    ///
+    /// Without `enable_property_cache` this is the same as `property`.
+    pub fn cached_property<T>(
+        &self,
+        path: &str,
+        interface: &str,
+        name: &str,
+    ) -> Result<T, DBusError>
+    where
+        DBusApi: VariantTo<T>,
+    {
+        if self.property_cache.borrow().is_none() {
+            return self.property(path, interface, name);
+        }
+
+        self.drain_signals();
+
+        let key = (path.to_string(), interface.to_string(), name.to_string());
+
+        if let Some(ref cache) = *self.property_cache.borrow() {
+            if let Some(result) =
+                cache.with_fresh(&key, |variant| property_value(variant, path, interface, name))
+            {
+                return result;
             }
-            ErrorKind::DBusAPI(message)
-        };
+        }
 
-        let path = self.with_path(path);
+        let variant = self.variant_property(path, interface, name)?;
+        let result = property_value(&variant, path, interface, name);
+        if let Some(ref cache) = *self.property_cache.borrow() {
+            cache.insert(key, variant);
+        }
+        result
+    }
 
-        match path.get(interface, name) {
-            Ok(variant) => match DBusApi::variant_to(&variant) {
-                Some(data) => Ok(data),
-                None => bail!(property_error("wrong property type", true)),
-            },
-            Err(e) => {
-                let dbus_err = match e.message() {
-                    Some(details) => property_error(details, false),
-                    None => property_error("no details", false),
-                };
-                Err(e).chain_err(|| dbus_err)
-            },
+    fn variant_property(
+        &self,
+        path: &str,
+        interface: &str,
+        name: &str,
+    ) -> Result<Variant<Box<RefArg>>, DBusError> {
+        self.with_path(path)
+            .get(interface, name)
+            .map_err(|e| property_error(DBusError::from(e), path, interface, name, false))
+    }
+
+    /// Reads all properties of `interface` in a single round-trip.
+    pub fn get_all(
+        &self,
+        path: &str,
+        interface: &str,
+    ) -> Result<HashMap<String, Variant<Box<RefArg>>>, DBusError> {
+        self.with_path(path).get_all(interface).map_err(|e| {
+            let message = format!("Get all {} properties failed on {}", interface, path);
+            let error = DBusError::from(e).with_context(&message);
+            error!("{}", error.message());
+            error
+        })
+    }
+
+    /// Reads the properties `names` of `interface` with a single `GetAll` round-trip.
+    pub fn get_many(
+        &self,
+        path: &str,
+        interface: &str,
+        names: &[&str],
+    ) -> Result<Properties, DBusError> {
+        let mut values = self.get_all(path, interface)?;
+        values.retain(|name, _| names.contains(&name.as_str()));
+
+        Ok(Properties {
+            path: path.to_string(),
+            interface: interface.to_string(),
+            values: values,
+        })
+    }
+
+    pub fn set_property<T>(
+        &self,
+        path: &str,
+        interface: &str,
+        name: &str,
+        value: T,
+    ) -> Result<(), DBusError>
+    where
+        T: RefArg + 'static,
+    {
+        let value = Variant(Box::new(value) as Box<RefArg>);
+
+        if self.dry_run.get() {
+            info!(
+                "Dry run: set {}::{} property on {} to {:?}",
+                interface, name, path, value
+            );
+            return Ok(());
         }
+
+        self.with_path(path).set(interface, name, value).map_err(|e| {
+            let message = format!("Set {}::{} property failed on {}", interface, name, path);
+            let error = DBusError::from(e).with_context(&message);
+            error!("{}", error.message());
+            error
+        })
     }
 
-    pub fn extract<'a, T>(&self, response: &'a Message) -> Result<T>
+    pub fn extract<'a, T>(&self, response: &'a Message) -> Result<T, DBusError>
     where
         T: Get<'a>,
     {
+        check_reply(response)?;
+
         response
             .get1()
-            .ok_or_else(|| ErrorKind::DBusAPI("Wrong response type".into()).into())
+            .ok_or_else(|| DBusError::WrongType("Wrong response type".into()))
     }
 
-    pub fn extract_two<'a, T1, T2>(&self, response: &'a Message) -> Result<(T1, T2)>
+    pub fn extract_two<'a, T1, T2>(&self, response: &'a Message) -> Result<(T1, T2), DBusError>
     where
         T1: Get<'a>,
         T2: Get<'a>,
     {
+        check_reply(response)?;
+
         let (first, second) = response.get2();
 
         if let Some(first) = first {
@@ -185,112 +510,1877 @@ impl DBusApi {
             }
         }
 
-        bail!(ErrorKind::DBusAPI("Wrong response type".into()))
+        bail!(DBusError::WrongType("Wrong response type".into()))
     }
 
-    fn with_path<'a, P: Into<Path<'a>>>(&'a self, path: P) -> ConnPath<&'a DBusConnection> {
-        self.connection
-            .with_path(self.base, path, self.method_timeout as i32 * 1000)
+    pub fn extract_three<'a, T1, T2, T3>(
+        &self,
+        response: &'a Message,
+    ) -> Result<(T1, T2, T3), DBusError>
+    where
+        T1: Get<'a>,
+        T2: Get<'a>,
+        T3: Get<'a>,
+    {
+        check_reply(response)?;
+
+        match response.get3() {
+            (Some(first), Some(second), Some(third)) => Ok((first, second, third)),
+            _ => bail!(DBusError::WrongType("Wrong response type".into())),
+        }
     }
-}
 
-pub trait VariantTo<T> {
-    fn variant_to(value: &Variant<Box<RefArg>>) -> Option<T>;
-}
+    /// Subscribes to the `interface::member` signals emitted by the service for `path`.
+    ///
+    /// Only signals emitted after subscribing are received. Callers waiting for a change should
+    /// subscribe before reading the current value or starting the change, or a signal emitted
+    /// in between is missed.
+    pub fn signals(
+        &self,
+        path: &str,
+        interface: &str,
+        member: &str,
+    ) -> Result<Signals, DBusError> {
+        let rule = self.subscribe(path, interface, member)?;
 
-impl VariantTo<String> for DBusApi {
-    fn variant_to(value: &Variant<Box<RefArg>>) -> Option<String> {
-        value.0.as_str().and_then(|v| Some(v.to_string()))
+        Ok(Signals {
+            dbus: self,
+            path: path.to_string(),
+            interface: interface.to_string(),
+            member: member.to_string(),
+            rule: rule,
+        })
     }
-}
 
-impl VariantTo<i64> for DBusApi {
-    fn variant_to(value: &Variant<Box<RefArg>>) -> Option<i64> {
-        value.0.as_i64()
+    /// Creates a router for dispatching signals of several subscriptions from a single loop.
+    pub fn signal_router(&self) -> SignalRouter {
+        SignalRouter {
+            dbus: self,
+            routes: Vec::new(),
+        }
     }
-}
 
-impl VariantTo<u32> for DBusApi {
-    fn variant_to(value: &Variant<Box<RefArg>>) -> Option<u32> {
-        value.0.as_i64().and_then(|v| Some(v as u32))
+    /// Subscribes to `PropertiesChanged` signals emitted by the service for `path`, see
+    /// `signals` for when to subscribe.
+    pub fn properties_changed(&self, path: &str) -> Result<PropertiesChanged, DBusError> {
+        Ok(PropertiesChanged {
+            signals: self.signals(path, PROPERTIES_INTERFACE, PROPERTIES_CHANGED)?,
+        })
     }
-}
 
-impl VariantTo<bool> for DBusApi {
-    fn variant_to(value: &Variant<Box<RefArg>>) -> Option<bool> {
-        value.0.as_i64().and_then(|v| Some(v == 0))
+    /// Adds the match rule for `interface::member` signals on `path`, returning the rule.
+    fn subscribe(&self, path: &str, interface: &str, member: &str) -> Result<String, DBusError> {
+        let rule = format!(
+            "type='signal', sender='{}', interface='{}', member='{}', path='{}'",
+            self.base, interface, member, path
+        );
+
+        self.connection.add_match(&rule).map_err(|e| {
+            let message = format!("Subscribing to {}::{} on {} failed", interface, member, path);
+            DBusError::from(e).with_context(&message)
+        })?;
+
+        self.signal_queue.borrow_mut().subscriptions.push((
+            path.to_string(),
+            interface.to_string(),
+            member.to_string(),
+        ));
+
+        Ok(rule)
     }
-}
 
-impl VariantTo<Vec<String>> for DBusApi {
-    fn variant_to(value: &Variant<Box<RefArg>>) -> Option<Vec<String>> {
-        let mut result = Vec::new();
+    /// Removes a subscription added by `subscribe`, dropping the pending signals that no other
+    /// subscription matches.
+    fn unsubscribe(&self, path: &str, interface: &str, member: &str, rule: &str) {
+        if let Err(e) = self.connection.remove_match(rule) {
+            debug!("Removing match rule failed: {}", DBusError::from(e));
+        }
 
-        if let Some(list) = value.0.as_iter() {
-            for element in list {
-                if let Some(string) = element.as_str() {
-                    result.push(string.to_string());
-                } else {
-                    return None;
-                }
+        let mut queue = self.signal_queue.borrow_mut();
+        let SignalQueue {
+            ref mut subscriptions,
+            ref mut pending,
+        } = *queue;
+
+        let position = subscriptions
+            .iter()
+            .position(|key| key.0 == path && key.1 == interface && key.2 == member);
+        if let Some(index) = position {
+            subscriptions.remove(index);
+        }
+
+        pending.retain(|signal| is_subscribed(subscriptions, signal));
+    }
+
+    /// Waits for the next signal accepted by `wanted`, giving up at `deadline` if there is one.
+    ///
+    /// Other signals received in the meantime are queued for the subscriptions they match, so
+    /// that `Signals` and a `SignalRouter` can take turns reading the connection.
+    fn receive_signal<F>(&self, deadline: Option<Instant>, wanted: F) -> Option<Message>
+    where
+        F: Fn(&Message) -> bool,
+    {
+        {
+            let mut queue = self.signal_queue.borrow_mut();
+
+            if let Some(index) = queue.pending.iter().position(&wanted) {
+                return queue.pending.remove(index);
             }
+        }
 
-            Some(result)
-        } else {
-            None
+        loop {
+            let timeout_ms = match deadline {
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return None;
+                    }
+                    timeout_millis(deadline - now)
+                },
+                None => -1,
+            };
+
+            match self.connection.iter(timeout_ms).next() {
+                Some(ConnectionItem::Signal(signal)) => {
+                    self.observe_signal(&signal);
+                    if wanted(&signal) {
+                        return Some(signal);
+                    }
+                    self.queue_signal(signal);
+                },
+                Some(_) => {},
+                None => return None,
+            }
         }
     }
-}
 
-impl VariantTo<Vec<u8>> for DBusApi {
-    fn variant_to(value: &Variant<Box<RefArg>>) -> Option<Vec<u8>> {
-        let mut result = Vec::new();
+    /// Reads the signals already sent to the connection without waiting, queueing them for
+    /// their subscriptions.
+    fn drain_signals(&self) {
+        loop {
+            match self.connection.iter(0).next() {
+                Some(ConnectionItem::Signal(signal)) => {
+                    self.observe_signal(&signal);
+                    self.queue_signal(signal);
+                },
+                Some(ConnectionItem::Nothing) | None => return,
+                Some(_) => {},
+            }
+        }
+    }
 
-        if let Some(list) = value.0.as_iter() {
-            for element in list {
-                if let Some(value) = element.as_i64() {
-                    result.push(value as u8);
-                } else {
-                    return None;
+    /// Drops the cached values of the properties a `PropertiesChanged` signal reports.
+    fn observe_signal(&self, signal: &Message) {
+        if let Some(ref cache) = *self.property_cache.borrow() {
+            let path = match signal.path() {
+                Some(path) => path,
+                None => return,
+            };
+
+            if signal.interface() == Some(PROPERTIES_INTERFACE.into())
+                && signal.member() == Some(PROPERTIES_CHANGED.into())
+            {
+                if let Some(change) = parse_properties_changed(signal) {
+                    cache.apply(&path, &change);
                 }
             }
+        }
+    }
 
-            Some(result)
-        } else {
-            None
+    fn queue_signal(&self, signal: Message) {
+        let mut queue = self.signal_queue.borrow_mut();
+
+        if !is_subscribed(&queue.subscriptions, &signal) {
+            return;
+        }
+
+        if queue.pending.len() >= MAX_PENDING_SIGNALS {
+            debug!("Dropping the oldest of {} pending signals", queue.pending.len());
+            queue.pending.pop_front();
         }
+
+        queue.pending.push_back(signal);
+    }
+
+    fn with_path<'a, P: Into<Path<'a>>>(&'a self, path: P) -> ConnPath<&'a DBusConnection> {
+        self.connection
+            .with_path(self.base, path, self.method_timeout as i32 * 1000)
+    }
+}
+
+/// Refuses the placeholder returned in place of a reply in dry-run mode.
+fn check_reply(response: &Message) -> Result<(), DBusError> {
+    if response.msg_type() != MessageType::MethodCall {
+        return Ok(());
     }
+
+    let method = response.member().map(|m| m.to_string()).unwrap_or_default();
+    bail!(DBusError::DryRun(format!(
+        "{} was not sent in dry-run mode, so it has no reply",
+        method
+    )))
 }
 
-pub fn extract<'a, T>(var: &mut Variant<Iter<'a>>) -> Result<T>
+fn property_value<T>(
+    variant: &Variant<Box<RefArg>>,
+    path: &str,
+    interface: &str,
+    name: &str,
+) -> Result<T, DBusError>
 where
-    T: Get<'a>,
+    DBusApi: VariantTo<T>,
 {
-    var.0
-        .get::<T>()
-        .ok_or_else(|| ErrorKind::DBusAPI(format!("Variant type does not match: {:?}", var)).into())
+    DBusApi::variant_to(variant).ok_or_else(|| {
+        let error = DBusError::WrongType("wrong property type".into());
+        property_error(error, path, interface, name, true)
+    })
 }
 
-pub fn variant_iter_to_vec_u8(var: &mut Variant<Iter>) -> Result<Vec<u8>> {
-    let array_option = &var.0.get::<Array<u8, _>>();
-
-    if let Some(array) = *array_option {
-        Ok(array.collect())
+fn property_error(
+    error: DBusError,
+    path: &str,
+    interface: &str,
+    name: &str,
+    err: bool,
+) -> DBusError {
+    let error = error.with_context(&format!(
+        "Get {}::{} property failed on {}",
+        interface, name, path
+    ));
+    if err {
+        error!("{}", error.message());
     } else {
-        bail!(ErrorKind::DBusAPI(format!(
-            "Variant not an array: {:?}",
-            var
-        )))
+        debug!("{}", error.message());
     }
+    error
 }
 
-pub fn path_to_string(path: &Path) -> Result<String> {
-    if let Ok(slice) = path.as_cstr().to_str() {
-        Ok(slice.to_string())
-    } else {
-        bail!(ErrorKind::DBusAPI(format!(
-            "Path not a UTF-8 string: {:?}",
-            path
-        )))
+/// Properties of a single object and interface read with `DBusApi::get_many`.
+pub struct Properties {
+    path: String,
+    interface: String,
+    values: HashMap<String, Variant<Box<RefArg>>>,
+}
+
+impl Properties {
+    pub fn get<T>(&self, name: &str) -> Result<T, DBusError>
+    where
+        DBusApi: VariantTo<T>,
+    {
+        match self.values.get(name) {
+            Some(variant) => property_value(variant, &self.path, &self.interface, name),
+            None => Err(property_error(
+                DBusError::UnknownMethod("property not returned".into()),
+                &self.path,
+                &self.interface,
+                name,
+                true,
+            )),
+        }
+    }
+
+    /// Whether the service returned `name`, e.g. to tell properties missing on older daemons.
+    pub fn contains(&self, name: &str) -> bool {
+        self.values.contains_key(name)
+    }
+}
+
+type PropertyKey = (String, String, String);
+type PropertyEntry = (Instant, Variant<Box<RefArg>>);
+
+/// Memoized property values for `DBusApi::cached_property`.
+///
+/// A value is read again once it is older than the TTL, or after a `PropertiesChanged` signal
+/// for it has been passed to `apply`.
+struct PropertyCache {
+    ttl: Duration,
+    entries: RefCell<HashMap<PropertyKey, PropertyEntry>>,
+}
+
+impl PropertyCache {
+    fn new(ttl: Duration) -> Self {
+        PropertyCache {
+            ttl: ttl,
+            entries: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// Drops the cached values of the properties changed or invalidated on `path`.
+    fn apply(&self, path: &str, change: &PropertiesChange) {
+        let mut entries = self.entries.borrow_mut();
+
+        for name in change.changed.keys().chain(change.invalidated.iter()) {
+            entries.remove(&(path.to_string(), change.interface.clone(), name.clone()));
+        }
+    }
+
+    fn with_fresh<R, F>(&self, key: &PropertyKey, f: F) -> Option<R>
+    where
+        F: FnOnce(&Variant<Box<RefArg>>) -> R,
+    {
+        let mut entries = self.entries.borrow_mut();
+
+        let fresh = match entries.get(key) {
+            Some(&(read, _)) => read.elapsed() < self.ttl,
+            None => return None,
+        };
+
+        if fresh {
+            entries.get(key).map(|entry| f(&entry.1))
+        } else {
+            entries.remove(key);
+            None
+        }
+    }
+
+    fn insert(&self, key: PropertyKey, variant: Variant<Box<RefArg>>) {
+        self.entries
+            .borrow_mut()
+            .insert(key, (Instant::now(), variant));
+    }
+}
+
+/// The properties of `interface` that changed in a single `PropertiesChanged` signal.
+#[derive(Debug)]
+pub struct PropertiesChange {
+    pub interface: String,
+    pub changed: HashMap<String, Variant<Box<RefArg>>>,
+    pub invalidated: Vec<String>,
+}
+
+type SignalKey = (String, String, String);
+
+/// Signals received while waiting for another subscription, kept until read by the
+/// subscription they match.
+#[derive(Default)]
+struct SignalQueue {
+    subscriptions: Vec<SignalKey>,
+    pending: VecDeque<Message>,
+}
+
+fn is_subscribed(subscriptions: &[SignalKey], signal: &Message) -> bool {
+    subscriptions
+        .iter()
+        .any(|key| is_signal(signal, &key.0, &key.1, &key.2))
+}
+
+/// A subscription to a single signal on a single object path.
+///
+/// Iterating blocks until the next signal arrives. The match rule is removed on drop.
+pub struct Signals<'a> {
+    dbus: &'a DBusApi,
+    path: String,
+    interface: String,
+    member: String,
+    rule: String,
+}
+
+impl<'a> Signals<'a> {
+    /// Waits at most `timeout` for the next signal.
+    pub fn next_timeout(&mut self, timeout: Duration) -> Option<Message> {
+        self.receive(Some(Instant::now() + timeout))
+    }
+
+    fn receive(&self, deadline: Option<Instant>) -> Option<Message> {
+        self.dbus.receive_signal(deadline, |signal| {
+            is_signal(signal, &self.path, &self.interface, &self.member)
+        })
+    }
+}
+
+impl<'a> Iterator for Signals<'a> {
+    type Item = Message;
+
+    /// Blocks until the next signal arrives, ending only if the connection closes.
+    fn next(&mut self) -> Option<Message> {
+        self.receive(None)
+    }
+}
+
+impl<'a> Drop for Signals<'a> {
+    fn drop(&mut self) {
+        self.dbus
+            .unsubscribe(&self.path, &self.interface, &self.member, &self.rule);
+    }
+}
+
+struct Route<'a> {
+    path: String,
+    interface: String,
+    member: String,
+    rule: String,
+    handler: Box<FnMut(&Message) + 'a>,
+}
+
+impl<'a> Route<'a> {
+    fn matches(&self, signal: &Message) -> bool {
+        is_signal(signal, &self.path, &self.interface, &self.member)
+    }
+}
+
+/// Dispatches incoming signals to the handler registered for their path, interface and member.
+///
+/// Every registration adds its own match rule, which is removed again on `unregister` or
+/// drop. The router shares the connection with `Signals` subscriptions, e.g. those of the
+/// `wait_for_*` helpers: signals for the routes that arrive while a subscription waits are
+/// dispatched by the next run, and signals for a subscription that arrive while running are
+/// kept for it.
+pub struct SignalRouter<'a> {
+    dbus: &'a DBusApi,
+    routes: Vec<Route<'a>>,
+}
+
+impl<'a> SignalRouter<'a> {
+    /// Calls `handler` with every `interface::member` signal emitted for `path`, replacing the
+    /// handler registered for it before.
+    pub fn register<F>(
+        &mut self,
+        path: &str,
+        interface: &str,
+        member: &str,
+        handler: F,
+    ) -> Result<(), DBusError>
+    where
+        F: FnMut(&Message) + 'a,
+    {
+        self.unregister(path, interface, member);
+
+        let rule = self.dbus.subscribe(path, interface, member)?;
+
+        self.routes.push(Route {
+            path: path.to_string(),
+            interface: interface.to_string(),
+            member: member.to_string(),
+            rule: rule,
+            handler: Box::new(handler),
+        });
+
+        Ok(())
+    }
+
+    /// Removes the handler for `interface::member` on `path`, returning whether there was one.
+    pub fn unregister(&mut self, path: &str, interface: &str, member: &str) -> bool {
+        let position = self.routes.iter().position(|route| {
+            route.path == path && route.interface == interface && route.member == member
+        });
+
+        match position {
+            Some(index) => {
+                let route = self.routes.remove(index);
+                self.dbus
+                    .unsubscribe(&route.path, &route.interface, &route.member, &route.rule);
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Dispatches the signals arriving within `timeout`, returning how many were handled.
+    pub fn run_timeout(&mut self, timeout: Duration) -> usize {
+        let deadline = Instant::now() + timeout;
+        let mut dispatched = 0;
+
+        while let Some(signal) = self.receive(Some(deadline)) {
+            self.dispatch(&signal);
+            dispatched += 1;
+        }
+
+        dispatched
+    }
+
+    /// Dispatches signals until the connection closes, blocking the calling thread.
+    pub fn run(&mut self) {
+        while let Some(signal) = self.receive(None) {
+            self.dispatch(&signal);
+        }
+    }
+
+    fn receive(&self, deadline: Option<Instant>) -> Option<Message> {
+        let routes = &self.routes;
+
+        self.dbus.receive_signal(deadline, |signal| {
+            routes.iter().any(|route| route.matches(signal))
+        })
+    }
+
+    fn dispatch(&mut self, signal: &Message) {
+        if let Some(route) = self.routes.iter_mut().find(|route| route.matches(signal)) {
+            (route.handler)(signal);
+        }
+    }
+}
+
+impl<'a> Drop for SignalRouter<'a> {
+    fn drop(&mut self) {
+        for route in &self.routes {
+            self.dbus
+                .unsubscribe(&route.path, &route.interface, &route.member, &route.rule);
+        }
+    }
+}
+
+/// A subscription to `PropertiesChanged` signals on a single object path.
+pub struct PropertiesChanged<'a> {
+    signals: Signals<'a>,
+}
+
+impl<'a> PropertiesChanged<'a> {
+    /// Waits at most `timeout` for the next change.
+    pub fn next_timeout(&mut self, timeout: Duration) -> Option<PropertiesChange> {
+        let deadline = Instant::now() + timeout;
+
+        loop {
+            let now = Instant::now();
+            if now >= deadline {
+                return None;
+            }
+
+            let signal = self.signals.next_timeout(deadline - now)?;
+
+            if let Some(change) = parse_properties_changed(&signal) {
+                return Some(change);
+            }
+        }
+    }
+}
+
+impl<'a> Iterator for PropertiesChanged<'a> {
+    type Item = PropertiesChange;
+
+    fn next(&mut self) -> Option<PropertiesChange> {
+        loop {
+            let signal = self.signals.next()?;
+
+            if let Some(change) = parse_properties_changed(&signal) {
+                return Some(change);
+            }
+        }
+    }
+}
+
+fn is_signal(message: &Message, path: &str, interface: &str, member: &str) -> bool {
+    message.path() == Some(path.into()) && message.interface() == Some(interface.into())
+        && message.member() == Some(member.into())
+}
+
+/// Decodes the arguments of a `PropertiesChanged` signal.
+pub fn parse_properties_changed(message: &Message) -> Option<PropertiesChange> {
+    match message.get3::<&str, HashMap<String, Variant<Box<RefArg>>>, Vec<String>>() {
+        (Some(interface), Some(changed), invalidated) => Some(PropertiesChange {
+            interface: interface.to_string(),
+            changed: changed,
+            invalidated: invalidated.unwrap_or_else(Vec::new),
+        }),
+        _ => None,
+    }
+}
+
+pub trait VariantTo<T> {
+    fn variant_to(value: &Variant<Box<RefArg>>) -> Option<T>;
+}
+
+impl VariantTo<String> for DBusApi {
+    fn variant_to(value: &Variant<Box<RefArg>>) -> Option<String> {
+        value.0.as_str().and_then(|v| Some(v.to_string()))
+    }
+}
+
+impl VariantTo<i64> for DBusApi {
+    fn variant_to(value: &Variant<Box<RefArg>>) -> Option<i64> {
+        value.0.as_i64()
+    }
+}
+
+impl VariantTo<u8> for DBusApi {
+    fn variant_to(value: &Variant<Box<RefArg>>) -> Option<u8> {
+        value.0.as_i64().and_then(|v| {
+            if i64::from(v as u8) == v {
+                Some(v as u8)
+            } else {
+                None
+            }
+        })
+    }
+}
+
+impl VariantTo<u32> for DBusApi {
+    fn variant_to(value: &Variant<Box<RefArg>>) -> Option<u32> {
+        value.0.as_i64().and_then(|v| Some(v as u32))
+    }
+}
+
+impl VariantTo<u64> for DBusApi {
+    fn variant_to(value: &Variant<Box<RefArg>>) -> Option<u64> {
+        match cast::<u64>(&*value.0) {
+            Some(v) => Some(*v),
+            None => value.0.as_i64().map(|v| v as u64),
+        }
+    }
+}
+
+impl VariantTo<i32> for DBusApi {
+    fn variant_to(value: &Variant<Box<RefArg>>) -> Option<i32> {
+        value.0.as_i64().and_then(|v| {
+            if i64::from(v as i32) == v {
+                Some(v as i32)
+            } else {
+                None
+            }
+        })
+    }
+}
+
+impl VariantTo<f64> for DBusApi {
+    fn variant_to(value: &Variant<Box<RefArg>>) -> Option<f64> {
+        cast::<f64>(&*value.0).cloned()
+    }
+}
+
+impl VariantTo<bool> for DBusApi {
+    fn variant_to(value: &Variant<Box<RefArg>>) -> Option<bool> {
+        value.0.as_i64().map(|v| v != 0)
+    }
+}
+
+impl VariantTo<Vec<String>> for DBusApi {
+    fn variant_to(value: &Variant<Box<RefArg>>) -> Option<Vec<String>> {
+        let mut result = Vec::new();
+
+        if let Some(list) = value.0.as_iter() {
+            for element in list {
+                if let Some(string) = element.as_str() {
+                    result.push(string.to_string());
+                } else {
+                    return None;
+                }
+            }
+
+            Some(result)
+        } else {
+            None
+        }
+    }
+}
+
+impl VariantTo<Vec<u8>> for DBusApi {
+    fn variant_to(value: &Variant<Box<RefArg>>) -> Option<Vec<u8>> {
+        let mut result = Vec::new();
+
+        if let Some(list) = value.0.as_iter() {
+            for element in list {
+                if let Some(value) = element.as_i64() {
+                    result.push(value as u8);
+                } else {
+                    return None;
+                }
+            }
+
+            Some(result)
+        } else {
+            None
+        }
+    }
+}
+
+impl VariantTo<Vec<u32>> for DBusApi {
+    fn variant_to(value: &Variant<Box<RefArg>>) -> Option<Vec<u32>> {
+        let mut result = Vec::new();
+
+        if let Some(list) = value.0.as_iter() {
+            for element in list {
+                if let Some(value) = element.as_i64() {
+                    result.push(value as u32);
+                } else {
+                    return None;
+                }
+            }
+
+            Some(result)
+        } else {
+            None
+        }
+    }
+}
+
+impl VariantTo<Vec<Vec<u8>>> for DBusApi {
+    fn variant_to(value: &Variant<Box<RefArg>>) -> Option<Vec<Vec<u8>>> {
+        let mut result = Vec::new();
+
+        if let Some(list) = value.0.as_iter() {
+            for element in list {
+                if &*element.signature() != "ay" {
+                    return None;
+                }
+
+                let mut bytes = Vec::new();
+
+                if let Some(inner) = element.as_iter() {
+                    for byte in inner {
+                        if let Some(byte) = byte.as_i64() {
+                            bytes.push(byte as u8);
+                        } else {
+                            return None;
+                        }
+                    }
+                }
+
+                result.push(bytes);
+            }
+
+            Some(result)
+        } else {
+            None
+        }
+    }
+}
+
+impl VariantTo<HashMap<String, String>> for DBusApi {
+    fn variant_to(value: &Variant<Box<RefArg>>) -> Option<HashMap<String, String>> {
+        if !value.0.signature().starts_with("a{") {
+            return None;
+        }
+
+        let mut result = HashMap::new();
+
+        if let Some(mut list) = value.0.as_iter() {
+            while let (Some(key), Some(element)) = (list.next(), list.next()) {
+                if let (Some(key), Some(string)) = (key.as_str(), element.as_str()) {
+                    result.insert(key.to_string(), string.to_string());
+                } else {
+                    return None;
+                }
+            }
+
+            Some(result)
+        } else {
+            None
+        }
+    }
+}
+
+pub fn extract<'a, T>(var: &mut Variant<Iter<'a>>) -> Result<T, DBusError>
+where
+    T: Get<'a>,
+{
+    var.0
+        .get::<T>()
+        .ok_or_else(|| DBusError::WrongType(format!("Variant type does not match: {:?}", var)))
+}
+
+pub fn variant_iter_to_vec_u8(var: &mut Variant<Iter>) -> Result<Vec<u8>, DBusError> {
+    if is_array_of(var, "ay") {
+        if let Some(array) = var.0.get::<Array<u8, _>>() {
+            return Ok(array.collect());
+        }
+    }
+
+    bail!(DBusError::WrongType(format!(
+        "Variant not a byte array: {:?}",
+        var
+    )))
+}
+
+pub fn variant_iter_to_vec_string(var: &mut Variant<Iter>) -> Result<Vec<String>, DBusError> {
+    if is_array_of(var, "as") {
+        if let Some(array) = var.0.get::<Array<&str, _>>() {
+            return Ok(array.map(|s| s.to_string()).collect());
+        }
+    }
+
+    bail!(DBusError::WrongType(format!(
+        "Variant not a string array: {:?}",
+        var
+    )))
+}
+
+/// Arrays are not checked against their element type when read, so an array of the wrong
+/// type would otherwise decode as an empty one.
+fn is_array_of(var: &mut Variant<Iter>, signature: &str) -> bool {
+    &*var.0.signature() == signature
+}
+
+pub fn path_to_string(path: &Path) -> Result<String, DBusError> {
+    if let Ok(slice) = path.as_cstr().to_str() {
+        Ok(slice.to_string())
+    } else {
+        bail!(DBusError::WrongType(format!(
+            "Path not a UTF-8 string: {:?}",
+            path
+        )))
+    }
+}
+
+/// Converts every path of `paths`, failing on the first that is not valid UTF-8.
+pub fn paths_to_strings<'a, I>(paths: I) -> Result<Vec<String>, DBusError>
+where
+    I: IntoIterator<Item = Path<'a>>,
+{
+    paths.into_iter().map(|path| path_to_string(&path)).collect()
+}
+
+fn timeout_millis(timeout: Duration) -> i32 {
+    let millis = timeout.as_secs() * 1000 + u64::from(timeout.subsec_nanos()) / 1_000_000;
+
+    ::std::cmp::min(millis, MAX_TIMEOUT_MILLIS) as i32
+}
+
+fn should_retry(name: Option<&str>, method_retry_error_names: &[&str]) -> bool {
+    let name = match name {
+        Some(name) => name,
+        None => return false,
+    };
+
+    // Retrying can not help when the service is not running at all
+    if let DBusError::ServiceUnknown(_) = DBusError::from_name(Some(name), String::new()) {
+        return false;
+    }
+
+    if method_retry_error_names.contains(&name) {
+        debug!("Should retry D-Bus method call: {}", name);
+
+        return true;
+    }
+
+    false
+}
+
+/// Calls `attempt` until it returns a result, sleeping according to `backoff` in between.
+///
+/// `attempt` is passed the number of retries so far and returns `Attempt::Retry` when the call
+/// should be retried, optionally with a delay overriding `backoff`. Gives up after
+/// `max_retries` attempts.
+fn retry<F>(max_retries: usize, backoff: Backoff, mut attempt: F) -> Result<Message, DBusError>
+where
+    F: FnMut(usize) -> Attempt,
+{
+    let mut retries = 0;
+
+    loop {
+        let delay = match attempt(retries) {
+            Attempt::Done(result) => return result,
+            Attempt::Retry(delay) => delay,
+        };
+
+        retries += 1;
+
+        if retries >= max_retries {
+            bail!(DBusError::Other(format!(
+                "Method call failed after {} retries",
+                max_retries
+            )));
+        }
+
+        ::std::thread::sleep(delay.unwrap_or_else(|| backoff.delay(retries)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::process::Command;
+    use std::rc::Rc;
+    use std::sync::{mpsc, Mutex};
+    use std::thread;
+    use std::time::Instant;
+
+    use dbus::arg::{Append, Arg};
+    use dbus::tree::{Access, MethodErr};
+
+    use mock::MockService;
+
+    use super::*;
+
+    type VariantMap = HashMap<String, Variant<Box<RefArg>>>;
+
+    const MOCK_PATH: &str = "/org/freedesktop/NetworkManager";
+    const MOCK_INTERFACE: &str = "org.freedesktop.NetworkManager";
+    const OTHER_PATH: &str = "/org/freedesktop/NetworkManager/Devices/1";
+
+    /// Serves a `WirelessEnabled` (read-write) and a `Version` (read-only) property.
+    fn spawn_mock_service(service: &'static str) -> MockService {
+        MockService::spawn(service, |f, _| {
+            let enabled = Rc::new(Cell::new(false));
+            let get_enabled = enabled.clone();
+            let set_enabled = enabled.clone();
+
+            f.tree(()).add(
+                f.object_path(MOCK_PATH, ()).introspectable().add(
+                    f.interface(MOCK_INTERFACE, ())
+                        .add_p(
+                            f.property::<bool, _>("WirelessEnabled", ())
+                                .access(Access::ReadWrite)
+                                .on_get(move |i, _| {
+                                    i.append(get_enabled.get());
+                                    Ok(())
+                                })
+                                .on_set(move |i, _| {
+                                    set_enabled.set(i.read()?);
+                                    Ok(())
+                                }),
+                        )
+                        .add_p(f.property::<&str, _>("Version", ()).on_get(|i, _| {
+                            i.append("1.10.6");
+                            Ok(())
+                        })),
+                ),
+            )
+        })
+    }
+
+    fn mock_dbus(service: &'static str) -> DBusApi {
+        DBusApi::with_bus_type(BusType::Session, service, &[], Some(2), None, None).unwrap()
+    }
+
+    fn properties_changed_signal(path: &str, enabled: bool) -> Message {
+        let mut changed = HashMap::new();
+        changed.insert("WirelessEnabled", Variant(enabled));
+
+        Message::new_signal(path, PROPERTIES_INTERFACE, PROPERTIES_CHANGED)
+            .unwrap()
+            .append3(MOCK_INTERFACE, changed, vec!["Version"])
+    }
+
+    #[test]
+    fn test_parse_properties_changed() {
+        let signal = properties_changed_signal(MOCK_PATH, true);
+
+        assert!(is_signal(&signal, MOCK_PATH, PROPERTIES_INTERFACE, PROPERTIES_CHANGED));
+        assert!(!is_signal(
+            &signal,
+            "/org/freedesktop/NetworkManager/Devices/0",
+            PROPERTIES_INTERFACE,
+            PROPERTIES_CHANGED
+        ));
+
+        let change = parse_properties_changed(&signal).unwrap();
+        assert_eq!(change.interface, MOCK_INTERFACE);
+        assert_eq!(change.changed.len(), 1);
+        let enabled: Option<bool> = DBusApi::variant_to(&change.changed["WirelessEnabled"]);
+        assert_eq!(enabled, Some(true));
+        assert_eq!(change.invalidated, vec!["Version".to_string()]);
+
+        let other = Message::new_signal(MOCK_PATH, MOCK_INTERFACE, "StateChanged")
+            .unwrap()
+            .append1(70_u32);
+        assert!(!is_signal(&other, MOCK_PATH, PROPERTIES_INTERFACE, PROPERTIES_CHANGED));
+        assert!(parse_properties_changed(&other).is_none());
+    }
+
+    #[test]
+    fn test_properties_changed() {
+        const SERVICE: &str = "org.freedesktop.NetworkManager.Test.PropertiesChanged";
+
+        let _service = spawn_mock_service(SERVICE);
+        let dbus = mock_dbus(SERVICE);
+
+        let mut changes = dbus.properties_changed(MOCK_PATH).unwrap();
+
+        // Setting the property makes the mock service emit PropertiesChanged
+        dbus.set_property(MOCK_PATH, MOCK_INTERFACE, "WirelessEnabled", true)
+            .unwrap();
+
+        let change = changes.next_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(change.interface, MOCK_INTERFACE);
+        let enabled: Option<bool> = DBusApi::variant_to(&change.changed["WirelessEnabled"]);
+        assert_eq!(enabled, Some(true));
+
+        assert!(changes.next_timeout(Duration::from_millis(50)).is_none());
+    }
+
+    #[test]
+    fn test_signals_deadline() {
+        const SERVICE: &str = "org.freedesktop.NetworkManager.Test.SignalsDeadline";
+
+        let (ready_tx, ready_rx) = mpsc::channel();
+        let (start_tx, start_rx) = mpsc::channel();
+
+        // Emits a steady stream of signals that only the other subscription matches
+        let service = thread::spawn(move || {
+            let connection = DBusConnection::get_private(BusType::Session).unwrap();
+            connection.register_name(SERVICE, 0).unwrap();
+            ready_tx.send(()).unwrap();
+
+            start_rx.recv().unwrap();
+            for state in 0..50_u32 {
+                let signal = Message::new_signal(OTHER_PATH, MOCK_INTERFACE, "StateChanged")
+                    .unwrap()
+                    .append1(state);
+                connection.send(signal).unwrap();
+                thread::sleep(Duration::from_millis(20));
+            }
+        });
+
+        ready_rx.recv().unwrap();
+        let dbus = mock_dbus(SERVICE);
+
+        let mut states = dbus.signals(MOCK_PATH, MOCK_INTERFACE, "StateChanged")
+            .unwrap();
+        let mut other_states = dbus.signals(OTHER_PATH, MOCK_INTERFACE, "StateChanged")
+            .unwrap();
+        start_tx.send(()).unwrap();
+
+        let start = Instant::now();
+        assert!(states.next_timeout(Duration::from_millis(300)).is_none());
+        assert!(start.elapsed() < Duration::from_millis(700));
+
+        let signal = other_states.next_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(signal.get1::<u32>(), Some(0));
+
+        service.join().unwrap();
+    }
+
+    #[test]
+    fn test_get_all() {
+        const SERVICE: &str = "org.freedesktop.NetworkManager.Test.GetAll";
+
+        let _service = spawn_mock_service(SERVICE);
+        let dbus = mock_dbus(SERVICE);
+
+        let properties = dbus.get_all(MOCK_PATH, MOCK_INTERFACE).unwrap();
+        assert_eq!(properties.len(), 2);
+
+        let version: Option<String> = DBusApi::variant_to(&properties["Version"]);
+        assert_eq!(version, Some("1.10.6".to_string()));
+
+        let enabled: Option<bool> = DBusApi::variant_to(&properties["WirelessEnabled"]);
+        assert_eq!(enabled, Some(false));
+    }
+
+    #[test]
+    fn test_cached_property() {
+        const SERVICE: &str = "org.freedesktop.NetworkManager.Test.CachedProperty";
+
+        let service = spawn_mock_service(SERVICE);
+        let dbus = mock_dbus(SERVICE);
+        dbus.enable_property_cache(Duration::from_secs(60)).unwrap();
+
+        let version: String = dbus.cached_property(MOCK_PATH, MOCK_INTERFACE, "Version")
+            .unwrap();
+        assert_eq!(version, "1.10.6");
+        let calls = service.method_calls();
+
+        let version: String = dbus.cached_property(MOCK_PATH, MOCK_INTERFACE, "Version")
+            .unwrap();
+        assert_eq!(version, "1.10.6");
+        assert_eq!(service.method_calls(), calls);
+
+        let enabled: bool = dbus
+            .cached_property(MOCK_PATH, MOCK_INTERFACE, "WirelessEnabled")
+            .unwrap();
+        assert!(!enabled);
+
+        // Setting the property makes the mock service emit PropertiesChanged, which drops the
+        // cached value without a subscription of the caller
+        dbus.set_property(MOCK_PATH, MOCK_INTERFACE, "WirelessEnabled", true)
+            .unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        loop {
+            let enabled: bool = dbus
+                .cached_property(MOCK_PATH, MOCK_INTERFACE, "WirelessEnabled")
+                .unwrap();
+            if enabled {
+                break;
+            }
+            assert!(Instant::now() < deadline, "cached value not dropped");
+            ::std::thread::sleep(Duration::from_millis(10));
+        }
+
+        dbus.enable_property_cache(Duration::from_secs(0)).unwrap();
+        let calls = service.method_calls();
+        for _ in 0..2 {
+            let _: String = dbus.cached_property(MOCK_PATH, MOCK_INTERFACE, "Version")
+                .unwrap();
+        }
+        assert_eq!(service.method_calls(), calls + 2);
+    }
+
+    #[test]
+    fn test_get_many() {
+        const SERVICE: &str = "org.freedesktop.NetworkManager.Test.GetMany";
+
+        let service = spawn_mock_service(SERVICE);
+        let dbus = mock_dbus(SERVICE);
+
+        let names = ["Version", "WirelessEnabled"];
+
+        let calls = service.method_calls();
+        let _: String = dbus.property(MOCK_PATH, MOCK_INTERFACE, "Version").unwrap();
+        let _: bool = dbus.property(MOCK_PATH, MOCK_INTERFACE, "WirelessEnabled")
+            .unwrap();
+        assert_eq!(service.method_calls(), calls + names.len());
+
+        let calls = service.method_calls();
+        let properties = dbus.get_many(MOCK_PATH, MOCK_INTERFACE, &names).unwrap();
+        assert_eq!(service.method_calls(), calls + 1);
+
+        let version: String = properties.get("Version").unwrap();
+        assert_eq!(version, "1.10.6");
+        let enabled: bool = properties.get("WirelessEnabled").unwrap();
+        assert!(!enabled);
+        assert!(properties.get::<bool>("Version").is_err());
+
+        let properties = dbus.get_many(MOCK_PATH, MOCK_INTERFACE, &["Version"]).unwrap();
+        assert!(properties.get::<bool>("WirelessEnabled").is_err());
+    }
+
+    #[test]
+    fn test_connection_ping() {
+        const SERVICE: &str = "org.freedesktop.NetworkManager.Test.Ping";
+
+        let _service = spawn_mock_service(SERVICE);
+        let dbus = mock_dbus(SERVICE);
+
+        let ping =
+            Message::new_method_call(SERVICE, MOCK_PATH, "org.freedesktop.DBus.Peer", "Ping")
+                .unwrap();
+        let reply = dbus.connection().send_with_reply_and_block(ping, 2000);
+        assert!(reply.is_ok());
+    }
+
+    /// Serves an `Emit` method emitting the `StateChanged` signals 1 and 4 on `MOCK_PATH`, 2 on
+    /// `OTHER_PATH` and an unrelated signal 3.
+    fn spawn_signal_emitter(service: &'static str) -> MockService {
+        MockService::spawn(service, |f, signals| {
+            let signals = signals.clone();
+
+            f.tree(()).add(f.object_path(MOCK_PATH, ()).add(
+                f.interface(MOCK_INTERFACE, ()).add_m(f.method("Emit", (), move |m| {
+                    let signal = |path: &str, member: &str, value: u32| {
+                        Message::new_signal(path, MOCK_INTERFACE, member)
+                            .unwrap()
+                            .append1(value)
+                    };
+                    signals.emit(signal(MOCK_PATH, "StateChanged", 1));
+                    signals.emit(signal(OTHER_PATH, "StateChanged", 2));
+                    signals.emit(signal(MOCK_PATH, "CheckPermissions", 3));
+                    signals.emit(signal(MOCK_PATH, "StateChanged", 4));
+                    Ok(vec![m.msg.method_return()])
+                })),
+            ))
+        })
+    }
+
+    #[test]
+    fn test_signal_router() {
+        const SERVICE: &str = "org.freedesktop.NetworkManager.Test.SignalRouter";
+
+        let _service = spawn_signal_emitter(SERVICE);
+        let dbus = mock_dbus(SERVICE);
+
+        let states = RefCell::new(Vec::new());
+        let other_states = RefCell::new(Vec::new());
+
+        {
+            let mut router = dbus.signal_router();
+            router
+                .register(MOCK_PATH, MOCK_INTERFACE, "StateChanged", |signal| {
+                    states.borrow_mut().push(signal.get1::<u32>().unwrap());
+                })
+                .unwrap();
+            router
+                .register(OTHER_PATH, MOCK_INTERFACE, "StateChanged", |signal| {
+                    other_states.borrow_mut().push(signal.get1::<u32>().unwrap());
+                })
+                .unwrap();
+
+            dbus.call(MOCK_PATH, MOCK_INTERFACE, "Emit").unwrap();
+
+            let deadline = Instant::now() + Duration::from_secs(5);
+            let mut dispatched = 0;
+            while dispatched < 3 && Instant::now() < deadline {
+                dispatched += router.run_timeout(Duration::from_millis(100));
+            }
+            assert_eq!(dispatched, 3);
+
+            assert!(router.unregister(OTHER_PATH, MOCK_INTERFACE, "StateChanged"));
+            assert!(!router.unregister(OTHER_PATH, MOCK_INTERFACE, "StateChanged"));
+        }
+
+        assert_eq!(*states.borrow(), vec![1, 4]);
+        assert_eq!(*other_states.borrow(), vec![2]);
+    }
+
+    #[test]
+    fn test_signal_router_with_signals() {
+        const SERVICE: &str = "org.freedesktop.NetworkManager.Test.SignalRouterWithSignals";
+
+        let _service = spawn_signal_emitter(SERVICE);
+        let dbus = mock_dbus(SERVICE);
+
+        let states = RefCell::new(Vec::new());
+        let mut router = dbus.signal_router();
+        router
+            .register(MOCK_PATH, MOCK_INTERFACE, "StateChanged", |signal| {
+                states.borrow_mut().push(signal.get1::<u32>().unwrap());
+            })
+            .unwrap();
+        let mut other_states = dbus.signals(OTHER_PATH, MOCK_INTERFACE, "StateChanged")
+            .unwrap();
+
+        let run_until = |router: &mut SignalRouter, count: usize| {
+            let deadline = Instant::now() + Duration::from_secs(5);
+            while states.borrow().len() < count && Instant::now() < deadline {
+                router.run_timeout(Duration::from_millis(100));
+            }
+        };
+
+        // Signals for the router that arrive while the subscription waits are dispatched later
+        dbus.call(MOCK_PATH, MOCK_INTERFACE, "Emit").unwrap();
+        let signal = other_states.next_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(signal.get1::<u32>(), Some(2));
+        run_until(&mut router, 2);
+        assert_eq!(*states.borrow(), vec![1, 4]);
+
+        // Signals for the subscription that arrive while the router runs are kept for it
+        dbus.call(MOCK_PATH, MOCK_INTERFACE, "Emit").unwrap();
+        run_until(&mut router, 4);
+        assert_eq!(*states.borrow(), vec![1, 4, 1, 4]);
+        let signal = other_states.next_timeout(Duration::from_millis(0)).unwrap();
+        assert_eq!(signal.get1::<u32>(), Some(2));
+    }
+
+    /// Forwards every logged message to a channel.
+    struct ChannelLogger(Mutex<mpsc::Sender<String>>);
+
+    impl ::log::Log for ChannelLogger {
+        fn enabled(&self, _: &::log::LogMetadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &::log::LogRecord) {
+            let _ = self.0.lock().unwrap().send(record.args().to_string());
+        }
+    }
+
+    #[test]
+    fn test_dry_run() {
+        const SERVICE: &str = "org.freedesktop.NetworkManager.Test.DryRun";
+
+        // No other test installs a logger, which can only be done once per process
+        let (logged_tx, logged_rx) = mpsc::channel();
+        ::log::set_logger(|max_level| {
+            max_level.set(::log::LogLevelFilter::Info);
+            Box::new(ChannelLogger(Mutex::new(logged_tx)))
+        }).unwrap();
+
+        let service = spawn_mock_service(SERVICE);
+        let dbus = mock_dbus(SERVICE);
+        dbus.set_dry_run(true);
+
+        let calls = service.method_calls();
+        let response = dbus.call_with_args(
+            MOCK_PATH,
+            MOCK_INTERFACE,
+            "Reload",
+            &[&7_u32 as &RefArg],
+        );
+        assert!(response.is_ok());
+        dbus.set_property(MOCK_PATH, MOCK_INTERFACE, "WirelessEnabled", true)
+            .unwrap();
+        assert_eq!(service.method_calls(), calls);
+
+        let logged = logged_rx.try_iter().collect::<Vec<_>>();
+        assert!(logged.iter().any(|message| {
+            message.starts_with("Dry run: org.freedesktop.NetworkManager::Reload method call")
+                && message.contains(MOCK_PATH) && message.contains('7')
+        }));
+        assert!(logged.iter().any(|message| {
+            message.starts_with("Dry run: set org.freedesktop.NetworkManager::WirelessEnabled")
+        }));
+
+        dbus.set_dry_run(false);
+        let enabled: bool = dbus.property(MOCK_PATH, MOCK_INTERFACE, "WirelessEnabled")
+            .unwrap();
+        assert!(!enabled);
+    }
+
+    #[cfg(feature = "tracing")]
+    mod spans {
+        use std::fmt;
+        use std::sync::{Arc, Mutex};
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        use tracing::{Event, Metadata, Subscriber};
+        use tracing::field::{Field, Visit};
+        use tracing::span::{Attributes, Id, Record};
+
+        pub type Fields = Vec<(String, String)>;
+
+        /// Records the name and fields of every span created while it is the default.
+        #[derive(Clone, Default)]
+        pub struct SpanRecorder {
+            pub spans: Arc<Mutex<Vec<(String, Fields)>>>,
+            next_id: Arc<AtomicUsize>,
+        }
+
+        struct FieldVisitor<'a>(&'a mut Fields);
+
+        impl<'a> Visit for FieldVisitor<'a> {
+            fn record_debug(&mut self, field: &Field, value: &fmt::Debug) {
+                self.0.push((field.name().to_string(), format!("{:?}", value)));
+            }
+        }
+
+        impl Subscriber for SpanRecorder {
+            fn enabled(&self, _: &Metadata) -> bool {
+                true
+            }
+
+            fn new_span(&self, span: &Attributes) -> Id {
+                let mut fields = Vec::new();
+                span.record(&mut FieldVisitor(&mut fields));
+
+                let mut spans = self.spans.lock().unwrap();
+                spans.push((span.metadata().name().to_string(), fields));
+
+                Id::from_u64(self.next_id.fetch_add(1, Ordering::SeqCst) as u64 + 1)
+            }
+
+            fn record(&self, span: &Id, values: &Record) {
+                let mut spans = self.spans.lock().unwrap();
+                let index = span.into_u64() as usize - 1;
+                values.record(&mut FieldVisitor(&mut spans[index].1));
+            }
+
+            fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+            fn event(&self, _: &Event) {}
+
+            fn enter(&self, _: &Id) {}
+
+            fn exit(&self, _: &Id) {}
+        }
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn test_tracing_spans() {
+        const SERVICE: &str = "org.freedesktop.NetworkManager.Test.TracingSpans";
+
+        let _service = spawn_mock_service(SERVICE);
+        let dbus = mock_dbus(SERVICE);
+
+        let recorder = spans::SpanRecorder::default();
+
+        ::tracing::subscriber::with_default(recorder.clone(), || {
+            let _: String = dbus.property(MOCK_PATH, MOCK_INTERFACE, "Version").unwrap();
+            assert!(dbus.call(MOCK_PATH, MOCK_INTERFACE, "Missing").is_err());
+        });
+
+        let spans = recorder.spans.lock().unwrap();
+        let field = |span: &(String, spans::Fields), name: &str| {
+            span.1
+                .iter()
+                .rev()
+                .find(|field| field.0 == name)
+                .map(|field| field.1.clone())
+        };
+
+        let property = spans.iter().find(|span| span.0 == "dbus_property").unwrap();
+        assert_eq!(field(property, "path"), Some(format!("{:?}", MOCK_PATH)));
+        assert_eq!(field(property, "name"), Some("\"Version\"".to_string()));
+
+        let call = spans.iter().find(|span| span.0 == "dbus_method_call").unwrap();
+        assert_eq!(field(call, "interface"), Some(format!("{:?}", MOCK_INTERFACE)));
+        assert_eq!(field(call, "method"), Some("\"Missing\"".to_string()));
+        assert_eq!(field(call, "retries"), Some("0".to_string()));
+    }
+
+    #[test]
+    fn test_from_connection() {
+        const SERVICE: &str = "org.freedesktop.NetworkManager.Test.FromConnection";
+
+        let _service = spawn_mock_service(SERVICE);
+
+        let connection = Rc::new(DBusConnection::get_private(BusType::Session).unwrap());
+        let first = DBusApi::from_connection(connection.clone(), SERVICE, &[], None, None, None);
+        let second = DBusApi::from_connection(connection.clone(), SERVICE, &[], None, None, None);
+        assert_eq!(Rc::strong_count(&connection), 3);
+
+        first
+            .set_property(MOCK_PATH, MOCK_INTERFACE, "WirelessEnabled", true)
+            .unwrap();
+        let enabled: bool = second
+            .property(MOCK_PATH, MOCK_INTERFACE, "WirelessEnabled")
+            .unwrap();
+        assert!(enabled);
+    }
+
+    #[test]
+    fn test_set_property() {
+        const SERVICE: &str = "org.freedesktop.NetworkManager.Test.SetProperty";
+
+        let _service = spawn_mock_service(SERVICE);
+        let dbus = mock_dbus(SERVICE);
+
+        let enabled: bool = dbus.property(MOCK_PATH, MOCK_INTERFACE, "WirelessEnabled")
+            .unwrap();
+        assert!(!enabled);
+
+        dbus.set_property(MOCK_PATH, MOCK_INTERFACE, "WirelessEnabled", true)
+            .unwrap();
+
+        let enabled: bool = dbus.property(MOCK_PATH, MOCK_INTERFACE, "WirelessEnabled")
+            .unwrap();
+        assert!(enabled);
+
+        let result = dbus.set_property(MOCK_PATH, MOCK_INTERFACE, "Version", "2.0".to_string());
+        assert!(result.is_err());
+    }
+
+    fn variant<T: RefArg + 'static>(value: T) -> Variant<Box<RefArg>> {
+        Variant(Box::new(value) as Box<RefArg>)
+    }
+
+    #[test]
+    fn test_call_with_args_timeout() {
+        const SERVICE: &str = "org.freedesktop.NetworkManager.Test.Timeout";
+
+        let (ready_tx, ready_rx) = mpsc::channel();
+        let (done_tx, done_rx) = mpsc::channel::<()>();
+
+        // Owns the service name but never answers method calls
+        let service = thread::spawn(move || {
+            let connection = DBusConnection::get_private(BusType::Session).unwrap();
+            connection.register_name(SERVICE, 0).unwrap();
+            ready_tx.send(()).unwrap();
+            done_rx.recv().unwrap();
+        });
+
+        ready_rx.recv().unwrap();
+
+        let dbus =
+            DBusApi::with_bus_type(BusType::Session, SERVICE, &[], None, None, None).unwrap();
+
+        let start = Instant::now();
+        let result = dbus.call_with_args_timeout(
+            "/",
+            SERVICE,
+            "Hang",
+            &[],
+            Duration::from_millis(200),
+        );
+        let elapsed = start.elapsed();
+
+        done_tx.send(()).unwrap();
+        service.join().unwrap();
+
+        match result {
+            Err(DBusError::Timeout(_)) => {},
+            other => panic!("expected a timeout, got {:?}", other.map(|_| ())),
+        }
+        assert!(elapsed < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_missing_service_fails_fast() {
+        const SERVICE: &str = "org.freedesktop.NetworkManager.Test.Missing";
+
+        let dbus = DBusApi::with_bus_type(
+            BusType::Session,
+            SERVICE,
+            &["org.freedesktop.DBus.Error.ServiceUnknown"],
+            None,
+            None,
+            None,
+        ).unwrap();
+
+        let start = Instant::now();
+        let result = dbus.call(MOCK_PATH, MOCK_INTERFACE, "state");
+
+        match result {
+            Err(DBusError::ServiceUnknown(_)) => {},
+            other => panic!("expected an unknown service, got {:?}", other.map(|_| ())),
+        }
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_should_retry() {
+        let names = &[
+            "org.freedesktop.NetworkManager.UnknownConnection",
+            "org.freedesktop.DBus.Error.ServiceUnknown",
+        ];
+
+        assert!(should_retry(
+            Some("org.freedesktop.NetworkManager.UnknownConnection"),
+            names
+        ));
+        assert!(!should_retry(
+            Some("org.freedesktop.DBus.Error.ServiceUnknown"),
+            names
+        ));
+        assert!(!should_retry(Some("org.freedesktop.DBus.Error.NoReply"), names));
+        assert!(!should_retry(None, names));
+    }
+
+    #[test]
+    fn test_new_without_bus() {
+        const CHILD: &str = "NETWORK_MANAGER_TEST_BOGUS_BUS";
+
+        if env::var_os(CHILD).is_some() {
+            let result =
+                DBusApi::with_bus_type(BusType::Session, MOCK_INTERFACE, &[], None, None, None);
+            assert!(result.is_err());
+            return;
+        }
+
+        // libdbus reads the bus addresses once per process, so the bogus address has to be
+        // set for a fresh test process
+        let output = Command::new(env::current_exe().unwrap())
+            .arg("--exact")
+            .arg("dbus_api::tests::test_new_without_bus")
+            .env(CHILD, "1")
+            .env(
+                "DBUS_SESSION_BUS_ADDRESS",
+                "unix:path=/nonexistent/network-manager-test",
+            )
+            .output()
+            .unwrap();
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(output.status.success(), "{}", stdout);
+        assert!(stdout.contains("1 passed"), "{}", stdout);
+    }
+
+    #[test]
+    fn test_paths_to_strings() {
+        let paths = vec![
+            Path::from("/org/freedesktop/NetworkManager/Devices/0"),
+            Path::from("/org/freedesktop/NetworkManager/Devices/1"),
+        ];
+        assert_eq!(
+            paths_to_strings(paths).unwrap(),
+            vec![
+                "/org/freedesktop/NetworkManager/Devices/0",
+                "/org/freedesktop/NetworkManager/Devices/1",
+            ]
+        );
+
+        let invalid = unsafe { Path::from_slice_unchecked(b"/org/\xff\0") };
+        let paths = vec![Path::from("/org/freedesktop/NetworkManager/Devices/0"), invalid];
+        match paths_to_strings(paths) {
+            Err(DBusError::WrongType(_)) => {},
+            other => panic!("expected a wrong type error, got {:?}", other),
+        }
+    }
+
+    fn variant_iter_message<T: Arg + Append>(value: T) -> Message {
+        Message::new_signal("/", "org.freedesktop.DBus.Test", "Test")
+            .unwrap()
+            .append1(Variant(value))
+    }
+
+    #[test]
+    fn test_variant_iter_to_vec_string() {
+        let message = variant_iter_message(vec!["example.com", "lan", "corp.example.com"]);
+        let mut variant: Variant<Iter> = message.get1().unwrap();
+
+        assert_eq!(
+            variant_iter_to_vec_string(&mut variant).unwrap(),
+            vec!["example.com", "lan", "corp.example.com"]
+        );
+    }
+
+    #[test]
+    fn test_variant_iter_to_vec_string_wrong_type() {
+        let message = variant_iter_message(vec![1_u32, 2, 3]);
+        let mut variant: Variant<Iter> = message.get1().unwrap();
+        assert!(variant_iter_to_vec_string(&mut variant).is_err());
+
+        let mut variant: Variant<Iter> = message.get1().unwrap();
+        assert!(variant_iter_to_vec_u8(&mut variant).is_err());
+
+        let message = variant_iter_message("example.com");
+        let mut variant: Variant<Iter> = message.get1().unwrap();
+        assert!(variant_iter_to_vec_string(&mut variant).is_err());
+    }
+
+    #[test]
+    fn test_variant_iter_empty_arrays() {
+        let message = variant_iter_message(Vec::<u8>::new());
+        let mut variant: Variant<Iter> = message.get1().unwrap();
+        assert_eq!(variant_iter_to_vec_u8(&mut variant), Ok(vec![]));
+
+        let message = variant_iter_message(Vec::<&str>::new());
+        let mut variant: Variant<Iter> = message.get1().unwrap();
+        assert_eq!(variant_iter_to_vec_string(&mut variant), Ok(vec![]));
+
+        let message = variant_iter_message(Vec::<u32>::new());
+        let mut variant: Variant<Iter> = message.get1().unwrap();
+        assert!(variant_iter_to_vec_u8(&mut variant).is_err());
+    }
+
+    #[test]
+    fn test_timeout_millis() {
+        assert_eq!(timeout_millis(Duration::from_millis(2500)), 2500);
+        assert_eq!(timeout_millis(Duration::from_secs(60)), 60_000);
+        assert_eq!(timeout_millis(Duration::from_secs(1 << 40)), 0x7fff_ffff);
+    }
+
+    #[test]
+    fn test_variant_to_bool_from_integer() {
+        let value: Option<bool> = DBusApi::variant_to(&variant(0_u32));
+        assert_eq!(value, Some(false));
+
+        let value: Option<bool> = DBusApi::variant_to(&variant(1_u32));
+        assert_eq!(value, Some(true));
+    }
+
+    #[test]
+    fn test_variant_to_bool_from_bool() {
+        let value: Option<bool> = DBusApi::variant_to(&variant(false));
+        assert_eq!(value, Some(false));
+
+        let value: Option<bool> = DBusApi::variant_to(&variant(true));
+        assert_eq!(value, Some(true));
+    }
+
+    #[test]
+    fn test_variant_to_string_from_object_path() {
+        let path = Path::new("/org/freedesktop/NetworkManager/ActiveConnection/1").unwrap();
+
+        let value: Option<String> = DBusApi::variant_to(&variant(path));
+        assert_eq!(
+            value,
+            Some("/org/freedesktop/NetworkManager/ActiveConnection/1".to_string())
+        );
+
+        let paths = vec![
+            Path::new("/org/freedesktop/NetworkManager/Devices/1").unwrap(),
+            Path::new("/org/freedesktop/NetworkManager/Devices/2").unwrap(),
+        ];
+
+        let value: Option<Vec<String>> = DBusApi::variant_to(&variant(paths));
+        assert_eq!(
+            value,
+            Some(vec![
+                "/org/freedesktop/NetworkManager/Devices/1".to_string(),
+                "/org/freedesktop/NetworkManager/Devices/2".to_string(),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_variant_to_i32() {
+        let value: Option<i32> = DBusApi::variant_to(&variant(-5_i32));
+        assert_eq!(value, Some(-5));
+
+        let value: Option<i32> = DBusApi::variant_to(&variant(3_000_000_000_u32));
+        assert_eq!(value, None);
+    }
+
+    #[test]
+    fn test_variant_to_u8() {
+        let value: Option<u8> = DBusApi::variant_to(&variant(72_u8));
+        assert_eq!(value, Some(72));
+
+        let value: Option<u8> = DBusApi::variant_to(&variant(300_u32));
+        assert_eq!(value, None);
+    }
+
+    #[test]
+    fn test_variant_to_u64() {
+        let value: Option<u64> = DBusApi::variant_to(&variant(5_000_000_000_u64));
+        assert_eq!(value, Some(5_000_000_000));
+
+        let value: Option<u64> = DBusApi::variant_to(&variant(42_u32));
+        assert_eq!(value, Some(42));
+    }
+
+    #[test]
+    fn test_variant_to_vec_u32() {
+        let value: Option<Vec<u32>> = DBusApi::variant_to(&variant(vec![1_u32, 2_u32, 3_u32]));
+        assert_eq!(value, Some(vec![1, 2, 3]));
+
+        let mixed: Vec<Box<RefArg>> = vec![Box::new(1_u32), Box::new("two".to_string())];
+
+        let value: Option<Vec<u32>> = DBusApi::variant_to(&variant(mixed));
+        assert_eq!(value, None);
+    }
+
+    #[test]
+    fn test_variant_to_vec_of_byte_arrays() {
+        let arrays = vec![b"first".to_vec(), vec![0x00_u8, 0xff_u8]];
+
+        let value: Option<Vec<Vec<u8>>> = DBusApi::variant_to(&variant(arrays.clone()));
+        assert_eq!(value, Some(arrays.clone()));
+
+        // Arrays of arrays read from a message are represented as `Vec<Box<RefArg>>`
+        let received: Vec<Box<RefArg>> = arrays
+            .iter()
+            .map(|a| Box::new(a.clone()) as Box<RefArg>)
+            .collect();
+
+        let value: Option<Vec<Vec<u8>>> = DBusApi::variant_to(&variant(received));
+        assert_eq!(value, Some(arrays));
+
+        let value: Option<Vec<Vec<u8>>> =
+            DBusApi::variant_to(&variant(vec![vec!["first".to_string()]]));
+        assert_eq!(value, None);
+    }
+
+    #[test]
+    fn test_variant_to_string_map() {
+        let mut dict: HashMap<String, Variant<Box<RefArg>>> = HashMap::new();
+        dict.insert("mode".into(), variant("infrastructure".to_string()));
+        dict.insert("band".into(), variant("bg".to_string()));
+
+        let value: Option<HashMap<String, String>> = DBusApi::variant_to(&variant(dict));
+        let map = value.unwrap();
+        assert_eq!(map.len(), 2);
+        assert_eq!(map["mode"], "infrastructure");
+        assert_eq!(map["band"], "bg");
+
+        let mut dict: HashMap<String, String> = HashMap::new();
+        dict.insert("domain_name".into(), "example.com".into());
+
+        let value: Option<HashMap<String, String>> = DBusApi::variant_to(&variant(dict.clone()));
+        assert_eq!(value, Some(dict));
+    }
+
+    #[test]
+    fn test_variant_to_string_map_wrong_type() {
+        let value: Option<HashMap<String, String>> =
+            DBusApi::variant_to(&variant(vec!["mode".to_string(), "ap".to_string()]));
+        assert_eq!(value, None);
+
+        let mut dict: HashMap<String, u32> = HashMap::new();
+        dict.insert("prefix".into(), 24);
+
+        let value: Option<HashMap<String, String>> = DBusApi::variant_to(&variant(dict));
+        assert_eq!(value, None);
+    }
+
+    #[test]
+    fn test_variant_to_f64() {
+        let value: Option<f64> = DBusApi::variant_to(&variant(3.5_f64));
+        assert_eq!(value, Some(3.5));
+
+        let value: Option<f64> = DBusApi::variant_to(&variant("3.5".to_string()));
+        assert_eq!(value, None);
+    }
+
+    #[test]
+    fn test_extract_three() {
+        const SERVICE: &str = "org.freedesktop.NetworkManager.Test.ExtractThree";
+
+        let dbus = mock_dbus(SERVICE);
+
+        let path = Path::new("/org/freedesktop/NetworkManager/Settings/1").unwrap();
+        let active = Path::new("/org/freedesktop/NetworkManager/ActiveConnection/1").unwrap();
+        let mut result = HashMap::new();
+        result.insert("persist", Variant("disk"));
+
+        let response = Message::new_signal("/", SERVICE, "Reply")
+            .unwrap()
+            .append3(path.clone(), active.clone(), result);
+
+        let (first, second, third): (Path, Path, VariantMap) =
+            dbus.extract_three(&response).unwrap();
+        assert_eq!(first, path);
+        assert_eq!(second, active);
+        let persist: Option<String> = DBusApi::variant_to(&third["persist"]);
+        assert_eq!(persist, Some("disk".to_string()));
+
+        let response = Message::new_signal("/", SERVICE, "Reply")
+            .unwrap()
+            .append2(path, active);
+        let result: Result<(Path, Path, VariantMap), DBusError> = dbus.extract_three(&response);
+        assert_eq!(
+            result.unwrap_err(),
+            DBusError::WrongType("Wrong response type".into())
+        );
+    }
+
+    #[test]
+    fn test_retry_predicate() {
+        const SERVICE: &str = "org.freedesktop.NetworkManager.Test.RetryPredicate";
+        const BUSY: &str = "org.freedesktop.NetworkManager.Test.Busy";
+        const SHUTTING_DOWN: &str = "org.freedesktop.NetworkManager.Test.ShuttingDown";
+
+        let service = MockService::spawn(SERVICE, |f, _| {
+            f.tree(()).add(f.object_path(MOCK_PATH, ()).add(
+                f.interface(MOCK_INTERFACE, ())
+                    .add_m(f.method("Busy", (), |_| Err(MethodErr::from((BUSY, "busy")))))
+                    .add_m(f.method("Shutdown", (), |_| {
+                        Err(MethodErr::from((SHUTTING_DOWN, "shutting down")))
+                    })),
+            ))
+        });
+
+        let mut dbus =
+            DBusApi::with_bus_type(BusType::Session, SERVICE, &[], Some(2), Some(3), None).unwrap();
+        dbus.set_retry_predicate(|error| match error.name() {
+            Some(BUSY) => RetryDecision::RetryAfter(Duration::from_millis(10)),
+            _ => RetryDecision::Fail,
+        });
+
+        let calls = service.method_calls();
+        let start = Instant::now();
+        match dbus.call(MOCK_PATH, MOCK_INTERFACE, "Shutdown") {
+            Err(DBusError::Other(message)) => assert!(message.contains(SHUTTING_DOWN)),
+            other => panic!("expected a failure, got {:?}", other.map(|_| ())),
+        }
+        assert_eq!(service.method_calls(), calls + 1);
+
+        let calls = service.method_calls();
+        match dbus.call(MOCK_PATH, MOCK_INTERFACE, "Busy") {
+            Err(DBusError::Other(message)) => {
+                assert!(message.ends_with("Method call failed after 3 retries"))
+            },
+            other => panic!("expected a failure, got {:?}", other.map(|_| ())),
+        }
+        assert_eq!(service.method_calls(), calls + 3);
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_retry_gives_up_after_max_retries() {
+        let mut attempts = 0;
+
+        let result = retry(3, Backoff::Fixed(Duration::from_millis(0)), |_| {
+            attempts += 1;
+            Attempt::Retry(None)
+        });
+
+        assert_eq!(attempts, 3);
+        assert_eq!(
+            result.unwrap_err(),
+            DBusError::Other("Method call failed after 3 retries".into())
+        );
+    }
+
+    #[test]
+    fn test_retry_returns_first_result() {
+        let response = Message::new_signal("/", "org.freedesktop.DBus.Test", "Test").unwrap();
+        let mut response = Some(response);
+
+        let result = retry(3, Backoff::Fixed(Duration::from_millis(0)), |retries| {
+            if retries == 1 {
+                Attempt::Done(Ok(response.take().unwrap()))
+            } else {
+                Attempt::Retry(None)
+            }
+        });
+
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_backoff_delay() {
+        let fixed = Backoff::default();
+        assert_eq!(fixed.delay(1), Duration::from_secs(1));
+        assert_eq!(fixed.delay(5), Duration::from_secs(1));
+
+        let exponential = Backoff::Exponential {
+            initial: Duration::from_millis(100),
+            max: Duration::from_millis(1000),
+        };
+        assert_eq!(exponential.delay(1), Duration::from_millis(100));
+        assert_eq!(exponential.delay(2), Duration::from_millis(200));
+        assert_eq!(exponential.delay(4), Duration::from_millis(800));
+        assert_eq!(exponential.delay(5), Duration::from_millis(1000));
+        assert_eq!(exponential.delay(64), Duration::from_millis(1000));
     }
 }
@@ -1,25 +1,44 @@
+use std::any::Any;
+use std::cmp;
 use std::collections::HashMap;
+use std::fmt;
 use std::net::Ipv4Addr;
+use std::rc::Rc;
+use std::thread;
+use std::time::{Duration, Instant};
 
-use dbus::Path;
-use dbus::arg::{Array, Dict, Iter, RefArg, Variant};
+use dbus::Connection as DBusConnection;
+use dbus::{BusType, Message, MessageItem, Path, Signature};
+use dbus::arg::{Array, ArgType, Dict, Iter, IterAppend, RefArg, Variant};
 
 use ascii::AsciiStr;
 
 use errors::*;
-use dbus_api::{extract, path_to_string, DBusApi, VariantTo, variant_iter_to_vec_u8};
-use manager::{Connectivity, NetworkManagerState};
-use connection::{ConnectionSettings, ConnectionState};
-use ssid::{AsSsidSlice, Ssid};
-use device::{DeviceState, DeviceType, IP4Config};
-use wifi::{AccessPoint, AccessPointCredentials, NM80211ApFlags, NM80211ApSecurityFlags};
+use dbus_api::{extract, path_to_string, paths_to_strings, Backoff, DBusApi, Properties,
+               PropertiesChange, RetryDecision, SignalRouter, Signals, VariantTo,
+               variant_iter_to_vec_string, variant_iter_to_vec_u8};
+use manager::{parse_version, CheckpointFlags, Connectivity, DnsEntry, DnsManagerInfo,
+              NetworkManagerState, NetworkManagerStatus};
+use connection::{ActiveConnection, ActiveConnectionStateReason, ConnectionSettings,
+                 ConnectionState};
+use ssid::{AsSsidSlice, Ssid, SsidSlice};
+use settings::{gsm_settings, vpn_settings, EapMethod, Enterprise8021x, GsmConfig,
+               WirelessPowersave};
+use device::{DeviceState, DeviceType, IP4Address, IP4Config, Metered, Stats};
+use wifi::{security_from_flags, AccessPoint, AccessPointCredentials, NM80211ApFlags,
+           NM80211ApSecurityFlags, Security, WirelessCapabilities};
 
-type VariantMap = HashMap<String, Variant<Box<RefArg>>>;
+/// The properties of a single setting, e.g. `802-11-wireless`, keyed by property name.
+pub type VariantMap = HashMap<String, Variant<Box<RefArg>>>;
+
+/// The settings of a connection profile, keyed by setting name.
+pub type SettingsMap = HashMap<String, VariantMap>;
 
 const NM_SERVICE_MANAGER: &str = "org.freedesktop.NetworkManager";
 
 const NM_SERVICE_PATH: &str = "/org/freedesktop/NetworkManager";
 const NM_SETTINGS_PATH: &str = "/org/freedesktop/NetworkManager/Settings";
+const NM_DNS_MANAGER_PATH: &str = "/org/freedesktop/NetworkManager/DnsManager";
 
 const NM_SERVICE_INTERFACE: &str = "org.freedesktop.NetworkManager";
 const NM_SETTINGS_INTERFACE: &str = "org.freedesktop.NetworkManager.Settings";
@@ -30,10 +49,45 @@ const NM_DEVICE_INTERFACE: &str = "org.freedesktop.NetworkManager.Device";
 const NM_WIRELESS_INTERFACE: &str = "org.freedesktop.NetworkManager.Device.Wireless";
 const NM_ACCESS_POINT_INTERFACE: &str = "org.freedesktop.NetworkManager.AccessPoint";
 const NM_IP4CONFIG_INTERFACE: &str = "org.freedesktop.NetworkManager.IP4Config";
+const NM_DHCP4CONFIG_INTERFACE: &str = "org.freedesktop.NetworkManager.DHCP4Config";
+const NM_STATISTICS_INTERFACE: &str = "org.freedesktop.NetworkManager.Device.Statistics";
+const NM_DNS_MANAGER_INTERFACE: &str = "org.freedesktop.NetworkManager.DnsManager";
+
+pub const NM_WEP_KEY_TYPE_PASSPHRASE: u32 = 2;
+
+/// How often connectivity is read while waiting for it, in case changes are not signalled.
+const CONNECTIVITY_POLL_INTERVAL_MS: u64 = 1000;
+
+/// How long a scan is assumed to take on daemons without a `LastScan` property.
+const SCAN_FALLBACK_DELAY_MS: u64 = 5000;
 
-const NM_WEP_KEY_TYPE_PASSPHRASE: u32 = 2;
+/// The first version providing `AddAndActivateConnection2`.
+const ADD_AND_ACTIVATE_2_VERSION: (u32, u32, u32) = (1, 16, 0);
 
 const UNKNOWN_CONNECTION: &str = "org.freedesktop.NetworkManager.UnknownConnection";
+
+const DEVICE_PROPERTIES: &[&str] = &["Interface", "DeviceType"];
+const DNS_MANAGER_PROPERTIES: &[&str] = &["Mode", "RcManager"];
+const STATUS_PROPERTIES: &[&str] = &[
+    "NetworkingEnabled",
+    "WirelessEnabled",
+    "WwanEnabled",
+    "ConnectivityCheckEnabled",
+    "State",
+    "Connectivity",
+];
+const ACCESS_POINT_PROPERTIES: &[&str] = &[
+    "Ssid",
+    "Strength",
+    "Frequency",
+    "HwAddress",
+    "Flags",
+    "WpaFlags",
+    "RsnFlags",
+    "Mode",
+    "MaxBitrate",
+];
+
 const METHOD_RETRY_ERROR_NAMES: &[&str; 1] = &[UNKNOWN_CONNECTION];
 
 pub struct DBusNetworkManager {
@@ -41,9 +95,68 @@ pub struct DBusNetworkManager {
 }
 
 impl DBusNetworkManager {
-    pub fn new(method_timeout: Option<u64>) -> Self {
+    pub fn new(method_timeout: Option<u64>) -> Result<Self> {
+        Ok(DBusNetworkManager {
+            dbus: DBusApi::new(
+                NM_SERVICE_MANAGER,
+                METHOD_RETRY_ERROR_NAMES,
+                method_timeout,
+                None,
+                None,
+            )?,
+        })
+    }
+
+    pub fn with_retries(max_retries: usize, backoff: Backoff) -> Result<Self> {
+        Ok(DBusNetworkManager {
+            dbus: DBusApi::new(
+                NM_SERVICE_MANAGER,
+                METHOD_RETRY_ERROR_NAMES,
+                None,
+                Some(max_retries),
+                Some(backoff),
+            )?,
+        })
+    }
+
+    pub fn with_bus_type(bus_type: BusType, method_timeout: Option<u64>) -> Result<Self> {
+        Ok(DBusNetworkManager {
+            dbus: DBusApi::with_bus_type(
+                bus_type,
+                NM_SERVICE_MANAGER,
+                METHOD_RETRY_ERROR_NAMES,
+                method_timeout,
+                None,
+                None,
+            )?,
+        })
+    }
+
+    pub fn from_connection(connection: Rc<DBusConnection>, method_timeout: Option<u64>) -> Self {
+        DBusNetworkManager {
+            dbus: DBusApi::from_connection(
+                connection,
+                NM_SERVICE_MANAGER,
+                METHOD_RETRY_ERROR_NAMES,
+                method_timeout,
+                None,
+                None,
+            ),
+        }
+    }
+
+    /// Talks to a mock service on the session bus instead of Network Manager.
+    #[cfg(test)]
+    pub fn with_mock_service(service: &'static str) -> Self {
         DBusNetworkManager {
-            dbus: DBusApi::new(NM_SERVICE_MANAGER, METHOD_RETRY_ERROR_NAMES, method_timeout),
+            dbus: DBusApi::with_bus_type(
+                BusType::Session,
+                service,
+                METHOD_RETRY_ERROR_NAMES,
+                Some(2),
+                Some(1),
+                None,
+            ).unwrap(),
         }
     }
 
@@ -51,46 +164,363 @@ impl DBusNetworkManager {
         self.dbus.method_timeout()
     }
 
+    pub fn connection(&self) -> &DBusConnection {
+        self.dbus.connection()
+    }
+
+    pub fn signal_router(&self) -> SignalRouter {
+        self.dbus.signal_router()
+    }
+
+    pub fn set_dry_run(&self, dry_run: bool) {
+        self.dbus.set_dry_run(dry_run)
+    }
+
+    pub fn set_retry_predicate<F>(&mut self, predicate: F)
+    where
+        F: Fn(&::dbus::Error) -> RetryDecision + 'static,
+    {
+        self.dbus.set_retry_predicate(predicate)
+    }
+
+    /// Serves repeated reads of device and access point properties from a cache for `ttl`.
+    pub fn enable_property_cache(&self, ttl: Duration) -> Result<()> {
+        self.dbus.enable_property_cache(ttl)?;
+
+        Ok(())
+    }
+
+    fn cached_property<T>(&self, path: &str, interface: &str, name: &str) -> Result<T>
+    where
+        DBusApi: VariantTo<T>,
+    {
+        Ok(self.dbus.cached_property(path, interface, name)?)
+    }
+
     pub fn get_state(&self) -> Result<NetworkManagerState> {
         let response = self.dbus
-            .call(NM_SERVICE_PATH, NM_SERVICE_INTERFACE, "state")?;
+            .query(NM_SERVICE_PATH, NM_SERVICE_INTERFACE, "state")?;
 
         let state: u32 = self.dbus.extract(&response)?;
 
         Ok(NetworkManagerState::from(state))
     }
 
+    pub fn get_status(&self) -> Result<NetworkManagerStatus> {
+        let properties = self.dbus
+            .get_many(NM_SERVICE_PATH, NM_SERVICE_INTERFACE, STATUS_PROPERTIES)?;
+
+        // ConnectivityCheckEnabled is missing before Network Manager 1.10
+        let connectivity_check_enabled = if properties.contains("ConnectivityCheckEnabled") {
+            Some(properties.get("ConnectivityCheckEnabled")?)
+        } else {
+            None
+        };
+
+        Ok(NetworkManagerStatus {
+            networking_enabled: properties.get("NetworkingEnabled")?,
+            wireless_enabled: properties.get("WirelessEnabled")?,
+            wwan_enabled: properties.get("WwanEnabled")?,
+            connectivity_check_enabled: connectivity_check_enabled,
+            state: NetworkManagerState::from(properties.get::<u32>("State")?),
+            connectivity: Connectivity::from(properties.get::<u32>("Connectivity")?),
+        })
+    }
+
+    pub fn state_changed(&self) -> Result<Signals> {
+        Ok(self.dbus
+            .signals(NM_SERVICE_PATH, NM_SERVICE_INTERFACE, "StateChanged")?)
+    }
+
+    /// Reads the connectivity found by the last check, without checking again.
+    pub fn get_connectivity(&self) -> Result<Connectivity> {
+        let connectivity: u32 = self.dbus
+            .property(NM_SERVICE_PATH, NM_SERVICE_INTERFACE, "Connectivity")?;
+
+        Ok(Connectivity::from(connectivity))
+    }
+
+    /// Checks connectivity right away, which may take as long as the check's HTTP request.
     pub fn check_connectivity(&self) -> Result<Connectivity> {
         let response = self.dbus
-            .call(NM_SERVICE_PATH, NM_SERVICE_INTERFACE, "CheckConnectivity")?;
+            .query(NM_SERVICE_PATH, NM_SERVICE_INTERFACE, "CheckConnectivity")?;
 
         let connectivity: u32 = self.dbus.extract(&response)?;
 
         Ok(Connectivity::from(connectivity))
     }
 
+    /// Waits until connectivity is at least `target`, failing if it is not within `timeout`.
+    pub fn wait_for_connectivity(
+        &self,
+        target: Connectivity,
+        timeout: Duration,
+    ) -> Result<Connectivity> {
+        let mut changes = self.dbus.properties_changed(NM_SERVICE_PATH)?;
+
+        let deadline = Instant::now() + timeout;
+        let poll_interval = Duration::from_millis(CONNECTIVITY_POLL_INTERVAL_MS);
+
+        let mut connectivity = self.get_connectivity()?;
+
+        while connectivity < target {
+            let now = Instant::now();
+
+            if now >= deadline {
+                bail!(ErrorKind::Timeout(format!(
+                    "Timed out waiting for connectivity {:?}: {:?}",
+                    target, connectivity
+                )));
+            }
+
+            let change = changes
+                .next_timeout(cmp::min(deadline - now, poll_interval))
+                .and_then(|change| decode_connectivity_changed(&change));
+
+            connectivity = match change {
+                Some(connectivity) => connectivity,
+                None => self.get_connectivity()?,
+            };
+
+            debug!("Connectivity: {:?}", connectivity);
+        }
+
+        Ok(connectivity)
+    }
+
+    pub fn get_version(&self) -> Result<String> {
+        Ok(self.dbus
+            .property(NM_SERVICE_PATH, NM_SERVICE_INTERFACE, "Version")?)
+    }
+
+    pub fn get_dns_configuration(&self) -> Result<Vec<DnsEntry>> {
+        Ok(self.dbus
+            .property(NM_DNS_MANAGER_PATH, NM_DNS_MANAGER_INTERFACE, "Configuration")?)
+    }
+
+    pub fn get_dns_manager_info(&self) -> Result<DnsManagerInfo> {
+        let properties = self.dbus.get_many(
+            NM_DNS_MANAGER_PATH,
+            NM_DNS_MANAGER_INTERFACE,
+            DNS_MANAGER_PROPERTIES,
+        )?;
+
+        Ok(DnsManagerInfo {
+            mode: properties.get("Mode")?,
+            rc_manager: properties.get("RcManager")?,
+        })
+    }
+
     pub fn is_wireless_enabled(&self) -> Result<bool> {
-        self.dbus
-            .property(NM_SERVICE_PATH, NM_SERVICE_INTERFACE, "WirelessEnabled")
+        Ok(self.dbus
+            .property(NM_SERVICE_PATH, NM_SERVICE_INTERFACE, "WirelessEnabled")?)
+    }
+
+    pub fn set_wireless_enabled(&self, enabled: bool) -> Result<()> {
+        Ok(self.dbus.set_property(
+            NM_SERVICE_PATH,
+            NM_SERVICE_INTERFACE,
+            "WirelessEnabled",
+            enabled,
+        )?)
+    }
+
+    pub fn is_wwan_enabled(&self) -> Result<bool> {
+        Ok(self.dbus
+            .property(NM_SERVICE_PATH, NM_SERVICE_INTERFACE, "WwanEnabled")?)
+    }
+
+    pub fn set_wwan_enabled(&self, enabled: bool) -> Result<()> {
+        Ok(self.dbus.set_property(
+            NM_SERVICE_PATH,
+            NM_SERVICE_INTERFACE,
+            "WwanEnabled",
+            enabled,
+        )?)
     }
 
     pub fn is_networking_enabled(&self) -> Result<bool> {
-        self.dbus
-            .property(NM_SERVICE_PATH, NM_SERVICE_INTERFACE, "NetworkingEnabled")
+        Ok(self.dbus
+            .property(NM_SERVICE_PATH, NM_SERVICE_INTERFACE, "NetworkingEnabled")?)
+    }
+
+    pub fn enable(&self, enable: bool) -> Result<()> {
+        self.dbus.call_with_args(
+            NM_SERVICE_PATH,
+            NM_SERVICE_INTERFACE,
+            "Enable",
+            &[&enable as &RefArg],
+        )?;
+
+        Ok(())
+    }
+
+    pub fn reload(&self, flags: u32) -> Result<()> {
+        self.dbus.call_with_args(
+            NM_SERVICE_PATH,
+            NM_SERVICE_INTERFACE,
+            "Reload",
+            &[&flags as &RefArg],
+        )?;
+
+        Ok(())
+    }
+
+    pub fn create_checkpoint(
+        &self,
+        devices: &[&str],
+        rollback_timeout: u32,
+        flags: CheckpointFlags,
+    ) -> Result<String> {
+        let mut paths = Vec::with_capacity(devices.len());
+        for device in devices {
+            paths.push(Path::new(*device)?);
+        }
+
+        let response = self.dbus.call_with_args(
+            NM_SERVICE_PATH,
+            NM_SERVICE_INTERFACE,
+            "CheckpointCreate",
+            &[
+                &paths as &RefArg,
+                &rollback_timeout as &RefArg,
+                &flags.bits() as &RefArg,
+            ],
+        )?;
+
+        let checkpoint: Path = self.dbus.extract(&response)?;
+
+        Ok(path_to_string(&checkpoint)?)
+    }
+
+    pub fn destroy_checkpoint(&self, path: &str) -> Result<()> {
+        self.dbus.call_with_args(
+            NM_SERVICE_PATH,
+            NM_SERVICE_INTERFACE,
+            "CheckpointDestroy",
+            &[&Path::new(path)? as &RefArg],
+        )?;
+
+        Ok(())
+    }
+
+    pub fn checkpoint_adjust_rollback_timeout(&self, path: &str, add_timeout: u32) -> Result<()> {
+        self.dbus.call_with_args(
+            NM_SERVICE_PATH,
+            NM_SERVICE_INTERFACE,
+            "CheckpointAdjustRollbackTimeout",
+            &[&Path::new(path)? as &RefArg, &add_timeout as &RefArg],
+        )?;
+
+        Ok(())
+    }
+
+    /// Rolls back to the checkpoint at `path`, returning the result code per device path.
+    pub fn rollback_checkpoint(&self, path: &str) -> Result<HashMap<String, u32>> {
+        let response = self.dbus.call_with_args(
+            NM_SERVICE_PATH,
+            NM_SERVICE_INTERFACE,
+            "CheckpointRollback",
+            &[&Path::new(path)? as &RefArg],
+        )?;
+
+        Ok(self.dbus.extract(&response)?)
     }
 
     pub fn list_connections(&self) -> Result<Vec<String>> {
         let response = self.dbus
-            .call(NM_SETTINGS_PATH, NM_SETTINGS_INTERFACE, "ListConnections")?;
+            .query(NM_SETTINGS_PATH, NM_SETTINGS_INTERFACE, "ListConnections")?;
 
         let array: Array<Path, _> = self.dbus.extract(&response)?;
 
-        Ok(array.map(|e| e.to_string()).collect())
+        Ok(paths_to_strings(array)?)
+    }
+
+    pub fn get_connection_by_uuid(&self, uuid: &str) -> Result<String> {
+        let response = self.dbus.query_with_args(
+            NM_SETTINGS_PATH,
+            NM_SETTINGS_INTERFACE,
+            "GetConnectionByUuid",
+            &[&uuid.to_string() as &RefArg],
+        )?;
+
+        let path: Path = self.dbus.extract(&response)?;
+
+        Ok(path_to_string(&path)?)
+    }
+
+    /// Saves a new profile to disk without activating it, returning the profile path.
+    pub fn add_connection(&self, settings: &SettingsMap) -> Result<String> {
+        self.add_connection_with(settings, "AddConnection")
+    }
+
+    /// Adds a new profile like `add_connection`, without saving it to disk.
+    pub fn add_connection_unsaved(&self, settings: &SettingsMap) -> Result<String> {
+        self.add_connection_with(settings, "AddConnectionUnsaved")
+    }
+
+    /// Saves a new VPN profile, returning the profile path; see `settings::vpn_settings`.
+    pub fn add_vpn_connection(
+        &self,
+        id: &str,
+        service_type: &str,
+        data: HashMap<String, String>,
+        secrets: HashMap<String, String>,
+    ) -> Result<String> {
+        self.add_connection(&vpn_settings(id, service_type, data, secrets))
+    }
+
+    fn add_connection_with(&self, settings: &SettingsMap, method: &str) -> Result<String> {
+        let response = self.dbus.call_with_args(
+            NM_SETTINGS_PATH,
+            NM_SETTINGS_INTERFACE,
+            method,
+            &[settings as &RefArg],
+        )?;
+
+        let path: Path = self.dbus.extract(&response)?;
+
+        Ok(path_to_string(&path)?)
     }
 
     pub fn get_active_connections(&self) -> Result<Vec<String>> {
-        self.dbus
-            .property(NM_SERVICE_PATH, NM_SERVICE_INTERFACE, "ActiveConnections")
+        Ok(self.dbus
+            .property(NM_SERVICE_PATH, NM_SERVICE_INTERFACE, "ActiveConnections")?)
+    }
+
+    /// Reads the path of the active connection carrying the default route, if any.
+    pub fn get_primary_connection(&self) -> Result<Option<String>> {
+        let path: String = self.dbus
+            .property(NM_SERVICE_PATH, NM_SERVICE_INTERFACE, "PrimaryConnection")?;
+
+        // The root path stands for no connection
+        if path == "/" {
+            Ok(None)
+        } else {
+            Ok(Some(path))
+        }
+    }
+
+    /// Reads the type of the primary connection, e.g. `802-11-wireless`, or `None` if there is
+    /// no primary connection.
+    pub fn get_primary_connection_type(&self) -> Result<Option<String>> {
+        let kind: String = self.dbus.property(
+            NM_SERVICE_PATH,
+            NM_SERVICE_INTERFACE,
+            "PrimaryConnectionType",
+        )?;
+
+        if kind.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(kind))
+        }
+    }
+
+    pub fn get_active_connection(&self, path: &str) -> Result<ActiveConnection> {
+        let properties = self.dbus.get_all(path, NM_ACTIVE_INTERFACE)?;
+
+        decode_active_connection(path, &properties)
     }
 
     pub fn get_active_connection_path(&self, path: &str) -> Option<String> {
@@ -108,9 +538,52 @@ impl DBusNetworkManager {
         Ok(ConnectionState::from(state))
     }
 
+    /// Waits until the active connection at `path` reaches `target`, failing if it does not
+    /// within `timeout` or if it is deactivated on the way.
+    pub fn wait_for_active_connection_state(
+        &self,
+        path: &str,
+        target: ConnectionState,
+        timeout: Duration,
+    ) -> Result<ConnectionState> {
+        let mut signals = self.dbus
+            .signals(path, NM_ACTIVE_INTERFACE, "StateChanged")?;
+
+        let deadline = Instant::now() + timeout;
+
+        let mut state = self.get_connection_state(path)?;
+
+        while state != target {
+            let now = Instant::now();
+
+            if now >= deadline {
+                bail!(ErrorKind::Timeout(format!(
+                    "Timed out waiting for active connection state {:?}: {:?}",
+                    target, state
+                )));
+            }
+
+            if let Some(signal) = signals.next_timeout(deadline - now) {
+                if let Some((new_state, reason)) = decode_active_state_changed(&signal) {
+                    debug!("Active connection state changed: {:?} ({:?})", new_state, reason);
+
+                    if new_state == ConnectionState::Deactivated
+                        && target != ConnectionState::Deactivated
+                    {
+                        bail!(ErrorKind::ActivationFailed(path.to_string(), reason));
+                    }
+
+                    state = new_state;
+                }
+            }
+        }
+
+        Ok(state)
+    }
+
     pub fn get_connection_settings(&self, path: &str) -> Result<ConnectionSettings> {
         let response = self.dbus
-            .call(path, NM_CONNECTION_INTERFACE, "GetSettings")?;
+            .query(path, NM_CONNECTION_INTERFACE, "GetSettings")?;
 
         let dict: Dict<&str, Dict<&str, Variant<Iter>, _>, _> = self.dbus.extract(&response)?;
 
@@ -119,8 +592,9 @@ impl DBusNetworkManager {
         let mut uuid = String::new();
         let mut ssid = Ssid::new();
         let mut mode = String::new();
+        let mut dns_search = Vec::new();
 
-        for (_, v1) in dict {
+        for (k1, v1) in dict {
             for (k2, mut v2) in v1 {
                 match k2 {
                     "id" => {
@@ -138,6 +612,9 @@ impl DBusNetworkManager {
                     "mode" => {
                         mode = extract::<String>(&mut v2)?;
                     },
+                    "dns-search" if k1 == "ipv4" || k1 == "ipv6" => {
+                        dns_search.extend(variant_iter_to_vec_string(&mut v2)?);
+                    },
                     _ => {},
                 }
             }
@@ -149,248 +626,442 @@ impl DBusNetworkManager {
             uuid: uuid,
             ssid: ssid,
             mode: mode,
+            dns_search: dns_search,
         })
     }
 
-    pub fn get_active_connection_devices(&self, path: &str) -> Result<Vec<String>> {
-        self.dbus.property(path, NM_ACTIVE_INTERFACE, "Devices")
-    }
+    /// Reads all settings of the profile at `path`.
+    ///
+    /// The values keep the signatures NetworkManager sent them with, so the map can be modified
+    /// and passed back to `update_connection`.
+    pub fn get_connection_settings_map(&self, path: &str) -> Result<SettingsMap> {
+        let response = self.dbus
+            .query(path, NM_CONNECTION_INTERFACE, "GetSettings")?;
 
-    pub fn get_ip4_config(&self, path: &str) -> Result<IP4Config> {
-        let response = self.dbus.call(path, NM_IP4CONFIG_INTERFACE, "GetGateway")?;
-        let dict: Dict<&str, Dict<&str, Variant<Iter>, _>, _> = self.dbus.extract(&response)?;
+        self.received_settings(&response)
+    }
 
-        let mut address = String::new();
-        let mut gateway = String::new();
-        let mut route1 = String::new();
-        let mut route2 = String::new();
-        let mut dns = String::new();
-        let mut domain = String::new();
+    /// Decodes an `a{sa{sv}}` reply, keeping the message item of every value for sending it
+    /// back.
+    fn received_settings(&self, response: &Message) -> Result<SettingsMap> {
+        let mut settings: SettingsMap = self.dbus.extract(response)?;
 
-        for(_, v1) in dict {
-            for(k2, mut v2) in v1 {
-                match k2 {
-                    "gateway" => {
-                        gateway = extract::<String>(&mut v2)?;
-                    },
-                    _ => {},
+        if let Some(MessageItem::Array(items, _)) = response.get_items().into_iter().next() {
+            for (setting, key, item) in settings_items(items) {
+                if let Some(values) = settings.get_mut(&setting) {
+                    if let Some(Variant(value)) = values.remove(&key) {
+                        let value = ReceivedValue {
+                            value: value,
+                            item: item,
+                        };
+                        values.insert(key, Variant(Box::new(value)));
+                    }
                 }
             }
         }
 
-        Ok(IP4Config {
-            address: address,
-            gateway: gateway,
-            route1: route1,
-            route2: route2,
-            dns: dns,
-            domain: domain,
-        })
-    }
-
-    pub fn get_ip4config_gateway(&self, path: &str) -> Result<String> {
-        self.dbus.property(path, NM_IP4CONFIG_INTERFACE, "Gateway")
+        Ok(settings)
     }
 
-    pub fn delete_connection(&self, path: &str) -> Result<()> {
-        self.dbus.call(path, NM_CONNECTION_INTERFACE, "Delete")?;
+    /// Replaces all settings of the profile at `path` and saves them to disk.
+    ///
+    /// To change single values, read the settings with `get_connection_settings_map` and
+    /// replace or remove values in the map before passing it here.
+    pub fn update_connection(&self, path: &str, settings: &SettingsMap) -> Result<()> {
+        self.dbus.call_with_args(
+            path,
+            NM_CONNECTION_INTERFACE,
+            "Update",
+            &[settings as &RefArg],
+        )?;
 
         Ok(())
     }
 
-    pub fn activate_connection(&self, path: &str) -> Result<()> {
+    /// Replaces all settings of the profile at `path` without saving them to disk.
+    pub fn update_connection_unsaved(&self, path: &str, settings: &SettingsMap) -> Result<()> {
         self.dbus.call_with_args(
-            NM_SERVICE_PATH,
-            NM_SERVICE_INTERFACE,
-            "ActivateConnection",
-            &[
-                &Path::new(path)? as &RefArg,
-                &Path::new("/")? as &RefArg,
-                &Path::new("/")? as &RefArg,
-            ],
+            path,
+            NM_CONNECTION_INTERFACE,
+            "UpdateUnsaved",
+            &[settings as &RefArg],
         )?;
 
         Ok(())
     }
 
-    pub fn deactivate_connection(&self, path: &str) -> Result<()> {
-        self.dbus.call_with_args(
-            NM_SERVICE_PATH,
-            NM_SERVICE_INTERFACE,
-            "DeactivateConnection",
-            &[&Path::new(path)? as &RefArg],
-        )?;
+    /// Enables or disables automatic activation of the profile at `path`.
+    pub fn set_autoconnect(&self, path: &str, enabled: bool) -> Result<()> {
+        self.update_connection_value(path, "connection", "autoconnect", enabled)
+    }
 
-        Ok(())
+    /// Sets the autoconnect priority of the profile at `path`; among the profiles available on
+    /// a device, the one with the highest priority is activated.
+    pub fn set_autoconnect_priority(&self, path: &str, priority: i32) -> Result<()> {
+        self.update_connection_value(path, "connection", "autoconnect-priority", priority)
     }
 
-    pub fn connect_to_access_point(
-        &self,
-        device_path: &str,
-        access_point: &AccessPoint,
-        credentials: &AccessPointCredentials,
-    ) -> Result<(String, String)> {
-        let mut settings: HashMap<String, VariantMap> = HashMap::new();
+    /// Sets the `connection.metered` setting of the profile at `path`.
+    pub fn set_metered(&self, path: &str, metered: Metered) -> Result<()> {
+        self.update_connection_value(path, "connection", "metered", metered.as_i32())
+    }
 
-        let mut wireless: VariantMap = HashMap::new();
-        add_val(
-            &mut wireless,
-            "ssid",
-            access_point.ssid().as_bytes().to_vec(),
-        );
-        settings.insert("802-11-wireless".to_string(), wireless);
+    /// Sets the `802-11-wireless.powersave` setting of the Wi-Fi profile at `path`.
+    pub fn set_wifi_powersave(&self, path: &str, powersave: WirelessPowersave) -> Result<()> {
+        self.update_connection_value(path, "802-11-wireless", "powersave", powersave.as_u32())
+    }
 
-        match *credentials {
-            AccessPointCredentials::Wep { ref passphrase } => {
-                let mut security_settings: VariantMap = HashMap::new();
+    /// Sets the MTU of the profile at `path`, which must be a wired or Wi-Fi profile.
+    pub fn set_mtu(&self, path: &str, mtu: u32) -> Result<()> {
+        let kind = self.get_connection_settings(path)?.kind;
 
-                add_val(
-                    &mut security_settings,
-                    "wep-key-type",
-                    NM_WEP_KEY_TYPE_PASSPHRASE,
-                );
-                add_str(
-                    &mut security_settings,
-                    "wep-key0",
-                    verify_ascii_password(passphrase)?,
-                );
+        if kind != "802-3-ethernet" && kind != "802-11-wireless" {
+            bail!(ErrorKind::Settings(format!(
+                "Setting the MTU of {} connections is not supported",
+                kind
+            )));
+        }
 
-                settings.insert("802-11-wireless-security".to_string(), security_settings);
-            },
-            AccessPointCredentials::Wpa { ref passphrase } => {
-                let mut security_settings: VariantMap = HashMap::new();
-
-                add_str(&mut security_settings, "key-mgmt", "wpa-psk");
-                add_str(
-                    &mut security_settings,
-                    "psk",
-                    verify_ascii_password(passphrase)?,
-                );
+        self.update_connection_value(path, &kind, "mtu", mtu)
+    }
 
-                settings.insert("802-11-wireless-security".to_string(), security_settings);
-            },
-            AccessPointCredentials::Enterprise {
-                ref identity,
-                ref passphrase,
-            } => {
-                let mut security_settings: VariantMap = HashMap::new();
-
-                add_str(&mut security_settings, "key-mgmt", "wpa-eap");
-
-                let mut eap: VariantMap = HashMap::new();
-                add_val(&mut eap, "eap", vec!["peap".to_string()]);
-                add_str(&mut eap, "identity", identity as &str);
-                add_str(&mut eap, "password", passphrase as &str);
-                add_str(&mut eap, "phase2-auth", "mschapv2");
-
-                settings.insert("802-11-wireless-security".to_string(), security_settings);
-                settings.insert("802-1x".to_string(), eap);
-            },
-            AccessPointCredentials::None => {},
-        };
+    /// Reads the firewalld zone of the profile at `path`, if one is set.
+    pub fn get_connection_zone(&self, path: &str) -> Result<Option<String>> {
+        let settings = self.get_connection_settings_map(path)?;
 
-        let response = self.dbus.call_with_args(
-            NM_SERVICE_PATH,
-            NM_SERVICE_INTERFACE,
-            "AddAndActivateConnection",
-            &[
-                &settings as &RefArg,
-                &Path::new(device_path.to_string())? as &RefArg,
-                &Path::new(access_point.path.to_string())? as &RefArg,
-            ],
-        )?;
+        let zone: Option<String> = settings
+            .get("connection")
+            .and_then(|connection| connection.get("zone"))
+            .and_then(DBusApi::variant_to);
 
-        let (conn_path, active_connection): (Path, Path) = self.dbus.extract_two(&response)?;
+        Ok(zone.and_then(|zone| if zone.is_empty() { None } else { Some(zone) }))
+    }
 
-        Ok((
-            path_to_string(&conn_path)?,
-            path_to_string(&active_connection)?,
-        ))
+    /// Sets the firewalld zone of the profile at `path`, or clears it with `None` so that the
+    /// default zone applies.
+    pub fn set_connection_zone(&self, path: &str, zone: Option<&str>) -> Result<()> {
+        match zone {
+            Some(zone) => {
+                self.update_connection_value(path, "connection", "zone", zone.to_string())
+            },
+            None => self.remove_connection_value(path, "connection", "zone"),
+        }
+    }
+
+    /// Restricts the profile at `path` to the interface `interface`, or with `None` allows it
+    /// on any compatible device.
+    pub fn bind_to_interface(&self, path: &str, interface: Option<&str>) -> Result<()> {
+        match interface {
+            Some(interface) => self.update_connection_value(
+                path,
+                "connection",
+                "interface-name",
+                interface.to_string(),
+            ),
+            None => self.remove_connection_value(path, "connection", "interface-name"),
+        }
+    }
+
+    /// Changes a single value of the profile at `path`, keeping all its other settings.
+    fn update_connection_value<V>(
+        &self,
+        path: &str,
+        setting: &str,
+        key: &str,
+        value: V,
+    ) -> Result<()>
+    where
+        V: Into<MessageItem>,
+    {
+        self.patch_connection_value(path, setting, key, Some(value.into()))
+    }
+
+    /// Resets a single value of the profile at `path` to its default, keeping all its other
+    /// settings.
+    fn remove_connection_value(&self, path: &str, setting: &str, key: &str) -> Result<()> {
+        self.patch_connection_value(path, setting, key, None)
+    }
+
+    /// Replaces or, with `None`, removes a single value of the profile at `path`.
+    fn patch_connection_value(
+        &self,
+        path: &str,
+        setting: &str,
+        key: &str,
+        value: Option<MessageItem>,
+    ) -> Result<()> {
+        let mut settings = self.get_connection_settings_map(path)?;
+
+        match value {
+            Some(value) => {
+                let values = settings
+                    .entry(setting.to_string())
+                    .or_insert_with(HashMap::new);
+                add_val(values, key, value);
+            },
+            None => {
+                if let Some(values) = settings.get_mut(setting) {
+                    values.remove(key);
+                }
+            },
+        }
+
+        self.update_connection(path, &settings)
+    }
+
+    /// Reads the secrets of the `setting_name` setting, which may require authorization.
+    pub fn get_connection_secrets(&self, path: &str, setting_name: &str) -> Result<SettingsMap> {
+        let response = self.dbus.query_with_args(
+            path,
+            NM_CONNECTION_INTERFACE,
+            "GetSecrets",
+            &[&setting_name.to_string() as &RefArg],
+        )?;
+
+        self.received_settings(&response)
+    }
+
+    pub fn get_active_connection_devices(&self, path: &str) -> Result<Vec<String>> {
+        Ok(self.dbus.property(path, NM_ACTIVE_INTERFACE, "Devices")?)
+    }
+
+    /// Reads the current IPv4 configuration of the device at `path`.
+    ///
+    /// The configuration is empty while the device has no IPv4 configuration.
+    pub fn get_ip4_config(&self, path: &str) -> Result<IP4Config> {
+        let config_path = self.get_device_ip4config_path(path)?;
+
+        if config_path == "/" {
+            return Ok(IP4Config::default());
+        }
+
+        let properties = self.dbus.get_all(&config_path, NM_IP4CONFIG_INTERFACE)?;
+
+        Ok(decode_ip4_config(&properties))
+    }
+
+    pub fn get_ip4config_gateway(&self, path: &str) -> Result<String> {
+        let config_path = self.get_device_ip4config_path(path)?;
+
+        if config_path == "/" {
+            return Ok(String::new());
+        }
+
+        Ok(self.dbus
+            .property(&config_path, NM_IP4CONFIG_INTERFACE, "Gateway")?)
+    }
+
+    /// Reads the options of the DHCP lease of the device at `path`, e.g. `dhcp_lease_time`.
+    ///
+    /// The options are empty while the device has no DHCP lease.
+    pub fn get_dhcp4_config(&self, path: &str) -> Result<HashMap<String, String>> {
+        let config_path: String = self.dbus
+            .property(path, NM_DEVICE_INTERFACE, "Dhcp4Config")?;
+
+        if config_path == "/" {
+            return Ok(HashMap::new());
+        }
+
+        Ok(self.dbus
+            .property(&config_path, NM_DHCP4CONFIG_INTERFACE, "Options")?)
+    }
+
+    /// Deletes the profile at `path`, failing with `DBusError::NotFound` if it is already gone.
+    pub fn delete_connection(&self, path: &str) -> Result<()> {
+        match self.dbus.call(path, NM_CONNECTION_INTERFACE, "Delete") {
+            Ok(_) => Ok(()),
+            Err(DBusError::UnknownMethod(message)) => Err(DBusError::NotFound(message).into()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Activates the profile at `path` on `device_path`, returning the active connection path.
+    ///
+    /// Either of `device_path` and `specific_object` may be `"/"` to let NetworkManager choose.
+    pub fn activate_connection(
+        &self,
+        path: &str,
+        device_path: &str,
+        specific_object: &str,
+    ) -> Result<String> {
+        let response = self.dbus.call_with_args(
+            NM_SERVICE_PATH,
+            NM_SERVICE_INTERFACE,
+            "ActivateConnection",
+            &[
+                &Path::new(path)? as &RefArg,
+                &Path::new(device_path)? as &RefArg,
+                &Path::new(specific_object)? as &RefArg,
+            ],
+        )?;
+
+        let active_path: Path = self.dbus.extract(&response)?;
+
+        Ok(path_to_string(&active_path)?)
+    }
+
+    pub fn deactivate_connection(&self, path: &str) -> Result<()> {
+        self.dbus.call_with_args(
+            NM_SERVICE_PATH,
+            NM_SERVICE_INTERFACE,
+            "DeactivateConnection",
+            &[&Path::new(path)? as &RefArg],
+        )?;
+
+        Ok(())
+    }
+
+    pub fn connect_to_access_point(
+        &self,
+        device_path: &str,
+        access_point: &AccessPoint,
+        credentials: &AccessPointCredentials,
+    ) -> Result<(String, String)> {
+        let settings = access_point_settings(access_point.ssid(), credentials)?;
+
+        self.add_and_activate_connection(&settings, device_path, &access_point.path)
+    }
+
+    /// Connects to the network `ssid` which does not broadcast its SSID.
+    ///
+    /// There is no access point object to refer to, so NetworkManager probes for the network
+    /// itself. Returns the connection and active connection paths.
+    pub fn connect_to_hidden_ssid<T>(
+        &self,
+        device_path: &str,
+        ssid: &T,
+        password: Option<&str>,
+    ) -> Result<(String, String)>
+    where
+        T: AsSsidSlice + ?Sized,
+    {
+        let settings = hidden_ssid_settings(ssid.as_ssid_slice()?, password)?;
+
+        self.add_and_activate_connection(&settings, device_path, "/")
     }
 
     pub fn create_hotspot<T>(
         &self,
         device_path: &str,
-        interface: &str,
         ssid: &T,
         password: Option<&str>,
         address: Option<Ipv4Addr>,
+        band: Option<&str>,
+        channel: Option<u32>,
     ) -> Result<(String, String)>
     where
         T: AsSsidSlice + ?Sized,
     {
-        let ssid = ssid.as_ssid_slice()?;
-        let ssid_vec = ssid.as_bytes().to_vec();
+        let interface = self.get_device_interface(device_path)?;
 
-        let mut wireless: VariantMap = HashMap::new();
-        add_val(&mut wireless, "ssid", ssid_vec);
-        add_str(&mut wireless, "band", "bg");
-        add_val(&mut wireless, "hidden", false);
-        add_str(&mut wireless, "mode", "ap");
+        let settings = hotspot_settings(
+            &interface,
+            ssid.as_ssid_slice()?,
+            password,
+            address,
+            band,
+            channel,
+        )?;
 
-        let mut connection: VariantMap = HashMap::new();
-        add_val(&mut connection, "autoconnect", false);
-        if let Ok(ssid_str) = ssid.as_str() {
-            add_str(&mut connection, "id", ssid_str);
-        }
-        add_str(&mut connection, "interface-name", interface);
-        add_str(&mut connection, "type", "802-11-wireless");
+        self.add_and_activate_connection(&settings, device_path, "/")
+    }
 
-        let mut ipv4: VariantMap = HashMap::new();
-        if let Some(address) = address {
-            add_str(&mut ipv4, "method", "manual");
+    /// Creates and activates a mobile broadband profile on the modem at `device_path`.
+    ///
+    /// Returns the connection and active connection paths.
+    pub fn create_gsm_connection(
+        &self,
+        device_path: &str,
+        config: &GsmConfig,
+    ) -> Result<(String, String)> {
+        let settings = gsm_settings(config)?;
 
-            let mut addr_map: VariantMap = HashMap::new();
-            add_str(&mut addr_map, "address", format!("{}", address));
-            add_val(&mut addr_map, "prefix", 24_u32);
+        self.add_and_activate_connection(&settings, device_path, "/")
+    }
 
-            add_val(&mut ipv4, "address-data", vec![addr_map]);
-        } else {
-            add_str(&mut ipv4, "method", "shared");
-        }
+    /// Saves and activates a new profile, returning the connection and active connection paths.
+    fn add_and_activate_connection(
+        &self,
+        settings: &SettingsMap,
+        device_path: &str,
+        specific_object: &str,
+    ) -> Result<(String, String)> {
+        let response = self.dbus.call_with_args(
+            NM_SERVICE_PATH,
+            NM_SERVICE_INTERFACE,
+            "AddAndActivateConnection",
+            &[
+                settings as &RefArg,
+                &Path::new(device_path)? as &RefArg,
+                &Path::new(specific_object)? as &RefArg,
+            ],
+        )?;
 
-        let mut settings: HashMap<String, VariantMap> = HashMap::new();
+        let (conn_path, active_connection): (Path, Path) = self.dbus.extract_two(&response)?;
 
-        if let Some(password) = password {
-            add_str(&mut wireless, "security", "802-11-wireless-security");
+        Ok((
+            path_to_string(&conn_path)?,
+            path_to_string(&active_connection)?,
+        ))
+    }
 
-            let mut security: VariantMap = HashMap::new();
-            add_str(&mut security, "key-mgmt", "wpa-psk");
-            add_str(&mut security, "psk", verify_ascii_password(password)?);
+    /// Adds a connection and activates it on the device at `device_path`, returning the new
+    /// connection, the active connection and the result map of the call.
+    ///
+    /// Network Manager 1.16 and newer get `AddAndActivateConnection2` with `options`; older
+    /// versions get `AddAndActivateConnection`, which takes no options and returns an empty
+    /// result map.
+    pub fn add_and_activate_connection_with_options(
+        &self,
+        settings: &SettingsMap,
+        device_path: &str,
+        specific_object: &str,
+        options: &VariantMap,
+    ) -> Result<(String, String, VariantMap)> {
+        let version = self.get_version()?;
 
-            settings.insert("802-11-wireless-security".to_string(), security);
+        match parse_version(&version) {
+            Some(version) if version >= ADD_AND_ACTIVATE_2_VERSION => {},
+            _ => {
+                let (path, active_path) =
+                    self.add_and_activate_connection(settings, device_path, specific_object)?;
+                return Ok((path, active_path, HashMap::new()));
+            },
         }
 
-        settings.insert("802-11-wireless".to_string(), wireless);
-        settings.insert("connection".to_string(), connection);
-        settings.insert("ipv4".to_string(), ipv4);
-
         let response = self.dbus.call_with_args(
             NM_SERVICE_PATH,
             NM_SERVICE_INTERFACE,
-            "AddAndActivateConnection",
+            "AddAndActivateConnection2",
             &[
-                &settings as &RefArg,
+                settings as &RefArg,
                 &Path::new(device_path)? as &RefArg,
-                &Path::new("/")? as &RefArg,
+                &Path::new(specific_object)? as &RefArg,
+                options as &RefArg,
             ],
         )?;
 
-        let (conn_path, active_connection): (Path, Path) = self.dbus.extract_two(&response)?;
+        let (conn_path, active_connection, result): (Path, Path, VariantMap) =
+            self.dbus.extract_three(&response)?;
 
         Ok((
             path_to_string(&conn_path)?,
             path_to_string(&active_connection)?,
+            result,
         ))
     }
 
     pub fn get_devices(&self) -> Result<Vec<String>> {
-        self.dbus
-            .property(NM_SERVICE_PATH, NM_SERVICE_INTERFACE, "Devices")
+        let response = self.dbus
+            .query(NM_SERVICE_PATH, NM_SERVICE_INTERFACE, "GetDevices")?;
+
+        let array: Array<Path, _> = self.dbus.extract(&response)?;
+
+        Ok(paths_to_strings(array)?)
     }
 
-    pub fn get_device_by_interface(&self, interface: &str) -> Result<String> {
-        let response = self.dbus.call_with_args(
+    pub fn get_device_by_ip_iface(&self, interface: &str) -> Result<String> {
+        let response = self.dbus.query_with_args(
             NM_SERVICE_PATH,
             NM_SERVICE_INTERFACE,
             "GetDeviceByIpIface",
@@ -399,27 +1070,125 @@ impl DBusNetworkManager {
 
         let path: Path = self.dbus.extract(&response)?;
 
-        path_to_string(&path)
+        Ok(path_to_string(&path)?)
     }
 
-    pub fn get_device_interface(&self, path: &str) -> Result<String> {
-        self.dbus.property(path, NM_DEVICE_INTERFACE, "Interface")
+    /// Reads the properties a `Device` is built from in a single round-trip.
+    pub fn get_device_properties(&self, path: &str) -> Result<Properties> {
+        Ok(self.dbus
+            .get_many(path, NM_DEVICE_INTERFACE, DEVICE_PROPERTIES)?)
     }
 
-    pub fn get_device_type(&self, path: &str) -> Result<DeviceType> {
-        self.dbus.property(path, NM_DEVICE_INTERFACE, "DeviceType")
+    pub fn get_device_interface(&self, path: &str) -> Result<String> {
+        self.cached_property(path, NM_DEVICE_INTERFACE, "Interface")
     }
 
     pub fn get_device_state(&self, path: &str) -> Result<DeviceState> {
-        self.dbus.property(path, NM_DEVICE_INTERFACE, "State")
+        self.cached_property(path, NM_DEVICE_INTERFACE, "State")
+    }
+
+    /// Waits until the device reaches `target`, failing with `ErrorKind::Timeout` if it does
+    /// not within `timeout`.
+    pub fn wait_for_device_state(
+        &self,
+        path: &str,
+        target: &DeviceState,
+        timeout: Duration,
+    ) -> Result<DeviceState> {
+        let mut signals = self.dbus
+            .signals(path, NM_DEVICE_INTERFACE, "StateChanged")?;
+
+        let deadline = Instant::now() + timeout;
+
+        let mut state = self.dbus.property(path, NM_DEVICE_INTERFACE, "State")?;
+
+        while state != *target {
+            let now = Instant::now();
+
+            if now >= deadline {
+                bail!(ErrorKind::Timeout(format!(
+                    "Timed out waiting for device state {:?}: {:?}",
+                    target, state
+                )));
+            }
+
+            if let Some(signal) = signals.next_timeout(deadline - now) {
+                if let Some(new_state) = decode_device_state_changed(&signal) {
+                    debug!("Device state changed: {:?}", new_state);
+                    state = new_state;
+                }
+            }
+        }
+
+        Ok(state)
     }
 
     pub fn get_device_ip4config_path(&self, path: &str) -> Result<String> {
-        self.dbus.property(path, NM_DEVICE_INTERFACE, "Ip4Config")
+        Ok(self.dbus.property(path, NM_DEVICE_INTERFACE, "Ip4Config")?)
+    }
+
+    /// Sets how often the traffic counters of the device at `path` are updated; `0` disables
+    /// updates.
+    pub fn enable_device_statistics(&self, path: &str, refresh_ms: u32) -> Result<()> {
+        Ok(self.dbus.set_property(
+            path,
+            NM_STATISTICS_INTERFACE,
+            "RefreshRateMs",
+            refresh_ms,
+        )?)
+    }
+
+    pub fn get_device_statistics(&self, path: &str) -> Result<Stats> {
+        let properties = self.dbus.get_all(path, NM_STATISTICS_INTERFACE)?;
+
+        let counter = |name: &str| -> u64 {
+            properties
+                .get(name)
+                .and_then(DBusApi::variant_to)
+                .unwrap_or(0)
+        };
+
+        Ok(Stats {
+            tx_bytes: counter("TxBytes"),
+            rx_bytes: counter("RxBytes"),
+            refresh_rate_ms: properties
+                .get("RefreshRateMs")
+                .and_then(DBusApi::variant_to)
+                .unwrap_or(0),
+        })
+    }
+
+    /// Reads whether the connection active on the device at `path` is metered.
+    pub fn get_device_metered(&self, path: &str) -> Result<Metered> {
+        self.cached_property(path, NM_DEVICE_INTERFACE, "Metered")
+    }
+
+    /// Whether NetworkManager manages the device at `path` or leaves it to other tools.
+    pub fn is_device_managed(&self, path: &str) -> Result<bool> {
+        Ok(self.dbus.property(path, NM_DEVICE_INTERFACE, "Managed")?)
+    }
+
+    pub fn set_device_managed(&self, path: &str, managed: bool) -> Result<()> {
+        Ok(self.dbus
+            .set_property(path, NM_DEVICE_INTERFACE, "Managed", managed)?)
+    }
+
+    /// Whether the device at `path` may activate connections on its own.
+    pub fn is_device_autoconnect(&self, path: &str) -> Result<bool> {
+        Ok(self.dbus.property(path, NM_DEVICE_INTERFACE, "Autoconnect")?)
+    }
+
+    pub fn set_device_autoconnect(&self, path: &str, autoconnect: bool) -> Result<()> {
+        Ok(self.dbus
+            .set_property(path, NM_DEVICE_INTERFACE, "Autoconnect", autoconnect)?)
+    }
+
+    pub fn get_wireless_capabilities(&self, path: &str) -> Result<WirelessCapabilities> {
+        self.cached_property(path, NM_WIRELESS_INTERFACE, "WirelessCapabilities")
     }
 
     pub fn get_hardware_address(&self, path: &str) -> Result<String> {
-        self.dbus.property(path, NM_WIRELESS_INTERFACE, "HwAddress")
+        self.cached_property(path, NM_WIRELESS_INTERFACE, "HwAddress")
     }
 
     pub fn connect_device(&self, path: &str) -> Result<()> {
@@ -437,6 +1206,10 @@ impl DBusNetworkManager {
         Ok(())
     }
 
+    /// Deactivates the connection on the device and blocks autoconnect until it is connected
+    /// again manually.
+    ///
+    /// Fails with `DBusError::NotActive` if the device has no active connection.
     pub fn disconnect_device(&self, path: &str) -> Result<()> {
         self.dbus.call(path, NM_DEVICE_INTERFACE, "Disconnect")?;
 
@@ -444,7 +1217,10 @@ impl DBusNetworkManager {
     }
 
     pub fn request_access_point_scan(&self, path: &str) -> Result<()> {
-        let options: VariantMap = HashMap::new();
+        self.request_scan(path, HashMap::new())
+    }
+
+    pub fn request_scan(&self, path: &str, options: VariantMap) -> Result<()> {
         self.dbus.call_with_args(
             path,
             NM_WIRELESS_INTERFACE,
@@ -455,38 +1231,132 @@ impl DBusNetworkManager {
         Ok(())
     }
 
-    pub fn get_device_access_points(&self, path: &str) -> Result<Vec<String>> {
-        self.dbus
-            .property(path, NM_WIRELESS_INTERFACE, "AccessPoints")
-    }
+    /// Requests a scan on the wireless device at `path` and waits until it completes, failing
+    /// if it does not within `timeout`.
+    ///
+    /// Completion is detected by the `LastScan` timestamp advancing. Before Network Manager 1.12
+    /// the property is missing and the scan is instead given a fixed delay, cut short by
+    /// `timeout`.
+    pub fn wait_for_scan(&self, path: &str, timeout: Duration) -> Result<()> {
+        let mut changes = self.dbus.properties_changed(path)?;
 
-    pub fn get_access_point_ssid(&self, path: &str) -> Option<Ssid> {
-        if let Ok(ssid_vec) = self.dbus
-            .property::<Vec<u8>>(path, NM_ACCESS_POINT_INTERFACE, "Ssid")
+        let deadline = Instant::now() + timeout;
+
+        let last_scan = match self.dbus
+            .property::<i64>(path, NM_WIRELESS_INTERFACE, "LastScan")
         {
-            Ssid::from_bytes(ssid_vec).ok()
-        } else {
-            None
+            Ok(last_scan) => last_scan,
+            // GDBus reports unknown properties as invalid arguments
+            Err(DBusError::UnknownMethod(_)) | Err(DBusError::WrongType(_)) => {
+                self.request_access_point_scan(path)?;
+
+                let delay = Duration::from_millis(SCAN_FALLBACK_DELAY_MS);
+                thread::sleep(cmp::min(delay, timeout));
+
+                return Ok(());
+            },
+            Err(e) => bail!(e),
+        };
+
+        self.request_access_point_scan(path)?;
+
+        loop {
+            let now = Instant::now();
+
+            if now >= deadline {
+                bail!(ErrorKind::Timeout(format!(
+                    "Timed out waiting for the scan on {} to complete",
+                    path
+                )));
+            }
+
+            if let Some(change) = changes.next_timeout(deadline - now) {
+                match decode_last_scan_changed(&change) {
+                    Some(scan) if scan > last_scan => return Ok(()),
+                    _ => {},
+                }
+            }
         }
     }
 
-    pub fn get_access_point_strength(&self, path: &str) -> Result<u32> {
-        self.dbus
-            .property(path, NM_ACCESS_POINT_INTERFACE, "Strength")
+    pub fn get_access_points(&self, path: &str) -> Result<Vec<String>> {
+        let paths = match self.dbus
+            .query(path, NM_WIRELESS_INTERFACE, "GetAllAccessPoints")
+        {
+            Ok(response) => {
+                let array: Array<Path, _> = self.dbus.extract(&response)?;
+                array.map(|e| e.to_string()).collect()
+            },
+            Err(DBusError::UnknownMethod(_)) => {
+                // GetAllAccessPoints is missing before Network Manager 1.2
+                self.dbus
+                    .property(path, NM_WIRELESS_INTERFACE, "AccessPoints")?
+            },
+            Err(e) => bail!(e),
+        };
+
+        Ok(dedup_paths(paths))
     }
 
-    pub fn get_access_point_flags(&self, path: &str) -> Result<NM80211ApFlags> {
-        self.dbus.property(path, NM_ACCESS_POINT_INTERFACE, "Flags")
+    /// Reads the properties an `AccessPoint` is decoded from in a single round-trip.
+    pub fn get_access_point_properties(&self, path: &str) -> Result<Properties> {
+        Ok(self.dbus
+            .get_many(path, NM_ACCESS_POINT_INTERFACE, ACCESS_POINT_PROPERTIES)?)
     }
 
-    pub fn get_access_point_wpa_flags(&self, path: &str) -> Result<NM80211ApSecurityFlags> {
-        self.dbus
-            .property(path, NM_ACCESS_POINT_INTERFACE, "WpaFlags")
+    /// Decodes the security supported by the access point at `path` from its `Flags`,
+    /// `WpaFlags` and `RsnFlags`.
+    pub fn get_ap_security(&self, path: &str) -> Result<Security> {
+        let properties = self.dbus.get_many(
+            path,
+            NM_ACCESS_POINT_INTERFACE,
+            &["Flags", "WpaFlags", "RsnFlags"],
+        )?;
+
+        Ok(security_from_flags(
+            properties.get("Flags")?,
+            properties.get("WpaFlags")?,
+            properties.get("RsnFlags")?,
+        ))
     }
 
-    pub fn get_access_point_rsn_flags(&self, path: &str) -> Result<NM80211ApSecurityFlags> {
-        self.dbus
-            .property(path, NM_ACCESS_POINT_INTERFACE, "RsnFlags")
+    /// Calls `callback` with the signal strength of the access point at `path`, first with the
+    /// current value and then on every change, until it returns `false` or `timeout` elapses.
+    pub fn watch_access_point_strength<F>(
+        &self,
+        path: &str,
+        timeout: Duration,
+        mut callback: F,
+    ) -> Result<()>
+    where
+        F: FnMut(u8) -> bool,
+    {
+        let mut changes = self.dbus.properties_changed(path)?;
+
+        let deadline = Instant::now() + timeout;
+
+        let strength: u8 = self.dbus
+            .property(path, NM_ACCESS_POINT_INTERFACE, "Strength")?;
+
+        if !callback(strength) {
+            return Ok(());
+        }
+
+        loop {
+            let now = Instant::now();
+
+            if now >= deadline {
+                return Ok(());
+            }
+
+            if let Some(change) = changes.next_timeout(deadline - now) {
+                if let Some(strength) = decode_strength_changed(&change) {
+                    if !callback(strength) {
+                        return Ok(());
+                    }
+                }
+            }
+        }
     }
 }
 
@@ -502,6 +1372,12 @@ impl VariantTo<DeviceState> for DBusApi {
     }
 }
 
+impl VariantTo<Metered> for DBusApi {
+    fn variant_to(value: &Variant<Box<RefArg>>) -> Option<Metered> {
+        value.0.as_i64().map(Metered::from)
+    }
+}
+
 impl VariantTo<NM80211ApFlags> for DBusApi {
     fn variant_to(value: &Variant<Box<RefArg>>) -> Option<NM80211ApFlags> {
         value
@@ -520,39 +1396,2769 @@ impl VariantTo<NM80211ApSecurityFlags> for DBusApi {
     }
 }
 
-pub fn add_val<K, V>(map: &mut VariantMap, key: K, value: V)
-where
-    K: Into<String>,
-    V: RefArg + 'static,
-{
-    map.insert(key.into(), Variant(Box::new(value)));
+impl VariantTo<WirelessCapabilities> for DBusApi {
+    fn variant_to(value: &Variant<Box<RefArg>>) -> Option<WirelessCapabilities> {
+        // Newer NetworkManager versions may report capabilities unknown here
+        value
+            .0
+            .as_i64()
+            .map(|v| WirelessCapabilities::from_bits_truncate(v as u32))
+    }
 }
 
-pub fn add_str<K, V>(map: &mut VariantMap, key: K, value: V)
-where
-    K: Into<String>,
-    V: Into<String>,
-{
-    map.insert(key.into(), Variant(Box::new(value.into())));
+pub fn decode_state_changed(signal: &Message) -> Option<NetworkManagerState> {
+    signal.get1::<u32>().map(NetworkManagerState::from)
 }
 
-fn verify_ascii_password(password: &str) -> Result<&str> {
-    match AsciiStr::from_ascii(password) {
-        Err(e) => Err(e).chain_err(|| ErrorKind::PreSharedKey("Not an ASCII password".into())),
-        Ok(p) => {
-            if p.len() < 8 {
-                bail!(ErrorKind::PreSharedKey(format!(
-                    "Password length should be at least 8 characters: {} len",
-                    p.len()
-                )))
-            } else if p.len() > 64 {
-                bail!(ErrorKind::PreSharedKey(format!(
-                    "Password length should not exceed 64: {} len",
-                    p.len()
-                )))
-            } else {
+pub fn decode_device_state_changed(signal: &Message) -> Option<DeviceState> {
+    signal.get1::<u32>().map(DeviceState::from)
+}
+
+/// Decodes the new state and its reason from an active connection `StateChanged` signal.
+pub fn decode_active_state_changed(
+    signal: &Message,
+) -> Option<(ConnectionState, ActiveConnectionStateReason)> {
+    match signal.get2::<u32, u32>() {
+        (Some(state), Some(reason)) => Some((
+            ConnectionState::from(state),
+            ActiveConnectionStateReason::from(reason),
+        )),
+        _ => None,
+    }
+}
+
+/// Extracts the new connectivity from a change of Network Manager properties.
+pub fn decode_connectivity_changed(change: &PropertiesChange) -> Option<Connectivity> {
+    if change.interface != NM_SERVICE_INTERFACE {
+        return None;
+    }
+
+    change
+        .changed
+        .get("Connectivity")
+        .and_then(|value| value.0.as_i64())
+        .map(|connectivity| Connectivity::from(connectivity as u32))
+}
+
+/// Extracts the new signal strength from a change of access point properties.
+pub fn decode_strength_changed(change: &PropertiesChange) -> Option<u8> {
+    if change.interface != NM_ACCESS_POINT_INTERFACE {
+        return None;
+    }
+
+    change
+        .changed
+        .get("Strength")
+        .and_then(|value| value.0.as_i64())
+        .map(|strength| strength as u8)
+}
+
+/// Decodes the `LastScan` timestamp of a wireless device from a property change, if it changed.
+pub fn decode_last_scan_changed(change: &PropertiesChange) -> Option<i64> {
+    if change.interface != NM_WIRELESS_INTERFACE {
+        return None;
+    }
+
+    change
+        .changed
+        .get("LastScan")
+        .and_then(|value| value.0.as_i64())
+}
+
+/// Decodes the properties of an IP4Config object.
+///
+/// Prefers the `AddressData` and `NameserverData` properties and falls back to the deprecated
+/// `Addresses` and `Nameservers` ones on daemons which lack them.
+pub fn decode_ip4_config(properties: &VariantMap) -> IP4Config {
+    let mut addresses = properties
+        .get("AddressData")
+        .map(decode_ip4_address_data)
+        .unwrap_or_default();
+    if addresses.is_empty() {
+        let legacy: Vec<Vec<u32>> = properties
+            .get("Addresses")
+            .and_then(|value| value.0.as_iter())
+            .map(|list| {
+                list.filter_map(|entry| entry.as_iter())
+                    .map(|entry| entry.filter_map(|value| value.as_i64()).map(|v| v as u32))
+                    .map(|entry| entry.collect())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        for entry in legacy {
+            if entry.len() >= 2 {
+                addresses.push(IP4Address {
+                    address: format!("{}", Ipv4Addr::from(u32::from_be(entry[0]))),
+                    prefix: entry[1],
+                });
+            }
+        }
+    }
+
+    let mut nameservers: Vec<String> = properties
+        .get("NameserverData")
+        .map(decode_ip4_address_data)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|nameserver| nameserver.address)
+        .collect();
+    if nameservers.is_empty() {
+        let legacy: Vec<u32> = properties
+            .get("Nameservers")
+            .and_then(DBusApi::variant_to)
+            .unwrap_or_default();
+
+        nameservers = legacy
+            .into_iter()
+            .map(|nameserver| format!("{}", Ipv4Addr::from(u32::from_be(nameserver))))
+            .collect();
+    }
+
+    IP4Config {
+        addresses: addresses,
+        gateway: properties
+            .get("Gateway")
+            .and_then(DBusApi::variant_to)
+            .unwrap_or_default(),
+        nameservers: nameservers,
+        domains: properties
+            .get("Domains")
+            .and_then(DBusApi::variant_to)
+            .unwrap_or_default(),
+    }
+}
+
+/// Decodes an `aa{sv}` list of address dictionaries with `address` and optional `prefix` keys.
+fn decode_ip4_address_data(value: &Variant<Box<RefArg>>) -> Vec<IP4Address> {
+    let mut addresses = Vec::new();
+
+    let list = match value.0.as_iter() {
+        Some(list) => list,
+        None => return addresses,
+    };
+
+    for entry in list {
+        let mut address = None;
+        let mut prefix = 0;
+
+        if let Some(mut items) = entry.as_iter() {
+            while let (Some(key), Some(item)) = (items.next(), items.next()) {
+                match key.as_str() {
+                    Some("address") => address = item.as_str().map(|a| a.to_string()),
+                    Some("prefix") => prefix = item.as_i64().unwrap_or(0) as u32,
+                    _ => {},
+                }
+            }
+        }
+
+        if let Some(address) = address {
+            addresses.push(IP4Address {
+                address: address,
+                prefix: prefix,
+            });
+        }
+    }
+
+    addresses
+}
+
+impl VariantTo<Vec<DnsEntry>> for DBusApi {
+    fn variant_to(value: &Variant<Box<RefArg>>) -> Option<Vec<DnsEntry>> {
+        value
+            .0
+            .as_iter()
+            .map(|list| list.filter_map(decode_dns_entry).collect())
+    }
+}
+
+/// Decodes a single `a{sv}` entry of the DNS manager `Configuration` property.
+fn decode_dns_entry(entry: &RefArg) -> Option<DnsEntry> {
+    let mut items = entry.as_iter()?;
+
+    let mut dns = DnsEntry::default();
+
+    while let (Some(key), Some(item)) = (items.next(), items.next()) {
+        match key.as_str() {
+            Some("nameservers") => dns.nameservers = decode_variant_strings(item),
+            Some("domains") => dns.domains = decode_variant_strings(item),
+            Some("interface") => dns.interface = item.as_str().unwrap_or_default().to_string(),
+            Some("priority") => dns.priority = item.as_i64().unwrap_or(0) as i32,
+            Some("vpn") => dns.vpn = item.as_i64() == Some(1),
+            _ => {},
+        }
+    }
+
+    Some(dns)
+}
+
+/// Decodes a variant holding an array of strings, skipping anything else.
+fn decode_variant_strings(item: &RefArg) -> Vec<String> {
+    let mut array = item;
+
+    // Iterating a variant yields the value it holds, which may be a variant again
+    while array.arg_type() == ArgType::Variant {
+        array = match array.as_iter().and_then(|mut inner| inner.next()) {
+            Some(inner) => inner,
+            None => return Vec::new(),
+        };
+    }
+
+    array
+        .as_iter()
+        .map(|values| {
+            values
+                .filter_map(|value| value.as_str())
+                .map(|value| value.to_string())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Decodes the properties of the active connection at `path`.
+pub fn decode_active_connection(
+    path: &str,
+    properties: &HashMap<String, Variant<Box<RefArg>>>,
+) -> Result<ActiveConnection> {
+    let state: u32 = active_connection_property(path, properties, "State")?;
+
+    Ok(ActiveConnection {
+        path: path.to_string(),
+        id: active_connection_property(path, properties, "Id")?,
+        uuid: active_connection_property(path, properties, "Uuid")?,
+        kind: active_connection_property(path, properties, "Type")?,
+        state: ConnectionState::from(state),
+        devices: active_connection_property(path, properties, "Devices")?,
+    })
+}
+
+fn active_connection_property<T>(
+    path: &str,
+    properties: &HashMap<String, Variant<Box<RefArg>>>,
+    name: &str,
+) -> Result<T>
+where
+    DBusApi: VariantTo<T>,
+{
+    match properties.get(name).and_then(DBusApi::variant_to) {
+        Some(value) => Ok(value),
+        None => bail!(ErrorKind::NetworkManager(format!(
+            "Missing or invalid active connection property {} on {}",
+            name, path
+        ))),
+    }
+}
+
+/// Builds the settings for connecting to the access point `ssid` with `credentials`.
+pub fn access_point_settings(
+    ssid: &SsidSlice,
+    credentials: &AccessPointCredentials,
+) -> Result<SettingsMap> {
+    let mut settings: SettingsMap = HashMap::new();
+
+    let mut wireless: VariantMap = HashMap::new();
+    add_val(&mut wireless, "ssid", ssid.as_bytes().to_vec());
+    settings.insert("802-11-wireless".to_string(), wireless);
+
+    match *credentials {
+        AccessPointCredentials::Wep { ref passphrase } => {
+            let mut security_settings: VariantMap = HashMap::new();
+
+            add_val(
+                &mut security_settings,
+                "wep-key-type",
+                NM_WEP_KEY_TYPE_PASSPHRASE,
+            );
+            add_str(
+                &mut security_settings,
+                "wep-key0",
+                verify_ascii_password(passphrase)?,
+            );
+
+            settings.insert("802-11-wireless-security".to_string(), security_settings);
+        },
+        AccessPointCredentials::Wpa { ref passphrase } => {
+            let mut security_settings: VariantMap = HashMap::new();
+
+            add_str(&mut security_settings, "key-mgmt", "wpa-psk");
+            add_str(
+                &mut security_settings,
+                "psk",
+                verify_ascii_password(passphrase)?,
+            );
+
+            settings.insert("802-11-wireless-security".to_string(), security_settings);
+        },
+        AccessPointCredentials::Enterprise {
+            ref identity,
+            ref passphrase,
+        } => {
+            let eap = Enterprise8021x::new(EapMethod::Peap)
+                .identity(identity)
+                .password(passphrase)
+                .phase2_auth("mschapv2");
+
+            add_enterprise_settings(&mut settings, &eap)?;
+        },
+        AccessPointCredentials::Eap(ref eap) => add_enterprise_settings(&mut settings, eap)?,
+        AccessPointCredentials::None => {},
+    };
+
+    Ok(settings)
+}
+
+/// Builds the settings for connecting to the hidden network `ssid`, secured with WPA if a
+/// `password` is given.
+pub fn hidden_ssid_settings(ssid: &SsidSlice, password: Option<&str>) -> Result<SettingsMap> {
+    let credentials = match password {
+        Some(password) => AccessPointCredentials::Wpa {
+            passphrase: password.to_string(),
+        },
+        None => AccessPointCredentials::None,
+    };
+
+    let mut settings = access_point_settings(ssid, &credentials)?;
+
+    if let Some(wireless) = settings.get_mut("802-11-wireless") {
+        add_val(wireless, "hidden", true);
+    }
+
+    Ok(settings)
+}
+
+/// Builds the settings for sharing a connection through an access point on `interface`.
+///
+/// The band defaults to `bg`; a `channel` is only accepted together with a band. A `password`
+/// secures the access point with WPA2 (RSN with CCMP).
+pub fn hotspot_settings(
+    interface: &str,
+    ssid: &SsidSlice,
+    password: Option<&str>,
+    address: Option<Ipv4Addr>,
+    band: Option<&str>,
+    channel: Option<u32>,
+) -> Result<SettingsMap> {
+    match band {
+        Some("a") | Some("bg") | None => {},
+        Some(band) => bail!(ErrorKind::NetworkManager(format!(
+            "Invalid hotspot band '{}': expected 'a' or 'bg'",
+            band
+        ))),
+    }
+
+    if channel.is_some() && band.is_none() {
+        bail!(ErrorKind::NetworkManager(
+            "A hotspot channel requires a band".into()
+        ));
+    }
+
+    let mut wireless: VariantMap = HashMap::new();
+    add_val(&mut wireless, "ssid", ssid.as_bytes().to_vec());
+    add_str(&mut wireless, "band", band.unwrap_or("bg"));
+    if let Some(channel) = channel {
+        add_val(&mut wireless, "channel", channel);
+    }
+    add_val(&mut wireless, "hidden", false);
+    add_str(&mut wireless, "mode", "ap");
+
+    let mut connection: VariantMap = HashMap::new();
+    add_val(&mut connection, "autoconnect", false);
+    if let Ok(ssid_str) = ssid.as_str() {
+        add_str(&mut connection, "id", ssid_str);
+    }
+    add_str(&mut connection, "interface-name", interface);
+    add_str(&mut connection, "type", "802-11-wireless");
+
+    let mut ipv4: VariantMap = HashMap::new();
+    if let Some(address) = address {
+        add_str(&mut ipv4, "method", "manual");
+
+        let mut addr_map: VariantMap = HashMap::new();
+        add_str(&mut addr_map, "address", format!("{}", address));
+        add_val(&mut addr_map, "prefix", 24_u32);
+
+        add_val(&mut ipv4, "address-data", vec![addr_map]);
+    } else {
+        add_str(&mut ipv4, "method", "shared");
+    }
+
+    let mut settings: SettingsMap = HashMap::new();
+
+    if let Some(password) = password {
+        add_str(&mut wireless, "security", "802-11-wireless-security");
+
+        let mut security: VariantMap = HashMap::new();
+        add_str(&mut security, "key-mgmt", "wpa-psk");
+        add_val(&mut security, "proto", vec!["rsn".to_string()]);
+        add_val(&mut security, "pairwise", vec!["ccmp".to_string()]);
+        add_val(&mut security, "group", vec!["ccmp".to_string()]);
+        add_str(&mut security, "psk", verify_ascii_password(password)?);
+
+        settings.insert("802-11-wireless-security".to_string(), security);
+    }
+
+    settings.insert("802-11-wireless".to_string(), wireless);
+    settings.insert("connection".to_string(), connection);
+    settings.insert("ipv4".to_string(), ipv4);
+
+    Ok(settings)
+}
+
+fn add_enterprise_settings(settings: &mut SettingsMap, eap: &Enterprise8021x) -> Result<()> {
+    let mut security_settings: VariantMap = HashMap::new();
+    add_str(&mut security_settings, "key-mgmt", "wpa-eap");
+
+    settings.insert("802-11-wireless-security".to_string(), security_settings);
+    settings.insert("802-1x".to_string(), eap.to_settings()?);
+
+    Ok(())
+}
+
+/// Removes repeated paths, keeping the first occurrence of each.
+fn dedup_paths(paths: Vec<String>) -> Vec<String> {
+    let mut result: Vec<String> = Vec::with_capacity(paths.len());
+
+    for path in paths {
+        if !result.contains(&path) {
+            result.push(path);
+        }
+    }
+
+    result
+}
+
+/// Builds `RequestScan` options that probe for the given, possibly hidden, SSIDs.
+pub fn scan_options(ssids: &[&SsidSlice]) -> VariantMap {
+    let mut options = HashMap::new();
+
+    if !ssids.is_empty() {
+        let ssids: Vec<Vec<u8>> = ssids.iter().map(|ssid| ssid.as_bytes().to_vec()).collect();
+        add_val(&mut options, "ssids", ssids);
+    }
+
+    options
+}
+
+/// The setting name, key and value of every value in the entries of an `a{sa{sv}}` settings
+/// message item.
+fn settings_items(settings: Vec<MessageItem>) -> Vec<(String, String, MessageItem)> {
+    let mut items = Vec::new();
+
+    for setting in settings {
+        if let MessageItem::DictEntry(name, values) = setting {
+            if let (MessageItem::Str(name), MessageItem::Array(values, _)) = (*name, *values) {
+                for value in values {
+                    if let MessageItem::DictEntry(key, value) = value {
+                        if let (MessageItem::Str(key), MessageItem::Variant(value)) =
+                            (*key, *value)
+                        {
+                            items.push((name.clone(), key, *value));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    items
+}
+
+/// A settings value as received from NetworkManager.
+///
+/// dbus decodes nested arrays as structs and drops the element type of empty arrays, so
+/// sending the decoded value back would change its signature. The value is read through the
+/// decoded value, e.g. with `DBusApi::variant_to` or `cast`, but appended as the received
+/// message item. It cannot be modified in place; replace it in the map instead.
+struct ReceivedValue {
+    value: Box<RefArg>,
+    item: MessageItem,
+}
+
+impl fmt::Debug for ReceivedValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.value.fmt(f)
+    }
+}
+
+impl RefArg for ReceivedValue {
+    fn arg_type(&self) -> ArgType {
+        self.item.arg_type()
+    }
+
+    fn signature(&self) -> Signature<'static> {
+        self.item.signature()
+    }
+
+    fn append(&self, i: &mut IterAppend) {
+        self.item.append(i)
+    }
+
+    fn as_any(&self) -> &Any {
+        (*self.value).as_any()
+    }
+
+    fn as_any_mut(&mut self) -> &mut Any {
+        self
+    }
+
+    fn as_i64(&self) -> Option<i64> {
+        self.value.as_i64()
+    }
+
+    fn as_str(&self) -> Option<&str> {
+        self.value.as_str()
+    }
+
+    fn as_iter<'a>(&'a self) -> Option<Box<Iterator<Item = &'a RefArg> + 'a>> {
+        self.value.as_iter()
+    }
+}
+
+pub fn add_val<K, V>(map: &mut VariantMap, key: K, value: V)
+where
+    K: Into<String>,
+    V: RefArg + 'static,
+{
+    map.insert(key.into(), Variant(Box::new(value)));
+}
+
+pub fn add_str<K, V>(map: &mut VariantMap, key: K, value: V)
+where
+    K: Into<String>,
+    V: Into<String>,
+{
+    map.insert(key.into(), Variant(Box::new(value.into())));
+}
+
+pub fn verify_ascii_password(password: &str) -> Result<&str> {
+    match AsciiStr::from_ascii(password) {
+        Err(e) => Err(e).chain_err(|| ErrorKind::PreSharedKey("Not an ASCII password".into())),
+        Ok(p) => {
+            if p.len() < 8 {
+                bail!(ErrorKind::PreSharedKey(format!(
+                    "Password length should be at least 8 characters: {} len",
+                    p.len()
+                )))
+            } else if p.len() > 64 {
+                bail!(ErrorKind::PreSharedKey(format!(
+                    "Password length should not exceed 64: {} len",
+                    p.len()
+                )))
+            } else {
                 Ok(password)
             }
         },
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::rc::Rc;
+    use std::sync::mpsc;
+
+    use dbus::arg::{Append, Arg};
+    use dbus::tree::{Access, MethodErr};
+
+    use mock::MockService;
+
+    use super::*;
+
+    #[test]
+    fn test_get_devices() {
+        const SERVICE: &str = "org.freedesktop.NetworkManager.Test.GetDevices";
+
+        let _service = MockService::spawn(SERVICE, |f, _| {
+            f.tree(()).add(f.object_path(NM_SERVICE_PATH, ()).add(
+                f.interface(NM_SERVICE_INTERFACE, ()).add_m(f.method(
+                    "GetDevices",
+                    (),
+                    |m| {
+                        let devices = vec![
+                            Path::from("/org/freedesktop/NetworkManager/Devices/1"),
+                            Path::from("/org/freedesktop/NetworkManager/Devices/2"),
+                        ];
+                        Ok(vec![m.msg.method_return().append1(devices)])
+                    },
+                )),
+            ))
+        });
+
+        let dbus_manager = DBusNetworkManager::with_mock_service(SERVICE);
+
+        assert_eq!(
+            dbus_manager.get_devices().unwrap(),
+            vec![
+                "/org/freedesktop/NetworkManager/Devices/1".to_string(),
+                "/org/freedesktop/NetworkManager/Devices/2".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_get_device_by_ip_iface() {
+        const SERVICE: &str = "org.freedesktop.NetworkManager.Test.GetDeviceByIpIface";
+
+        let _service = MockService::spawn(SERVICE, |f, _| {
+            f.tree(()).add(f.object_path(NM_SERVICE_PATH, ()).add(
+                f.interface(NM_SERVICE_INTERFACE, ()).add_m(f.method(
+                    "GetDeviceByIpIface",
+                    (),
+                    |m| match m.msg.read1::<&str>()? {
+                        "wlan0" => {
+                            let path = Path::from("/org/freedesktop/NetworkManager/Devices/3");
+                            Ok(vec![m.msg.method_return().append1(path)])
+                        },
+                        _ => Err(MethodErr::from((
+                            "org.freedesktop.NetworkManager.UnknownDevice",
+                            "No device found for the requested iface.",
+                        ))),
+                    },
+                )),
+            ))
+        });
+
+        let dbus_manager = DBusNetworkManager::with_mock_service(SERVICE);
+
+        assert_eq!(
+            dbus_manager.get_device_by_ip_iface("wlan0").unwrap(),
+            "/org/freedesktop/NetworkManager/Devices/3"
+        );
+
+        match *dbus_manager.get_device_by_ip_iface("wlan9").unwrap_err().kind() {
+            ErrorKind::DBusError(DBusError::NotFound(_)) => {},
+            ref other => panic!("expected not found, got {:?}", other),
+        }
+    }
+
+    const DEVICE_PATH: &str = "/org/freedesktop/NetworkManager/Devices/1";
+    const ACTIVE_PATH: &str = "/org/freedesktop/NetworkManager/ActiveConnection/1";
+    const PROFILE_PATH: &str = "/org/freedesktop/NetworkManager/Settings/1";
+
+    /// A `PropertiesChanged` signal reporting the new `value` of `name`.
+    fn property_changed<T>(path: &str, interface: &str, name: &str, value: T) -> Message
+    where
+        T: Append + Arg,
+    {
+        let mut changed = HashMap::new();
+        changed.insert(name, Variant(value));
+
+        Message::new_signal(path, "org.freedesktop.DBus.Properties", "PropertiesChanged")
+            .unwrap()
+            .append3(interface, changed, Vec::<String>::new())
+    }
+
+    /// Serves a managed device with autoconnect in the `Prepare` state which, if `activate` is
+    /// set, reports being activated after its state has been read once. `Disconnect` moves it to
+    /// the `Disconnected` state.
+    fn spawn_device(service: &'static str, activate: bool) -> MockService {
+        MockService::spawn(service, move |f, signals| {
+            let state_signals = signals.clone();
+            let disconnect_signals = signals.clone();
+            let state = Rc::new(Cell::new(40_u32));
+            let get_state = state.clone();
+            let reads = Cell::new(0);
+
+            let flag = |name: &'static str| {
+                let value = Rc::new(Cell::new(true));
+                let set_value = value.clone();
+
+                f.property::<bool, _>(name, ())
+                    .access(Access::ReadWrite)
+                    .on_get(move |i, _| {
+                        i.append(value.get());
+                        Ok(())
+                    })
+                    .on_set(move |i, _| {
+                        set_value.set(i.read()?);
+                        Ok(())
+                    })
+            };
+
+            f.tree(()).add(f.object_path(DEVICE_PATH, ()).add(
+                f.interface(NM_DEVICE_INTERFACE, ())
+                    .add_p(f.property::<u32, _>("State", ()).on_get(move |i, _| {
+                        reads.set(reads.get() + 1);
+                        if activate && reads.get() == 1 {
+                            let signal = Message::new_signal(
+                                DEVICE_PATH,
+                                NM_DEVICE_INTERFACE,
+                                "StateChanged",
+                            ).unwrap()
+                                .append3(100_u32, 40_u32, 0_u32);
+                            state_signals.emit(signal);
+                        }
+                        i.append(get_state.get());
+                        Ok(())
+                    }))
+                    .add_p(flag("Managed"))
+                    .add_p(flag("Autoconnect"))
+                    .add_m(f.method("Disconnect", (), move |m| {
+                        state.set(30);
+                        disconnect_signals.emit(property_changed(
+                            DEVICE_PATH,
+                            NM_DEVICE_INTERFACE,
+                            "State",
+                            30_u32,
+                        ));
+                        Ok(vec![m.msg.method_return()])
+                    }))
+                    .add_s(f.signal("StateChanged", ())),
+            ))
+        })
+    }
+
+    #[test]
+    fn test_wait_for_device_state() {
+        const SERVICE: &str = "org.freedesktop.NetworkManager.Test.WaitForDeviceState";
+
+        let _service = spawn_device(SERVICE, true);
+        let dbus_manager = DBusNetworkManager::with_mock_service(SERVICE);
+
+        let state = dbus_manager
+            .wait_for_device_state(DEVICE_PATH, &DeviceState::Activated, Duration::from_secs(5))
+            .unwrap();
+        assert_eq!(state, DeviceState::Activated);
+    }
+
+    #[test]
+    fn test_cached_device_state() {
+        const SERVICE: &str = "org.freedesktop.NetworkManager.Test.CachedDeviceState";
+
+        let service = spawn_device(SERVICE, false);
+        let dbus_manager = DBusNetworkManager::with_mock_service(SERVICE);
+        dbus_manager
+            .enable_property_cache(Duration::from_secs(60))
+            .unwrap();
+
+        assert_eq!(dbus_manager.get_device_state(DEVICE_PATH).unwrap(), DeviceState::Prepare);
+        let calls = service.method_calls();
+        assert_eq!(dbus_manager.get_device_state(DEVICE_PATH).unwrap(), DeviceState::Prepare);
+        assert_eq!(service.method_calls(), calls);
+
+        dbus_manager.disconnect_device(DEVICE_PATH).unwrap();
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while dbus_manager.get_device_state(DEVICE_PATH).unwrap() != DeviceState::Disconnected {
+            assert!(Instant::now() < deadline, "stale device state served from the cache");
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn test_wait_for_device_state_timeout() {
+        const SERVICE: &str = "org.freedesktop.NetworkManager.Test.WaitForDeviceStateTimeout";
+
+        let _service = spawn_device(SERVICE, false);
+        let dbus_manager = DBusNetworkManager::with_mock_service(SERVICE);
+
+        let result = dbus_manager.wait_for_device_state(
+            DEVICE_PATH,
+            &DeviceState::Activated,
+            Duration::from_millis(200),
+        );
+
+        match *result.unwrap_err().kind() {
+            ErrorKind::Timeout(_) => {},
+            ref other => panic!("expected a timeout, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_request_scan() {
+        const SERVICE: &str = "org.freedesktop.NetworkManager.Test.RequestScan";
+
+        let (options_tx, options_rx) = mpsc::channel();
+
+        let _service = MockService::spawn(SERVICE, move |f, _| {
+            f.tree(()).add(f.object_path(DEVICE_PATH, ()).add(
+                f.interface(NM_WIRELESS_INTERFACE, ()).add_m(f.method(
+                    "RequestScan",
+                    (),
+                    move |m| {
+                        let options: VariantMap = m.msg.read1()?;
+                        let ssids: Option<Vec<Vec<u8>>> =
+                            options.get("ssids").and_then(DBusApi::variant_to);
+                        options_tx.send(ssids).unwrap();
+                        Ok(vec![m.msg.method_return()])
+                    },
+                )),
+            ))
+        });
+
+        let dbus_manager = DBusNetworkManager::with_mock_service(SERVICE);
+
+        dbus_manager.request_access_point_scan(DEVICE_PATH).unwrap();
+        assert_eq!(options_rx.recv().unwrap(), None);
+
+        let hidden = "hidden".as_ssid_slice().unwrap();
+        let office = "office".as_ssid_slice().unwrap();
+        dbus_manager
+            .request_scan(DEVICE_PATH, scan_options(&[hidden, office]))
+            .unwrap();
+        assert_eq!(
+            options_rx.recv().unwrap(),
+            Some(vec![b"hidden".to_vec(), b"office".to_vec()])
+        );
+    }
+
+    #[test]
+    fn test_scan_options() {
+        assert!(scan_options(&[]).is_empty());
+
+        let options = scan_options(&["hidden".as_ssid_slice().unwrap()]);
+        let ssids: Option<Vec<Vec<u8>>> = DBusApi::variant_to(&options["ssids"]);
+        assert_eq!(ssids, Some(vec![b"hidden".to_vec()]));
+    }
+
+    const ACCESS_POINTS: &[&str] = &[
+        "/org/freedesktop/NetworkManager/AccessPoint/3",
+        "/org/freedesktop/NetworkManager/AccessPoint/1",
+        "/org/freedesktop/NetworkManager/AccessPoint/2",
+    ];
+
+    fn access_point_paths() -> Vec<Path<'static>> {
+        ACCESS_POINTS.iter().map(|path| Path::from(*path)).collect()
+    }
+
+    #[test]
+    fn test_get_access_points() {
+        const SERVICE: &str = "org.freedesktop.NetworkManager.Test.GetAccessPoints";
+
+        let _service = MockService::spawn(SERVICE, |f, _| {
+            f.tree(()).add(f.object_path(DEVICE_PATH, ()).add(
+                f.interface(NM_WIRELESS_INTERFACE, ()).add_m(f.method(
+                    "GetAllAccessPoints",
+                    (),
+                    |m| Ok(vec![m.msg.method_return().append1(access_point_paths())]),
+                )),
+            ))
+        });
+
+        let dbus_manager = DBusNetworkManager::with_mock_service(SERVICE);
+
+        assert_eq!(dbus_manager.get_access_points(DEVICE_PATH).unwrap(), ACCESS_POINTS);
+    }
+
+    #[test]
+    fn test_get_access_points_property_fallback() {
+        const SERVICE: &str = "org.freedesktop.NetworkManager.Test.GetAccessPointsFallback";
+
+        let _service = MockService::spawn(SERVICE, |f, _| {
+            f.tree(()).add(f.object_path(DEVICE_PATH, ()).add(
+                f.interface(NM_WIRELESS_INTERFACE, ()).add_p(
+                    f.property::<Vec<Path>, _>("AccessPoints", ())
+                        .on_get(|i, _| {
+                            let mut paths = access_point_paths();
+                            paths.push(Path::from(ACCESS_POINTS[0]));
+                            i.append(paths);
+                            Ok(())
+                        }),
+                ),
+            ))
+        });
+
+        let dbus_manager = DBusNetworkManager::with_mock_service(SERVICE);
+
+        assert_eq!(dbus_manager.get_access_points(DEVICE_PATH).unwrap(), ACCESS_POINTS);
+    }
+
+    #[test]
+    fn test_dedup_paths() {
+        let paths = vec!["/b".to_string(), "/a".to_string(), "/b".to_string()];
+
+        assert_eq!(dedup_paths(paths), vec!["/b".to_string(), "/a".to_string()]);
+    }
+
+    #[test]
+    fn test_access_point_settings_wpa() {
+        let ssid = "office".as_ssid_slice().unwrap();
+        let credentials = AccessPointCredentials::Wpa {
+            passphrase: "hunter22".to_string(),
+        };
+
+        let settings = access_point_settings(ssid, &credentials).unwrap();
+
+        let wireless_ssid: Option<Vec<u8>> =
+            DBusApi::variant_to(&settings["802-11-wireless"]["ssid"]);
+        assert_eq!(wireless_ssid, Some(b"office".to_vec()));
+
+        let security = &settings["802-11-wireless-security"];
+        let key_mgmt: Option<String> = DBusApi::variant_to(&security["key-mgmt"]);
+        assert_eq!(key_mgmt, Some("wpa-psk".to_string()));
+        let psk: Option<String> = DBusApi::variant_to(&security["psk"]);
+        assert_eq!(psk, Some("hunter22".to_string()));
+    }
+
+    #[test]
+    fn test_access_point_settings_open() {
+        let ssid = "cafe".as_ssid_slice().unwrap();
+
+        let settings = access_point_settings(ssid, &AccessPointCredentials::None).unwrap();
+
+        assert!(settings.contains_key("802-11-wireless"));
+        assert!(!settings.contains_key("802-11-wireless-security"));
+    }
+
+    #[test]
+    fn test_access_point_settings_short_password() {
+        let ssid = "office".as_ssid_slice().unwrap();
+        let credentials = AccessPointCredentials::Wpa {
+            passphrase: "short".to_string(),
+        };
+
+        assert!(access_point_settings(ssid, &credentials).is_err());
+    }
+
+    #[test]
+    fn test_hotspot_settings() {
+        let ssid = "hotspot".as_ssid_slice().unwrap();
+
+        let settings =
+            hotspot_settings("wlan0", ssid, Some("hunter22"), None, Some("a"), Some(36)).unwrap();
+
+        let wireless = &settings["802-11-wireless"];
+        let mode: Option<String> = DBusApi::variant_to(&wireless["mode"]);
+        assert_eq!(mode, Some("ap".to_string()));
+        let band: Option<String> = DBusApi::variant_to(&wireless["band"]);
+        assert_eq!(band, Some("a".to_string()));
+        let channel: Option<u32> = DBusApi::variant_to(&wireless["channel"]);
+        assert_eq!(channel, Some(36));
+
+        let method: Option<String> = DBusApi::variant_to(&settings["ipv4"]["method"]);
+        assert_eq!(method, Some("shared".to_string()));
+
+        let security = &settings["802-11-wireless-security"];
+        let key_mgmt: Option<String> = DBusApi::variant_to(&security["key-mgmt"]);
+        assert_eq!(key_mgmt, Some("wpa-psk".to_string()));
+        assert!(security.contains_key("proto"));
+        assert!(security.contains_key("pairwise"));
+    }
+
+    #[test]
+    fn test_hotspot_settings_open() {
+        let ssid = "hotspot".as_ssid_slice().unwrap();
+
+        let settings = hotspot_settings("wlan0", ssid, None, None, None, None).unwrap();
+
+        let band: Option<String> = DBusApi::variant_to(&settings["802-11-wireless"]["band"]);
+        assert_eq!(band, Some("bg".to_string()));
+        assert!(!settings["802-11-wireless"].contains_key("channel"));
+        assert!(!settings.contains_key("802-11-wireless-security"));
+    }
+
+    #[test]
+    fn test_hotspot_settings_invalid_band() {
+        let ssid = "hotspot".as_ssid_slice().unwrap();
+
+        assert!(hotspot_settings("wlan0", ssid, None, None, Some("ac"), None).is_err());
+        assert!(hotspot_settings("wlan0", ssid, None, None, None, Some(6)).is_err());
+    }
+
+    #[test]
+    fn test_get_active_connection() {
+        const SERVICE: &str = "org.freedesktop.NetworkManager.Test.GetActiveConnection";
+
+        let _service = MockService::spawn(SERVICE, |f, _| {
+            f.tree(())
+                .add(f.object_path(NM_SERVICE_PATH, ()).add(
+                    f.interface(NM_SERVICE_INTERFACE, ()).add_p(
+                        f.property::<Vec<Path>, _>("ActiveConnections", ())
+                            .on_get(|i, _| {
+                                i.append(vec![Path::from(ACTIVE_PATH)]);
+                                Ok(())
+                            }),
+                    ),
+                ))
+                .add(f.object_path(ACTIVE_PATH, ()).add(
+                    f.interface(NM_ACTIVE_INTERFACE, ())
+                        .add_p(f.property::<&str, _>("Id", ()).on_get(|i, _| {
+                            i.append("office");
+                            Ok(())
+                        }))
+                        .add_p(f.property::<&str, _>("Uuid", ()).on_get(|i, _| {
+                            i.append("6a1c5b2e-0a4d-4c8e-9f47-3f2b8d1e5c90");
+                            Ok(())
+                        }))
+                        .add_p(f.property::<&str, _>("Type", ()).on_get(|i, _| {
+                            i.append("802-11-wireless");
+                            Ok(())
+                        }))
+                        .add_p(f.property::<u32, _>("State", ()).on_get(|i, _| {
+                            i.append(2_u32);
+                            Ok(())
+                        }))
+                        .add_p(f.property::<Vec<Path>, _>("Devices", ()).on_get(|i, _| {
+                            i.append(vec![Path::from(DEVICE_PATH)]);
+                            Ok(())
+                        })),
+                ))
+        });
+
+        let dbus_manager = DBusNetworkManager::with_mock_service(SERVICE);
+
+        let active_paths = dbus_manager.get_active_connections().unwrap();
+        assert_eq!(active_paths, vec![ACTIVE_PATH.to_string()]);
+
+        let active = dbus_manager.get_active_connection(&active_paths[0]).unwrap();
+        assert_eq!(active.id, "office");
+        assert_eq!(active.uuid, "6a1c5b2e-0a4d-4c8e-9f47-3f2b8d1e5c90");
+        assert_eq!(active.kind, "802-11-wireless");
+        assert_eq!(active.state, ConnectionState::Activated);
+        assert_eq!(active.devices, vec![DEVICE_PATH.to_string()]);
+    }
+
+    const CONNECTION_UUID: &str = "0a0e6f42-8c4b-4f3e-b3a1-7d5c2e9f1b64";
+
+    fn spawn_settings(service: &'static str) -> MockService {
+        MockService::spawn(service, |f, _| {
+            f.tree(()).add(
+                f.object_path(NM_SETTINGS_PATH, ()).add(
+                    f.interface(NM_SETTINGS_INTERFACE, ())
+                        .add_m(f.method("ListConnections", (), |m| {
+                            let connections = vec![
+                                Path::from("/org/freedesktop/NetworkManager/Settings/1"),
+                                Path::from("/org/freedesktop/NetworkManager/Settings/2"),
+                            ];
+                            Ok(vec![m.msg.method_return().append1(connections)])
+                        }))
+                        .add_m(f.method("GetConnectionByUuid", (), |m| {
+                            if m.msg.read1::<&str>()? == CONNECTION_UUID {
+                                let path = Path::from("/org/freedesktop/NetworkManager/Settings/2");
+                                Ok(vec![m.msg.method_return().append1(path)])
+                            } else {
+                                Err(MethodErr::from((
+                                    "org.freedesktop.NetworkManager.Settings.InvalidConnection",
+                                    "No connection with the UUID was found.",
+                                )))
+                            }
+                        })),
+                ),
+            )
+        })
+    }
+
+    #[test]
+    fn test_list_connections() {
+        const SERVICE: &str = "org.freedesktop.NetworkManager.Test.ListConnections";
+
+        let _service = spawn_settings(SERVICE);
+        let dbus_manager = DBusNetworkManager::with_mock_service(SERVICE);
+
+        assert_eq!(
+            dbus_manager.list_connections().unwrap(),
+            vec![
+                "/org/freedesktop/NetworkManager/Settings/1".to_string(),
+                "/org/freedesktop/NetworkManager/Settings/2".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_get_connection_by_uuid() {
+        const SERVICE: &str = "org.freedesktop.NetworkManager.Test.GetConnectionByUuid";
+
+        let _service = spawn_settings(SERVICE);
+        let dbus_manager = DBusNetworkManager::with_mock_service(SERVICE);
+
+        assert_eq!(
+            dbus_manager.get_connection_by_uuid(CONNECTION_UUID).unwrap(),
+            "/org/freedesktop/NetworkManager/Settings/2"
+        );
+
+        let unknown_uuid = "ffffffff-ffff-ffff-ffff-ffffffffffff";
+        match *dbus_manager.get_connection_by_uuid(unknown_uuid).unwrap_err().kind() {
+            ErrorKind::DBusError(DBusError::NotFound(_)) => {},
+            ref other => panic!("expected not found, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_dry_run() {
+        const SERVICE: &str = "org.freedesktop.NetworkManager.Test.DryRunSettings";
+
+        let service = spawn_settings(SERVICE);
+        let dbus_manager = DBusNetworkManager::with_mock_service(SERVICE);
+        dbus_manager.set_dry_run(true);
+
+        let calls = service.method_calls();
+        assert_eq!(dbus_manager.list_connections().unwrap().len(), 2);
+        assert_eq!(
+            dbus_manager.get_connection_by_uuid(CONNECTION_UUID).unwrap(),
+            "/org/freedesktop/NetworkManager/Settings/2"
+        );
+        assert_eq!(service.method_calls(), calls + 2);
+
+        let mut connection: VariantMap = HashMap::new();
+        add_str(&mut connection, "id", "staged");
+        let mut settings: SettingsMap = HashMap::new();
+        settings.insert("connection".to_string(), connection);
+
+        match *dbus_manager.add_connection(&settings).unwrap_err().kind() {
+            ErrorKind::DBusError(DBusError::DryRun(_)) => {},
+            ref other => panic!("expected dry run, got {:?}", other),
+        }
+        assert_eq!(service.method_calls(), calls + 2);
+    }
+
+    #[test]
+    fn test_get_connection_settings_map() {
+        const SERVICE: &str = "org.freedesktop.NetworkManager.Test.GetConnectionSettingsMap";
+
+        let (updates_tx, _updates_rx) = mpsc::channel();
+        let _service = spawn_profiles(SERVICE, &[(PROFILE_PATH, "802-11-wireless")], updates_tx);
+        let dbus_manager = DBusNetworkManager::with_mock_service(SERVICE);
+
+        let settings = dbus_manager
+            .get_connection_settings_map(PROFILE_PATH)
+            .unwrap();
+
+        let id: Option<String> = DBusApi::variant_to(&settings["connection"]["id"]);
+        assert_eq!(id, Some("uplink".to_string()));
+        let ssid: Option<Vec<u8>> = DBusApi::variant_to(&settings["802-11-wireless"]["ssid"]);
+        assert_eq!(ssid, Some(b"uplink".to_vec()));
+    }
+
+    #[test]
+    fn test_get_connection_secrets() {
+        const SERVICE: &str = "org.freedesktop.NetworkManager.Test.GetConnectionSecrets";
+        const LOCKED_PATH: &str = "/org/freedesktop/NetworkManager/Settings/2";
+
+        let _service = MockService::spawn(SERVICE, |f, _| {
+            let secrets = || {
+                f.interface(NM_CONNECTION_INTERFACE, ()).add_m(f.method(
+                    "GetSecrets",
+                    (),
+                    |m| {
+                        if &**m.path.get_name() == LOCKED_PATH {
+                            return Err(MethodErr::from((
+                                "org.freedesktop.NetworkManager.Settings.Connection.NotAuthorized",
+                                "Insufficient privileges.",
+                            )));
+                        }
+
+                        let mut security: VariantMap = HashMap::new();
+                        if m.msg.read1::<&str>()? == "802-11-wireless-security" {
+                            add_str(&mut security, "psk", "hunter22");
+                        }
+
+                        let mut secrets: SettingsMap = HashMap::new();
+                        secrets.insert("802-11-wireless-security".to_string(), security);
+
+                        Ok(vec![m.msg.method_return().append1(secrets)])
+                    },
+                ))
+            };
+
+            f.tree(())
+                .add(f.object_path(PROFILE_PATH, ()).add(secrets()))
+                .add(f.object_path(LOCKED_PATH, ()).add(secrets()))
+        });
+
+        let dbus_manager = DBusNetworkManager::with_mock_service(SERVICE);
+
+        let secrets = dbus_manager
+            .get_connection_secrets(PROFILE_PATH, "802-11-wireless-security")
+            .unwrap();
+        let psk: Option<String> = DBusApi::variant_to(&secrets["802-11-wireless-security"]["psk"]);
+        assert_eq!(psk, Some("hunter22".to_string()));
+
+        let error = dbus_manager
+            .get_connection_secrets(LOCKED_PATH, "802-11-wireless-security")
+            .unwrap_err();
+        match *error.kind() {
+            ErrorKind::DBusError(DBusError::NotAuthorized(_)) => {},
+            ref other => panic!("expected not authorized, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_activate_deactivate_connection() {
+        const SERVICE: &str = "org.freedesktop.NetworkManager.Test.ActivateConnection";
+
+        let (calls_tx, calls_rx) = mpsc::channel();
+
+        let _service = MockService::spawn(SERVICE, move |f, _| {
+            let deactivate_tx = calls_tx.clone();
+
+            f.tree(()).add(
+                f.object_path(NM_SERVICE_PATH, ()).add(
+                    f.interface(NM_SERVICE_INTERFACE, ())
+                        .add_m(f.method("ActivateConnection", (), move |m| {
+                            let (connection, device, specific_object) =
+                                m.msg.read3::<Path, Path, Path>()?;
+                            calls_tx
+                                .send(vec![
+                                    connection.to_string(),
+                                    device.to_string(),
+                                    specific_object.to_string(),
+                                ])
+                                .unwrap();
+                            Ok(vec![m.msg.method_return().append1(Path::from(ACTIVE_PATH))])
+                        }))
+                        .add_m(f.method("DeactivateConnection", (), move |m| {
+                            let active: Path = m.msg.read1()?;
+                            deactivate_tx.send(vec![active.to_string()]).unwrap();
+                            Ok(vec![m.msg.method_return()])
+                        })),
+                ),
+            )
+        });
+
+        let dbus_manager = DBusNetworkManager::with_mock_service(SERVICE);
+
+        let active_path = dbus_manager
+            .activate_connection(PROFILE_PATH, DEVICE_PATH, "/")
+            .unwrap();
+        assert_eq!(active_path, ACTIVE_PATH);
+        assert_eq!(
+            calls_rx.recv().unwrap(),
+            vec![PROFILE_PATH, DEVICE_PATH, "/"]
+        );
+
+        dbus_manager.deactivate_connection(&active_path).unwrap();
+        assert_eq!(calls_rx.recv().unwrap(), vec![ACTIVE_PATH]);
+    }
+
+    #[test]
+    fn test_update_connection() {
+        const SERVICE: &str = "org.freedesktop.NetworkManager.Test.UpdateConnection";
+
+        let (updates_tx, updates_rx) = mpsc::channel();
+        let _service = spawn_profiles(SERVICE, &[(PROFILE_PATH, "802-3-ethernet")], updates_tx);
+        let dbus_manager = DBusNetworkManager::with_mock_service(SERVICE);
+
+        let mut connection: VariantMap = HashMap::new();
+        add_str(&mut connection, "id", "office");
+        add_val(&mut connection, "autoconnect", false);
+
+        let mut settings: SettingsMap = HashMap::new();
+        settings.insert("connection".to_string(), connection);
+
+        dbus_manager
+            .update_connection(PROFILE_PATH, &settings)
+            .unwrap();
+        let (method, path, update) = updates_rx.recv().unwrap();
+        assert_eq!((method, path.as_str()), ("Update", PROFILE_PATH));
+        assert_eq!(update["connection.id"], MessageItem::Str("office".to_string()));
+        assert_eq!(update["connection.autoconnect"], MessageItem::Bool(false));
+
+        dbus_manager
+            .update_connection_unsaved(PROFILE_PATH, &settings)
+            .unwrap();
+        let (method, _, update) = updates_rx.recv().unwrap();
+        assert_eq!(method, "UpdateUnsaved");
+        assert_eq!(update["connection.id"], MessageItem::Str("office".to_string()));
+    }
+
+    #[test]
+    fn test_update_connection_round_trip() {
+        const SERVICE: &str = "org.freedesktop.NetworkManager.Test.UpdateConnectionRoundTrip";
+
+        let (updates_tx, updates_rx) = mpsc::channel();
+        let _service = spawn_profiles(SERVICE, &[(PROFILE_PATH, "802-11-wireless")], updates_tx);
+        let dbus_manager = DBusNetworkManager::with_mock_service(SERVICE);
+
+        let mut settings = dbus_manager
+            .get_connection_settings_map(PROFILE_PATH)
+            .unwrap();
+
+        let id: Option<String> = DBusApi::variant_to(&settings["connection"]["id"]);
+        assert_eq!(id, Some("uplink".to_string()));
+        let dns: Option<Vec<u32>> = DBusApi::variant_to(&settings["ipv4"]["dns"]);
+        assert_eq!(dns, Some(Vec::new()));
+
+        add_val(settings.get_mut("connection").unwrap(), "autoconnect", false);
+        let mut security: VariantMap = HashMap::new();
+        add_str(&mut security, "psk", "hunter22");
+        settings.insert("802-11-wireless-security".to_string(), security);
+
+        dbus_manager
+            .update_connection(PROFILE_PATH, &settings)
+            .unwrap();
+        let (_, _, update) = updates_rx.recv().unwrap();
+        assert_eq!(update["connection.autoconnect"], MessageItem::Bool(false));
+        assert_eq!(
+            update["802-11-wireless-security.psk"],
+            MessageItem::Str("hunter22".to_string())
+        );
+        assert_profile_kept(&update, "802-11-wireless", "connection.autoconnect");
+    }
+
+    #[test]
+    fn test_add_connection() {
+        const SERVICE: &str = "org.freedesktop.NetworkManager.Test.AddConnection";
+
+        let (ids_tx, ids_rx) = mpsc::channel();
+
+        let _service = MockService::spawn(SERVICE, move |f, _| {
+            let add = |method: &'static str, path: &'static str| {
+                let ids = ids_tx.clone();
+
+                f.method(method, (), move |m| {
+                    let settings: SettingsMap = m.msg.read1()?;
+                    let id: Option<String> = DBusApi::variant_to(&settings["connection"]["id"]);
+                    ids.send(id).unwrap();
+                    Ok(vec![m.msg.method_return().append1(Path::from(path))])
+                })
+            };
+
+            f.tree(()).add(
+                f.object_path(NM_SETTINGS_PATH, ()).add(
+                    f.interface(NM_SETTINGS_INTERFACE, ())
+                        .add_m(add(
+                            "AddConnection",
+                            "/org/freedesktop/NetworkManager/Settings/7",
+                        ))
+                        .add_m(add(
+                            "AddConnectionUnsaved",
+                            "/org/freedesktop/NetworkManager/Settings/8",
+                        )),
+                ),
+            )
+        });
+
+        let dbus_manager = DBusNetworkManager::with_mock_service(SERVICE);
+
+        let mut connection: VariantMap = HashMap::new();
+        add_str(&mut connection, "id", "staged");
+        add_str(&mut connection, "type", "802-3-ethernet");
+
+        let mut settings: SettingsMap = HashMap::new();
+        settings.insert("connection".to_string(), connection);
+
+        assert_eq!(
+            dbus_manager.add_connection(&settings).unwrap(),
+            "/org/freedesktop/NetworkManager/Settings/7"
+        );
+        assert_eq!(ids_rx.recv().unwrap(), Some("staged".to_string()));
+
+        assert_eq!(
+            dbus_manager.add_connection_unsaved(&settings).unwrap(),
+            "/org/freedesktop/NetworkManager/Settings/8"
+        );
+        assert_eq!(ids_rx.recv().unwrap(), Some("staged".to_string()));
+    }
+
+    #[test]
+    fn test_get_ip4_config() {
+        const SERVICE: &str = "org.freedesktop.NetworkManager.Test.GetIp4Config";
+        const UNCONFIGURED_PATH: &str = "/org/freedesktop/NetworkManager/Devices/2";
+        const CONFIG_PATH: &str = "/org/freedesktop/NetworkManager/IP4Config/1";
+
+        let _service = MockService::spawn(SERVICE, |f, _| {
+            let device = |path: &'static str, config: &'static str| {
+                f.object_path(path, ()).add(
+                    f.interface(NM_DEVICE_INTERFACE, ()).add_p(
+                        f.property::<Path, _>("Ip4Config", ()).on_get(move |i, _| {
+                            i.append(Path::from(config));
+                            Ok(())
+                        }),
+                    ),
+                )
+            };
+
+            f.tree(())
+                .add(device(DEVICE_PATH, CONFIG_PATH))
+                .add(device(UNCONFIGURED_PATH, "/"))
+                .add(f.object_path(CONFIG_PATH, ()).add(
+                    f.interface(NM_IP4CONFIG_INTERFACE, ())
+                        .add_p(
+                            f.property::<Vec<VariantMap>, _>("AddressData", ())
+                                .on_get(|i, _| {
+                                    let mut address: VariantMap = HashMap::new();
+                                    add_str(&mut address, "address", "192.168.1.10");
+                                    add_val(&mut address, "prefix", 24_u32);
+                                    i.append(vec![address]);
+                                    Ok(())
+                                }),
+                        )
+                        .add_p(f.property::<&str, _>("Gateway", ()).on_get(|i, _| {
+                            i.append("192.168.1.1");
+                            Ok(())
+                        }))
+                        .add_p(
+                            f.property::<Vec<VariantMap>, _>("NameserverData", ())
+                                .on_get(|i, _| {
+                                    let mut nameserver: VariantMap = HashMap::new();
+                                    add_str(&mut nameserver, "address", "192.168.1.53");
+                                    i.append(vec![nameserver]);
+                                    Ok(())
+                                }),
+                        )
+                        .add_p(f.property::<Vec<&str>, _>("Domains", ()).on_get(|i, _| {
+                            i.append(vec!["lan"]);
+                            Ok(())
+                        })),
+                ))
+        });
+
+        let dbus_manager = DBusNetworkManager::with_mock_service(SERVICE);
+
+        assert_eq!(
+            dbus_manager.get_ip4_config(DEVICE_PATH).unwrap(),
+            IP4Config {
+                addresses: vec![
+                    IP4Address {
+                        address: "192.168.1.10".to_string(),
+                        prefix: 24,
+                    },
+                ],
+                gateway: "192.168.1.1".to_string(),
+                nameservers: vec!["192.168.1.53".to_string()],
+                domains: vec!["lan".to_string()],
+            }
+        );
+
+        assert_eq!(
+            dbus_manager.get_ip4_config(UNCONFIGURED_PATH).unwrap(),
+            IP4Config::default()
+        );
+    }
+
+    #[test]
+    fn test_decode_ip4_config_legacy() {
+        let address = u32::from(Ipv4Addr::new(10, 0, 0, 2)).to_be();
+        let nameserver = u32::from(Ipv4Addr::new(10, 0, 0, 1)).to_be();
+
+        let mut properties: VariantMap = HashMap::new();
+        add_val(&mut properties, "Addresses", vec![vec![address, 8_u32, 0_u32]]);
+        add_val(&mut properties, "Nameservers", vec![nameserver]);
+
+        let config = decode_ip4_config(&properties);
+
+        assert_eq!(
+            config.addresses,
+            vec![
+                IP4Address {
+                    address: "10.0.0.2".to_string(),
+                    prefix: 8,
+                },
+            ]
+        );
+        assert_eq!(config.nameservers, vec!["10.0.0.1".to_string()]);
+    }
+
+    #[test]
+    fn test_get_dhcp4_config() {
+        const SERVICE: &str = "org.freedesktop.NetworkManager.Test.GetDhcp4Config";
+        const STATIC_PATH: &str = "/org/freedesktop/NetworkManager/Devices/2";
+        const CONFIG_PATH: &str = "/org/freedesktop/NetworkManager/DHCP4Config/1";
+
+        let _service = MockService::spawn(SERVICE, |f, _| {
+            let device = |path: &'static str, config: &'static str| {
+                f.object_path(path, ()).add(
+                    f.interface(NM_DEVICE_INTERFACE, ()).add_p(
+                        f.property::<Path, _>("Dhcp4Config", ()).on_get(move |i, _| {
+                            i.append(Path::from(config));
+                            Ok(())
+                        }),
+                    ),
+                )
+            };
+
+            f.tree(())
+                .add(device(DEVICE_PATH, CONFIG_PATH))
+                .add(device(STATIC_PATH, "/"))
+                .add(f.object_path(CONFIG_PATH, ()).add(
+                    f.interface(NM_DHCP4CONFIG_INTERFACE, ()).add_p(
+                        f.property::<VariantMap, _>("Options", ()).on_get(|i, _| {
+                            let mut options: VariantMap = HashMap::new();
+                            add_str(&mut options, "dhcp_lease_time", "86400");
+                            add_str(&mut options, "domain_name", "lan");
+                            i.append(options);
+                            Ok(())
+                        }),
+                    ),
+                ))
+        });
+
+        let dbus_manager = DBusNetworkManager::with_mock_service(SERVICE);
+
+        let options = dbus_manager.get_dhcp4_config(DEVICE_PATH).unwrap();
+        assert_eq!(options.len(), 2);
+        assert_eq!(options["dhcp_lease_time"], "86400");
+        assert_eq!(options["domain_name"], "lan");
+
+        assert!(dbus_manager.get_dhcp4_config(STATIC_PATH).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_enable_reload() {
+        const SERVICE: &str = "org.freedesktop.NetworkManager.Test.EnableReload";
+
+        let (calls_tx, calls_rx) = mpsc::channel();
+
+        let _service = MockService::spawn(SERVICE, move |f, _| {
+            let reload_tx = calls_tx.clone();
+
+            f.tree(()).add(
+                f.object_path(NM_SERVICE_PATH, ()).add(
+                    f.interface(NM_SERVICE_INTERFACE, ())
+                        .add_m(f.method("Enable", (), move |m| {
+                            let enable: bool = m.msg.read1()?;
+                            calls_tx.send(format!("Enable({})", enable)).unwrap();
+                            Ok(vec![m.msg.method_return()])
+                        }))
+                        .add_m(f.method("Reload", (), move |m| {
+                            let flags: u32 = m.msg.read1()?;
+                            reload_tx.send(format!("Reload({})", flags)).unwrap();
+                            Ok(vec![m.msg.method_return()])
+                        })),
+                ),
+            )
+        });
+
+        let dbus_manager = DBusNetworkManager::with_mock_service(SERVICE);
+
+        dbus_manager.enable(false).unwrap();
+        assert_eq!(calls_rx.recv().unwrap(), "Enable(false)");
+
+        dbus_manager.reload(0x04).unwrap();
+        assert_eq!(calls_rx.recv().unwrap(), "Reload(4)");
+    }
+
+    #[test]
+    fn test_get_dns_configuration() {
+        const SERVICE: &str = "org.freedesktop.NetworkManager.Test.DnsConfiguration";
+
+        let _service = MockService::spawn(SERVICE, |f, _| {
+            f.tree(()).add(
+                f.object_path(NM_DNS_MANAGER_PATH, ()).add(
+                    f.interface(NM_DNS_MANAGER_INTERFACE, ()).add_p(
+                        f.property::<Vec<VariantMap>, _>("Configuration", ())
+                            .on_get(|i, _| {
+                                let mut entry: VariantMap = HashMap::new();
+                                add_val(
+                                    &mut entry,
+                                    "nameservers",
+                                    vec!["192.168.1.53".to_string(), "192.168.1.54".to_string()],
+                                );
+                                add_val(&mut entry, "domains", vec!["lan".to_string()]);
+                                add_str(&mut entry, "interface", "wlan0");
+                                add_val(&mut entry, "priority", 600_i32);
+                                add_val(&mut entry, "vpn", false);
+                                i.append(vec![entry]);
+                                Ok(())
+                            }),
+                    ),
+                ),
+            )
+        });
+
+        let dbus_manager = DBusNetworkManager::with_mock_service(SERVICE);
+
+        assert_eq!(
+            dbus_manager.get_dns_configuration().unwrap(),
+            vec![
+                DnsEntry {
+                    nameservers: vec!["192.168.1.53".to_string(), "192.168.1.54".to_string()],
+                    domains: vec!["lan".to_string()],
+                    interface: "wlan0".to_string(),
+                    priority: 600,
+                    vpn: false,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_get_dns_manager_info() {
+        const SERVICE: &str = "org.freedesktop.NetworkManager.Test.DnsManagerInfo";
+
+        let _service = MockService::spawn(SERVICE, |f, _| {
+            f.tree(()).add(
+                f.object_path(NM_DNS_MANAGER_PATH, ()).add(
+                    f.interface(NM_DNS_MANAGER_INTERFACE, ())
+                        .add_p(f.property::<&str, _>("Mode", ()).on_get(|i, _| {
+                            i.append("systemd-resolved");
+                            Ok(())
+                        }))
+                        .add_p(f.property::<&str, _>("RcManager", ()).on_get(|i, _| {
+                            i.append("symlink");
+                            Ok(())
+                        })),
+                ),
+            )
+        });
+
+        let dbus_manager = DBusNetworkManager::with_mock_service(SERVICE);
+
+        assert_eq!(
+            dbus_manager.get_dns_manager_info().unwrap(),
+            DnsManagerInfo {
+                mode: "systemd-resolved".to_string(),
+                rc_manager: "symlink".to_string(),
+            }
+        );
+    }
+
+    /// Serves the primary connection properties, reporting `path` and `kind`.
+    fn spawn_primary_connection(
+        service: &'static str,
+        path: &'static str,
+        kind: &'static str,
+    ) -> MockService {
+        MockService::spawn(service, move |f, _| {
+            f.tree(()).add(
+                f.object_path(NM_SERVICE_PATH, ()).add(
+                    f.interface(NM_SERVICE_INTERFACE, ())
+                        .add_p(f.property::<Path, _>("PrimaryConnection", ()).on_get(
+                            move |i, _| {
+                                i.append(Path::from(path));
+                                Ok(())
+                            },
+                        ))
+                        .add_p(f.property::<&str, _>("PrimaryConnectionType", ()).on_get(
+                            move |i, _| {
+                                i.append(kind);
+                                Ok(())
+                            },
+                        )),
+                ),
+            )
+        })
+    }
+
+    #[test]
+    fn test_get_primary_connection() {
+        const SERVICE: &str = "org.freedesktop.NetworkManager.Test.PrimaryConnection";
+
+        let _service = spawn_primary_connection(SERVICE, ACTIVE_PATH, "802-11-wireless");
+        let dbus_manager = DBusNetworkManager::with_mock_service(SERVICE);
+
+        assert_eq!(
+            dbus_manager.get_primary_connection().unwrap(),
+            Some(ACTIVE_PATH.to_string())
+        );
+        assert_eq!(
+            dbus_manager.get_primary_connection_type().unwrap(),
+            Some("802-11-wireless".to_string())
+        );
+    }
+
+    #[test]
+    fn test_get_primary_connection_none() {
+        const SERVICE: &str = "org.freedesktop.NetworkManager.Test.NoPrimaryConnection";
+
+        let _service = spawn_primary_connection(SERVICE, "/", "");
+        let dbus_manager = DBusNetworkManager::with_mock_service(SERVICE);
+
+        assert_eq!(dbus_manager.get_primary_connection().unwrap(), None);
+        assert_eq!(dbus_manager.get_primary_connection_type().unwrap(), None);
+    }
+
+    #[test]
+    fn test_get_status() {
+        const SERVICE: &str = "org.freedesktop.NetworkManager.Test.Status";
+
+        let _service = MockService::spawn(SERVICE, |f, _| {
+            let flag = |name: &'static str, value: bool| {
+                f.property::<bool, _>(name, ()).on_get(move |i, _| {
+                    i.append(value);
+                    Ok(())
+                })
+            };
+
+            f.tree(()).add(
+                f.object_path(NM_SERVICE_PATH, ()).add(
+                    f.interface(NM_SERVICE_INTERFACE, ())
+                        .add_p(flag("NetworkingEnabled", true))
+                        .add_p(flag("WirelessEnabled", false))
+                        .add_p(flag("WwanEnabled", true))
+                        .add_p(flag("ConnectivityCheckEnabled", false))
+                        .add_p(f.property::<u32, _>("State", ()).on_get(|i, _| {
+                            i.append(70_u32);
+                            Ok(())
+                        }))
+                        .add_p(f.property::<u32, _>("Connectivity", ()).on_get(|i, _| {
+                            i.append(2_u32);
+                            Ok(())
+                        })),
+                ),
+            )
+        });
+
+        let dbus_manager = DBusNetworkManager::with_mock_service(SERVICE);
+
+        assert_eq!(
+            dbus_manager.get_status().unwrap(),
+            NetworkManagerStatus {
+                networking_enabled: true,
+                wireless_enabled: false,
+                wwan_enabled: true,
+                connectivity_check_enabled: Some(false),
+                state: NetworkManagerState::ConnectedGlobal,
+                connectivity: Connectivity::Portal,
+            }
+        );
+    }
+
+    const CHECKPOINT_PATH: &str = "/org/freedesktop/NetworkManager/Checkpoint/1";
+
+    /// Serves the checkpoint methods, describing the calls received via `calls`.
+    fn spawn_checkpoints(service: &'static str, calls: mpsc::Sender<String>) -> MockService {
+        MockService::spawn(service, move |f, _| {
+            let destroy_tx = calls.clone();
+            let rollback_tx = calls.clone();
+            let adjust_tx = calls.clone();
+
+            f.tree(()).add(
+                f.object_path(NM_SERVICE_PATH, ()).add(
+                    f.interface(NM_SERVICE_INTERFACE, ())
+                        .add_m(f.method("CheckpointCreate", (), move |m| {
+                            let (devices, timeout, flags): (Vec<Path>, u32, u32) =
+                                m.msg.read3()?;
+                            let devices = devices.iter().map(|d| d.to_string()).collect::<Vec<_>>();
+                            calls
+                                .send(format!("Create({:?}, {}, {})", devices, timeout, flags))
+                                .unwrap();
+                            Ok(vec![m.msg.method_return().append1(Path::from(CHECKPOINT_PATH))])
+                        }))
+                        .add_m(f.method("CheckpointDestroy", (), move |m| {
+                            let checkpoint: Path = m.msg.read1()?;
+                            destroy_tx.send(format!("Destroy({})", checkpoint)).unwrap();
+                            Ok(vec![m.msg.method_return()])
+                        }))
+                        .add_m(f.method("CheckpointAdjustRollbackTimeout", (), move |m| {
+                            let (checkpoint, timeout): (Path, u32) = m.msg.read2()?;
+                            adjust_tx
+                                .send(format!("Adjust({}, {})", checkpoint, timeout))
+                                .unwrap();
+                            Ok(vec![m.msg.method_return()])
+                        }))
+                        .add_m(f.method("CheckpointRollback", (), move |m| {
+                            let checkpoint: Path = m.msg.read1()?;
+                            rollback_tx.send(format!("Rollback({})", checkpoint)).unwrap();
+
+                            let mut results = HashMap::new();
+                            results.insert(DEVICE_PATH, 0_u32);
+                            results.insert("/org/freedesktop/NetworkManager/Devices/2", 1_u32);
+                            Ok(vec![m.msg.method_return().append1(results)])
+                        })),
+                ),
+            )
+        })
+    }
+
+    #[test]
+    fn test_checkpoints() {
+        const SERVICE: &str = "org.freedesktop.NetworkManager.Test.Checkpoints";
+
+        let (calls_tx, calls_rx) = mpsc::channel();
+        let _service = spawn_checkpoints(SERVICE, calls_tx);
+        let dbus_manager = DBusNetworkManager::with_mock_service(SERVICE);
+
+        let flags = CheckpointFlags::DESTROY_ALL | CheckpointFlags::DELETE_NEW_CONNECTIONS;
+        let checkpoint = dbus_manager
+            .create_checkpoint(&[DEVICE_PATH], 60, flags)
+            .unwrap();
+        assert_eq!(checkpoint, CHECKPOINT_PATH);
+        assert_eq!(
+            calls_rx.recv().unwrap(),
+            format!("Create([\"{}\"], 60, 3)", DEVICE_PATH)
+        );
+
+        dbus_manager
+            .checkpoint_adjust_rollback_timeout(&checkpoint, 30)
+            .unwrap();
+        assert_eq!(calls_rx.recv().unwrap(), format!("Adjust({}, 30)", CHECKPOINT_PATH));
+
+        let results = dbus_manager.rollback_checkpoint(&checkpoint).unwrap();
+        assert_eq!(calls_rx.recv().unwrap(), format!("Rollback({})", CHECKPOINT_PATH));
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[DEVICE_PATH], 0);
+        assert_eq!(results["/org/freedesktop/NetworkManager/Devices/2"], 1);
+
+        dbus_manager.destroy_checkpoint(&checkpoint).unwrap();
+        assert_eq!(calls_rx.recv().unwrap(), format!("Destroy({})", CHECKPOINT_PATH));
+    }
+
+    #[test]
+    fn test_set_radio_enabled() {
+        const SERVICE: &str = "org.freedesktop.NetworkManager.Test.SetRadioEnabled";
+
+        let _service = MockService::spawn(SERVICE, |f, _| {
+            let radio = |name: &'static str| {
+                let enabled = Rc::new(Cell::new(true));
+                let set_enabled = enabled.clone();
+
+                f.property::<bool, _>(name, ())
+                    .access(Access::ReadWrite)
+                    .on_get(move |i, _| {
+                        i.append(enabled.get());
+                        Ok(())
+                    })
+                    .on_set(move |i, _| {
+                        set_enabled.set(i.read()?);
+                        Ok(())
+                    })
+            };
+
+            f.tree(()).add(
+                f.object_path(NM_SERVICE_PATH, ()).add(
+                    f.interface(NM_SERVICE_INTERFACE, ())
+                        .add_p(radio("WirelessEnabled"))
+                        .add_p(radio("WwanEnabled")),
+                ),
+            )
+        });
+
+        let dbus_manager = DBusNetworkManager::with_mock_service(SERVICE);
+
+        assert!(dbus_manager.is_wireless_enabled().unwrap());
+        dbus_manager.set_wireless_enabled(false).unwrap();
+        assert!(!dbus_manager.is_wireless_enabled().unwrap());
+        assert!(dbus_manager.is_wwan_enabled().unwrap());
+
+        dbus_manager.set_wwan_enabled(false).unwrap();
+        assert!(!dbus_manager.is_wwan_enabled().unwrap());
+        dbus_manager.set_wwan_enabled(true).unwrap();
+        assert!(dbus_manager.is_wwan_enabled().unwrap());
+    }
+
+    #[test]
+    fn test_set_device_flags() {
+        const SERVICE: &str = "org.freedesktop.NetworkManager.Test.SetDeviceFlags";
+
+        let _service = spawn_device(SERVICE, false);
+        let dbus_manager = DBusNetworkManager::with_mock_service(SERVICE);
+
+        assert!(dbus_manager.is_device_managed(DEVICE_PATH).unwrap());
+        dbus_manager.set_device_managed(DEVICE_PATH, false).unwrap();
+        assert!(!dbus_manager.is_device_managed(DEVICE_PATH).unwrap());
+
+        assert!(dbus_manager.is_device_autoconnect(DEVICE_PATH).unwrap());
+        dbus_manager
+            .set_device_autoconnect(DEVICE_PATH, false)
+            .unwrap();
+        assert!(!dbus_manager.is_device_autoconnect(DEVICE_PATH).unwrap());
+        assert!(!dbus_manager.is_device_managed(DEVICE_PATH).unwrap());
+    }
+
+    #[test]
+    fn test_get_check_connectivity() {
+        const SERVICE: &str = "org.freedesktop.NetworkManager.Test.Connectivity";
+
+        let _service = MockService::spawn(SERVICE, |f, _| {
+            f.tree(()).add(
+                f.object_path(NM_SERVICE_PATH, ()).add(
+                    f.interface(NM_SERVICE_INTERFACE, ())
+                        .add_p(f.property::<u32, _>("Connectivity", ()).on_get(|i, _| {
+                            i.append(2_u32);
+                            Ok(())
+                        }))
+                        .add_m(f.method("CheckConnectivity", (), |m| {
+                            Ok(vec![m.msg.method_return().append1(4_u32)])
+                        })),
+                ),
+            )
+        });
+
+        let dbus_manager = DBusNetworkManager::with_mock_service(SERVICE);
+
+        assert_eq!(dbus_manager.get_connectivity().unwrap(), Connectivity::Portal);
+        assert_eq!(dbus_manager.check_connectivity().unwrap(), Connectivity::Full);
+    }
+
+    #[test]
+    fn test_access_point_settings_eap() {
+        let ssid = "corp".as_ssid_slice().unwrap();
+        let credentials = AccessPointCredentials::Eap(
+            Enterprise8021x::new(EapMethod::Ttls)
+                .identity("alice")
+                .password("hunter22")
+                .phase2_auth("pap"),
+        );
+
+        let settings = access_point_settings(ssid, &credentials).unwrap();
+
+        let key_mgmt: Option<String> =
+            DBusApi::variant_to(&settings["802-11-wireless-security"]["key-mgmt"]);
+        assert_eq!(key_mgmt, Some("wpa-eap".to_string()));
+
+        let eap: Option<Vec<String>> = DBusApi::variant_to(&settings["802-1x"]["eap"]);
+        assert_eq!(eap, Some(vec!["ttls".to_string()]));
+        let phase2_auth: Option<String> = DBusApi::variant_to(&settings["802-1x"]["phase2-auth"]);
+        assert_eq!(phase2_auth, Some("pap".to_string()));
+    }
+
+    #[test]
+    fn test_connect_to_hidden_ssid() {
+        const SERVICE: &str = "org.freedesktop.NetworkManager.Test.ConnectToHiddenSsid";
+
+        let (calls_tx, calls_rx) = mpsc::channel();
+
+        let _service = MockService::spawn(SERVICE, move |f, _| {
+            f.tree(()).add(f.object_path(NM_SERVICE_PATH, ()).add(
+                f.interface(NM_SERVICE_INTERFACE, ()).add_m(f.method(
+                    "AddAndActivateConnection",
+                    (),
+                    move |m| {
+                        let (settings, device, specific_object) =
+                            m.msg.read3::<SettingsMap, Path, Path>()?;
+                        let wireless = &settings["802-11-wireless"];
+                        let ssid: Option<Vec<u8>> = DBusApi::variant_to(&wireless["ssid"]);
+                        let hidden: Option<bool> = DBusApi::variant_to(&wireless["hidden"]);
+                        calls_tx
+                            .send((
+                                ssid,
+                                hidden,
+                                device.to_string(),
+                                specific_object.to_string(),
+                            ))
+                            .unwrap();
+                        Ok(vec![
+                            m.msg.method_return().append2(
+                                Path::from("/org/freedesktop/NetworkManager/Settings/4"),
+                                Path::from("/org/freedesktop/NetworkManager/ActiveConnection/4"),
+                            ),
+                        ])
+                    },
+                )),
+            ))
+        });
+
+        let dbus_manager = DBusNetworkManager::with_mock_service(SERVICE);
+
+        let (path, active_path) = dbus_manager
+            .connect_to_hidden_ssid(DEVICE_PATH, "stealth", Some("hunter22"))
+            .unwrap();
+        assert_eq!(path, "/org/freedesktop/NetworkManager/Settings/4");
+        assert_eq!(active_path, "/org/freedesktop/NetworkManager/ActiveConnection/4");
+
+        assert_eq!(
+            calls_rx.recv().unwrap(),
+            (
+                Some(b"stealth".to_vec()),
+                Some(true),
+                DEVICE_PATH.to_string(),
+                "/".to_string(),
+            )
+        );
+    }
+
+    #[test]
+    fn test_hidden_ssid_settings() {
+        let ssid = "stealth".as_ssid_slice().unwrap();
+
+        let settings = hidden_ssid_settings(ssid, None).unwrap();
+        let hidden: Option<bool> = DBusApi::variant_to(&settings["802-11-wireless"]["hidden"]);
+        assert_eq!(hidden, Some(true));
+        assert!(!settings.contains_key("802-11-wireless-security"));
+
+        let settings = hidden_ssid_settings(ssid, Some("hunter22")).unwrap();
+        let key_mgmt: Option<String> =
+            DBusApi::variant_to(&settings["802-11-wireless-security"]["key-mgmt"]);
+        assert_eq!(key_mgmt, Some("wpa-psk".to_string()));
+    }
+
+    #[test]
+    fn test_disconnect_device() {
+        const SERVICE: &str = "org.freedesktop.NetworkManager.Test.DisconnectDevice";
+        const IDLE_PATH: &str = "/org/freedesktop/NetworkManager/Devices/2";
+
+        let (disconnected_tx, disconnected_rx) = mpsc::channel();
+
+        let _service = MockService::spawn(SERVICE, move |f, _| {
+            f.tree(())
+                .add(f.object_path(DEVICE_PATH, ()).add(
+                    f.interface(NM_DEVICE_INTERFACE, ()).add_m(f.method(
+                        "Disconnect",
+                        (),
+                        move |m| {
+                            let path = m.msg.path().unwrap().to_string();
+                            disconnected_tx.send(path).unwrap();
+                            Ok(vec![m.msg.method_return()])
+                        },
+                    )),
+                ))
+                .add(f.object_path(IDLE_PATH, ()).add(
+                    f.interface(NM_DEVICE_INTERFACE, ())
+                        .add_m(f.method("Disconnect", (), |_| {
+                            Err(MethodErr::from((
+                                "org.freedesktop.NetworkManager.Device.NotActive",
+                                "This device is not active",
+                            )))
+                        })),
+                ))
+        });
+
+        let dbus_manager = DBusNetworkManager::with_mock_service(SERVICE);
+
+        dbus_manager.disconnect_device(DEVICE_PATH).unwrap();
+        assert_eq!(disconnected_rx.recv().unwrap(), DEVICE_PATH);
+
+        match *dbus_manager.disconnect_device(IDLE_PATH).unwrap_err().kind() {
+            ErrorKind::DBusError(DBusError::NotActive(_)) => {},
+            ref other => panic!("expected not active, got {:?}", other),
+        }
+    }
+
+    /// A setting holding `values`, as an entry of `a{sa{sv}}` connection settings.
+    fn setting_item(name: &str, values: Vec<(&str, MessageItem)>) -> MessageItem {
+        let values = values
+            .into_iter()
+            .map(|(key, value)| {
+                MessageItem::DictEntry(
+                    Box::new(key.into()),
+                    Box::new(MessageItem::Variant(Box::new(value))),
+                )
+            })
+            .collect();
+
+        MessageItem::DictEntry(
+            Box::new(name.into()),
+            Box::new(MessageItem::Array(values, "{sv}".into())),
+        )
+    }
+
+    /// The settings of a `kind` profile as NetworkManager serves them, including nested and
+    /// empty containers whose signatures are easily lost when sending the settings back.
+    fn nested_profile(kind: &str) -> MessageItem {
+        let address = MessageItem::Array(
+            vec![
+                u32::from(Ipv4Addr::new(10, 0, 0, 2)).to_be().into(),
+                24_u32.into(),
+                0_u32.into(),
+            ],
+            "u".into(),
+        );
+        let s390_option = MessageItem::DictEntry(Box::new("portno".into()), Box::new("0".into()));
+
+        let mut settings = vec![
+            setting_item(
+                "connection",
+                vec![
+                    ("id", "uplink".into()),
+                    ("type", kind.into()),
+                    ("autoconnect", true.into()),
+                    ("zone", "work".into()),
+                    ("interface-name", "eth0".into()),
+                ],
+            ),
+            setting_item(
+                "802-3-ethernet",
+                vec![
+                    (
+                        "s390-options",
+                        MessageItem::Array(vec![s390_option], "{ss}".into()),
+                    ),
+                ],
+            ),
+            setting_item(
+                "ipv4",
+                vec![
+                    ("method", "manual".into()),
+                    ("addresses", MessageItem::Array(vec![address], "au".into())),
+                    ("address-data", MessageItem::Array(Vec::new(), "a{sv}".into())),
+                    ("dns", MessageItem::Array(Vec::new(), "u".into())),
+                ],
+            ),
+            setting_item(
+                "ipv6",
+                vec![
+                    ("method", "auto".into()),
+                    ("addresses", MessageItem::Array(Vec::new(), "(ayuay)".into())),
+                    ("dns", MessageItem::Array(Vec::new(), "ay".into())),
+                ],
+            ),
+        ];
+
+        if kind == "802-11-wireless" {
+            let ssid = b"uplink".iter().map(|&byte| byte.into()).collect();
+            settings.push(setting_item(
+                "802-11-wireless",
+                vec![("ssid", MessageItem::Array(ssid, "y".into()))],
+            ));
+        }
+
+        MessageItem::Array(settings, "{sa{sv}}".into())
+    }
+
+    /// The values of `a{sa{sv}}` connection settings by `setting.key`.
+    fn flatten_settings(settings: &MessageItem) -> HashMap<String, MessageItem> {
+        let mut values = HashMap::new();
+
+        if let MessageItem::Array(ref settings, _) = *settings {
+            for setting in settings {
+                if let MessageItem::DictEntry(ref name, ref setting) = *setting {
+                    if let MessageItem::Array(ref entries, _) = **setting {
+                        for entry in entries {
+                            if let MessageItem::DictEntry(ref key, ref value) = *entry {
+                                if let MessageItem::Variant(ref value) = **value {
+                                    let name: &str = name.inner().unwrap();
+                                    let key: &str = key.inner().unwrap();
+                                    values.insert(format!("{}.{}", name, key), (**value).clone());
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        values
+    }
+
+    /// The method, path and flattened settings of an update received by `spawn_profiles`.
+    type ProfileUpdate = (&'static str, String, HashMap<String, MessageItem>);
+
+    /// Serves a `nested_profile` of each of the given paths and kinds, sending every `Update`
+    /// and `UpdateUnsaved` call via `updates`.
+    fn spawn_profiles(
+        service: &'static str,
+        profiles: &'static [(&'static str, &'static str)],
+        updates: mpsc::Sender<ProfileUpdate>,
+    ) -> MockService {
+        MockService::spawn(service, move |f, _| {
+            let update = |path: &'static str, method: &'static str| {
+                let updates = updates.clone();
+
+                f.method(method, (), move |m| {
+                    let settings = flatten_settings(&m.msg.get_items()[0]);
+                    updates.send((method, path.to_string(), settings)).unwrap();
+                    Ok(vec![m.msg.method_return()])
+                })
+            };
+
+            let mut tree = f.tree(());
+
+            for &(path, kind) in profiles {
+                tree = tree.add(
+                    f.object_path(path, ()).add(
+                        f.interface(NM_CONNECTION_INTERFACE, ())
+                            .add_m(f.method("GetSettings", (), move |m| {
+                                Ok(vec![m.msg.method_return().append1(nested_profile(kind))])
+                            }))
+                            .add_m(update(path, "Update"))
+                            .add_m(update(path, "UpdateUnsaved")),
+                    ),
+                );
+            }
+
+            tree
+        })
+    }
+
+    /// Asserts that `update` kept all values of a `kind` profile but `changed`, with the
+    /// signatures NetworkManager serves them with.
+    fn assert_profile_kept(update: &HashMap<String, MessageItem>, kind: &str, changed: &str) {
+        for (key, value) in &flatten_settings(&nested_profile(kind)) {
+            if key != changed {
+                assert_eq!(update.get(key), Some(value), "{} not kept", key);
+            }
+        }
+
+        assert_eq!(&*update["ipv4.addresses"].type_sig(), "aau");
+        assert_eq!(&*update["ipv4.address-data"].type_sig(), "aa{sv}");
+        assert_eq!(&*update["ipv4.dns"].type_sig(), "au");
+        assert_eq!(&*update["ipv6.addresses"].type_sig(), "a(ayuay)");
+        assert_eq!(&*update["ipv6.dns"].type_sig(), "aay");
+        assert_eq!(&*update["802-3-ethernet.s390-options"].type_sig(), "a{ss}");
+    }
+
+    #[test]
+    fn test_set_autoconnect() {
+        const SERVICE: &str = "org.freedesktop.NetworkManager.Test.SetAutoconnect";
+
+        let (updates_tx, updates_rx) = mpsc::channel();
+        let _service = spawn_profiles(SERVICE, &[(PROFILE_PATH, "802-3-ethernet")], updates_tx);
+        let dbus_manager = DBusNetworkManager::with_mock_service(SERVICE);
+
+        dbus_manager
+            .set_autoconnect_priority(PROFILE_PATH, 50)
+            .unwrap();
+        let (_, path, update) = updates_rx.recv().unwrap();
+        assert_eq!(path, PROFILE_PATH);
+        assert_eq!(update["connection.autoconnect-priority"], MessageItem::Int32(50));
+        assert_profile_kept(&update, "802-3-ethernet", "connection.autoconnect-priority");
+
+        dbus_manager
+            .set_autoconnect(PROFILE_PATH, false)
+            .unwrap();
+        let (_, _, update) = updates_rx.recv().unwrap();
+        assert_eq!(update["connection.autoconnect"], MessageItem::Bool(false));
+        assert!(!update.contains_key("connection.autoconnect-priority"));
+        assert_profile_kept(&update, "802-3-ethernet", "connection.autoconnect");
+    }
+
+    #[test]
+    fn test_set_wifi_powersave() {
+        const SERVICE: &str = "org.freedesktop.NetworkManager.Test.SetWifiPowersave";
+
+        let (updates_tx, updates_rx) = mpsc::channel();
+        let _service = spawn_profiles(SERVICE, &[(PROFILE_PATH, "802-11-wireless")], updates_tx);
+        let dbus_manager = DBusNetworkManager::with_mock_service(SERVICE);
+
+        dbus_manager
+            .set_wifi_powersave(PROFILE_PATH, WirelessPowersave::Disable)
+            .unwrap();
+        let (_, _, update) = updates_rx.recv().unwrap();
+        assert_eq!(update["802-11-wireless.powersave"], MessageItem::UInt32(2));
+        assert_profile_kept(&update, "802-11-wireless", "802-11-wireless.powersave");
+    }
+
+    #[test]
+    fn test_set_mtu() {
+        const SERVICE: &str = "org.freedesktop.NetworkManager.Test.SetMtu";
+        const WIFI_PATH: &str = "/org/freedesktop/NetworkManager/Settings/2";
+        const VPN_PATH: &str = "/org/freedesktop/NetworkManager/Settings/3";
+
+        let (updates_tx, updates_rx) = mpsc::channel();
+        let _service = spawn_profiles(
+            SERVICE,
+            &[
+                (PROFILE_PATH, "802-3-ethernet"),
+                (WIFI_PATH, "802-11-wireless"),
+                (VPN_PATH, "vpn"),
+            ],
+            updates_tx,
+        );
+        let dbus_manager = DBusNetworkManager::with_mock_service(SERVICE);
+
+        dbus_manager.set_mtu(PROFILE_PATH, 9000).unwrap();
+        let (_, path, update) = updates_rx.recv().unwrap();
+        assert_eq!(path, PROFILE_PATH);
+        assert_eq!(update["802-3-ethernet.mtu"], MessageItem::UInt32(9000));
+        assert_profile_kept(&update, "802-3-ethernet", "802-3-ethernet.mtu");
+
+        dbus_manager.set_mtu(WIFI_PATH, 1492).unwrap();
+        let (_, path, update) = updates_rx.recv().unwrap();
+        assert_eq!(path, WIFI_PATH);
+        assert_eq!(update["802-11-wireless.mtu"], MessageItem::UInt32(1492));
+        assert!(!update.contains_key("802-3-ethernet.mtu"));
+        assert_profile_kept(&update, "802-11-wireless", "802-11-wireless.mtu");
+
+        match *dbus_manager.set_mtu(VPN_PATH, 1400).unwrap_err().kind() {
+            ErrorKind::Settings(_) => {},
+            ref other => panic!("expected a settings error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_bind_to_interface() {
+        const SERVICE: &str = "org.freedesktop.NetworkManager.Test.BindToInterface";
+
+        let (updates_tx, updates_rx) = mpsc::channel();
+        let _service = spawn_profiles(SERVICE, &[(PROFILE_PATH, "802-3-ethernet")], updates_tx);
+        let dbus_manager = DBusNetworkManager::with_mock_service(SERVICE);
+
+        dbus_manager
+            .bind_to_interface(PROFILE_PATH, Some("enp3s0"))
+            .unwrap();
+        let (_, _, update) = updates_rx.recv().unwrap();
+        assert_eq!(
+            update["connection.interface-name"],
+            MessageItem::Str("enp3s0".to_string())
+        );
+        assert_profile_kept(&update, "802-3-ethernet", "connection.interface-name");
+
+        dbus_manager
+            .bind_to_interface(PROFILE_PATH, None)
+            .unwrap();
+        let (_, _, update) = updates_rx.recv().unwrap();
+        assert!(!update.contains_key("connection.interface-name"));
+        assert_profile_kept(&update, "802-3-ethernet", "connection.interface-name");
+    }
+
+    #[test]
+    fn test_connection_zone() {
+        const SERVICE: &str = "org.freedesktop.NetworkManager.Test.ConnectionZone";
+
+        let (updates_tx, updates_rx) = mpsc::channel();
+        let _service = spawn_profiles(SERVICE, &[(PROFILE_PATH, "802-3-ethernet")], updates_tx);
+        let dbus_manager = DBusNetworkManager::with_mock_service(SERVICE);
+
+        assert_eq!(
+            dbus_manager.get_connection_zone(PROFILE_PATH).unwrap(),
+            Some("work".to_string())
+        );
+
+        dbus_manager
+            .set_connection_zone(PROFILE_PATH, Some("public"))
+            .unwrap();
+        let (_, _, update) = updates_rx.recv().unwrap();
+        assert_eq!(update["connection.zone"], MessageItem::Str("public".to_string()));
+        assert_profile_kept(&update, "802-3-ethernet", "connection.zone");
+
+        dbus_manager
+            .set_connection_zone(PROFILE_PATH, None)
+            .unwrap();
+        let (_, _, update) = updates_rx.recv().unwrap();
+        assert!(!update.contains_key("connection.zone"));
+        assert_profile_kept(&update, "802-3-ethernet", "connection.zone");
+    }
+
+    #[test]
+    fn test_metered() {
+        const SERVICE: &str = "org.freedesktop.NetworkManager.Test.Metered";
+        const DEVICE_SERVICE: &str = "org.freedesktop.NetworkManager.Test.DeviceMetered";
+
+        let (updates_tx, updates_rx) = mpsc::channel();
+        let _service = spawn_profiles(SERVICE, &[(PROFILE_PATH, "802-3-ethernet")], updates_tx);
+        let dbus_manager = DBusNetworkManager::with_mock_service(SERVICE);
+
+        dbus_manager
+            .set_metered(PROFILE_PATH, Metered::Yes)
+            .unwrap();
+        let (_, _, update) = updates_rx.recv().unwrap();
+        assert_eq!(
+            update["connection.metered"],
+            MessageItem::Int32(Metered::Yes.as_i32())
+        );
+        assert_profile_kept(&update, "802-3-ethernet", "connection.metered");
+
+        let _device_service = MockService::spawn(DEVICE_SERVICE, |f, _| {
+            f.tree(()).add(
+                f.object_path(DEVICE_PATH, ()).add(
+                    f.interface(NM_DEVICE_INTERFACE, ()).add_p(
+                        f.property::<u32, _>("Metered", ()).on_get(|i, _| {
+                            i.append(Metered::GuessYes.as_i32() as u32);
+                            Ok(())
+                        }),
+                    ),
+                ),
+            )
+        });
+        let dbus_manager = DBusNetworkManager::with_mock_service(DEVICE_SERVICE);
+
+        assert_eq!(
+            dbus_manager.get_device_metered(DEVICE_PATH).unwrap(),
+            Metered::GuessYes
+        );
+    }
+
+    #[test]
+    fn test_create_gsm_connection() {
+        const SERVICE: &str = "org.freedesktop.NetworkManager.Test.CreateGsmConnection";
+
+        let (calls_tx, calls_rx) = mpsc::channel();
+
+        let _service = MockService::spawn(SERVICE, move |f, _| {
+            f.tree(()).add(f.object_path(NM_SERVICE_PATH, ()).add(
+                f.interface(NM_SERVICE_INTERFACE, ()).add_m(f.method(
+                    "AddAndActivateConnection",
+                    (),
+                    move |m| {
+                        let (settings, device, _) = m.msg.read3::<SettingsMap, Path, Path>()?;
+                        let kind: Option<String> =
+                            DBusApi::variant_to(&settings["connection"]["type"]);
+                        let apn: Option<String> = DBusApi::variant_to(&settings["gsm"]["apn"]);
+                        calls_tx.send((kind, apn, device.to_string())).unwrap();
+                        Ok(vec![
+                            m.msg.method_return().append2(
+                                Path::from("/org/freedesktop/NetworkManager/Settings/5"),
+                                Path::from("/org/freedesktop/NetworkManager/ActiveConnection/5"),
+                            ),
+                        ])
+                    },
+                )),
+            ))
+        });
+
+        let dbus_manager = DBusNetworkManager::with_mock_service(SERVICE);
+
+        let (path, _) = dbus_manager
+            .create_gsm_connection(DEVICE_PATH, &GsmConfig::new("internet"))
+            .unwrap();
+        assert_eq!(path, "/org/freedesktop/NetworkManager/Settings/5");
+
+        assert_eq!(
+            calls_rx.recv().unwrap(),
+            (
+                Some("gsm".to_string()),
+                Some("internet".to_string()),
+                DEVICE_PATH.to_string(),
+            )
+        );
+    }
+
+    fn spawn_add_and_activate(
+        service: &'static str,
+        version: &'static str,
+        calls: mpsc::Sender<(String, Option<String>)>,
+    ) -> MockService {
+        MockService::spawn(service, move |f, _| {
+            let legacy_tx = calls.clone();
+
+            f.tree(()).add(
+                f.object_path(NM_SERVICE_PATH, ()).add(
+                    f.interface(NM_SERVICE_INTERFACE, ())
+                        .add_p(f.property::<&str, _>("Version", ()).on_get(move |i, _| {
+                            i.append(version);
+                            Ok(())
+                        }))
+                        .add_m(f.method("AddAndActivateConnection", (), move |m| {
+                            m.msg.read3::<SettingsMap, Path, Path>()?;
+                            legacy_tx
+                                .send(("AddAndActivateConnection".to_string(), None))
+                                .unwrap();
+                            Ok(vec![m.msg.method_return().append2(
+                                Path::from("/org/freedesktop/NetworkManager/Settings/6"),
+                                Path::from(ACTIVE_PATH),
+                            )])
+                        }))
+                        .add_m(f.method("AddAndActivateConnection2", (), move |m| {
+                            let mut args = m.msg.iter_init();
+                            let _: SettingsMap = args.read()?;
+                            let _: Path = args.read()?;
+                            let _: Path = args.read()?;
+                            let options: VariantMap = args.read()?;
+                            let persist: Option<String> = DBusApi::variant_to(&options["persist"]);
+                            calls
+                                .send(("AddAndActivateConnection2".to_string(), persist))
+                                .unwrap();
+
+                            let mut result = HashMap::new();
+                            result.insert("path", Variant(DEVICE_PATH));
+                            Ok(vec![m.msg.method_return().append3(
+                                Path::from("/org/freedesktop/NetworkManager/Settings/6"),
+                                Path::from(ACTIVE_PATH),
+                                result,
+                            )])
+                        })),
+                ),
+            )
+        })
+    }
+
+    #[test]
+    fn test_add_and_activate_connection_with_options() {
+        const NEW_SERVICE: &str = "org.freedesktop.NetworkManager.Test.AddAndActivate2";
+        const OLD_SERVICE: &str = "org.freedesktop.NetworkManager.Test.AddAndActivateLegacy";
+
+        let mut options: VariantMap = HashMap::new();
+        options.insert(
+            "persist".to_string(),
+            Variant(Box::new("volatile".to_string()) as Box<RefArg>),
+        );
+        let settings = SettingsMap::new();
+
+        let (calls_tx, calls_rx) = mpsc::channel();
+        let _service = spawn_add_and_activate(NEW_SERVICE, "1.16.0", calls_tx);
+        let dbus_manager = DBusNetworkManager::with_mock_service(NEW_SERVICE);
+
+        let (path, active_path, result) = dbus_manager
+            .add_and_activate_connection_with_options(&settings, DEVICE_PATH, "/", &options)
+            .unwrap();
+        assert_eq!(path, "/org/freedesktop/NetworkManager/Settings/6");
+        assert_eq!(active_path, ACTIVE_PATH);
+        assert_eq!(result.len(), 1);
+        assert_eq!(
+            calls_rx.recv().unwrap(),
+            (
+                "AddAndActivateConnection2".to_string(),
+                Some("volatile".to_string())
+            )
+        );
+
+        let (calls_tx, calls_rx) = mpsc::channel();
+        let _service = spawn_add_and_activate(OLD_SERVICE, "1.10.6", calls_tx);
+        let dbus_manager = DBusNetworkManager::with_mock_service(OLD_SERVICE);
+
+        let (path, _, result) = dbus_manager
+            .add_and_activate_connection_with_options(&settings, DEVICE_PATH, "/", &options)
+            .unwrap();
+        assert_eq!(path, "/org/freedesktop/NetworkManager/Settings/6");
+        assert!(result.is_empty());
+        assert_eq!(
+            calls_rx.recv().unwrap(),
+            ("AddAndActivateConnection".to_string(), None)
+        );
+    }
+
+    #[test]
+    fn test_watch_access_point_strength() {
+        const SERVICE: &str = "org.freedesktop.NetworkManager.Test.WatchStrength";
+        const AP_PATH: &str = "/org/freedesktop/NetworkManager/AccessPoint/1";
+
+        let _service = MockService::spawn(SERVICE, |f, signals| {
+            let signals = signals.clone();
+
+            f.tree(()).add(f.object_path(AP_PATH, ()).add(
+                f.interface(NM_ACCESS_POINT_INTERFACE, ()).add_p(
+                    f.property::<u8, _>("Strength", ())
+                        .on_get(move |i, _| {
+                            signals.emit(property_changed(
+                                AP_PATH,
+                                NM_ACCESS_POINT_INTERFACE,
+                                "Strength",
+                                48_u8,
+                            ));
+
+                            i.append(72_u8);
+                            Ok(())
+                        }),
+                ),
+            ))
+        });
+
+        let dbus_manager = DBusNetworkManager::with_mock_service(SERVICE);
+
+        let mut strengths = Vec::new();
+
+        dbus_manager
+            .watch_access_point_strength(AP_PATH, Duration::from_secs(5), |strength| {
+                strengths.push(strength);
+                strengths.len() < 2
+            })
+            .unwrap();
+
+        assert_eq!(strengths, vec![72, 48]);
+    }
+
+    /// Serves a wireless device whose `LastScan` advances once a scan is requested, or which
+    /// lacks `LastScan` like Network Manager before 1.12 if `last_scan` is false.
+    fn spawn_scanning_device(service: &'static str, last_scan: bool) -> MockService {
+        MockService::spawn(service, move |f, signals| {
+            let signals = signals.clone();
+            let scanned = Rc::new(Cell::new(false));
+            let read_scanned = scanned.clone();
+
+            let mut interface = f.interface(NM_WIRELESS_INTERFACE, ())
+                .add_m(f.method("RequestScan", (), move |m| {
+                    scanned.set(true);
+
+                    if last_scan {
+                        signals.emit(property_changed(
+                            DEVICE_PATH,
+                            NM_WIRELESS_INTERFACE,
+                            "LastScan",
+                            2000_i64,
+                        ));
+                    }
+
+                    Ok(vec![m.msg.method_return()])
+                }));
+
+            if last_scan {
+                interface = interface.add_p(f.property::<i64, _>("LastScan", ()).on_get(
+                    move |i, _| {
+                        i.append(if read_scanned.get() { 2000_i64 } else { 1000 });
+                        Ok(())
+                    },
+                ));
+            }
+
+            f.tree(())
+                .add(f.object_path(DEVICE_PATH, ()).add(interface))
+        })
+    }
+
+    #[test]
+    fn test_wait_for_scan() {
+        const SERVICE: &str = "org.freedesktop.NetworkManager.Test.WaitForScan";
+
+        let _service = spawn_scanning_device(SERVICE, true);
+        let dbus_manager = DBusNetworkManager::with_mock_service(SERVICE);
+
+        let started = Instant::now();
+        dbus_manager
+            .wait_for_scan(DEVICE_PATH, Duration::from_secs(5))
+            .unwrap();
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_wait_for_scan_without_last_scan() {
+        const SERVICE: &str = "org.freedesktop.NetworkManager.Test.WaitForScanFallback";
+
+        let _service = spawn_scanning_device(SERVICE, false);
+        let dbus_manager = DBusNetworkManager::with_mock_service(SERVICE);
+
+        let started = Instant::now();
+        dbus_manager
+            .wait_for_scan(DEVICE_PATH, Duration::from_millis(200))
+            .unwrap();
+        assert!(started.elapsed() >= Duration::from_millis(200));
+    }
+
+    /// Serves a `Connectivity` property which reads `initial` and then signals a change to
+    /// `changed`, if any.
+    fn spawn_connectivity(
+        service: &'static str,
+        initial: Connectivity,
+        changed: Option<Connectivity>,
+    ) -> MockService {
+        MockService::spawn(service, move |f, signals| {
+            let signals = signals.clone();
+
+            f.tree(()).add(f.object_path(NM_SERVICE_PATH, ()).add(
+                f.interface(NM_SERVICE_INTERFACE, ()).add_p(
+                    f.property::<u32, _>("Connectivity", ())
+                        .on_get(move |i, _| {
+                            if let Some(ref connectivity) = changed {
+                                signals.emit(property_changed(
+                                    NM_SERVICE_PATH,
+                                    NM_SERVICE_INTERFACE,
+                                    "Connectivity",
+                                    code(connectivity),
+                                ));
+                            }
+
+                            i.append(code(&initial));
+                            Ok(())
+                        }),
+                ),
+            ))
+        })
+    }
+
+    fn code(connectivity: &Connectivity) -> u32 {
+        match *connectivity {
+            Connectivity::Unknown => 0,
+            Connectivity::None => 1,
+            Connectivity::Portal => 2,
+            Connectivity::Limited => 3,
+            Connectivity::Full => 4,
+        }
+    }
+
+    #[test]
+    fn test_wait_for_connectivity() {
+        const SERVICE: &str = "org.freedesktop.NetworkManager.Test.WaitForConnectivity";
+
+        let _service = spawn_connectivity(SERVICE, Connectivity::Portal, Some(Connectivity::Full));
+        let dbus_manager = DBusNetworkManager::with_mock_service(SERVICE);
+
+        let connectivity = dbus_manager
+            .wait_for_connectivity(Connectivity::Full, Duration::from_secs(5))
+            .unwrap();
+        assert_eq!(connectivity, Connectivity::Full);
+
+        let connectivity = dbus_manager
+            .wait_for_connectivity(Connectivity::Portal, Duration::from_secs(5))
+            .unwrap();
+        assert_eq!(connectivity, Connectivity::Portal);
+    }
+
+    #[test]
+    fn test_wait_for_connectivity_timeout() {
+        const SERVICE: &str = "org.freedesktop.NetworkManager.Test.WaitForConnectivityTimeout";
+
+        let _service = spawn_connectivity(SERVICE, Connectivity::Limited, None);
+        let dbus_manager = DBusNetworkManager::with_mock_service(SERVICE);
+
+        let result =
+            dbus_manager.wait_for_connectivity(Connectivity::Full, Duration::from_millis(200));
+
+        match result {
+            Err(Error(ErrorKind::Timeout(_), _)) => {},
+            other => panic!("Unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_device_statistics() {
+        const SERVICE: &str = "org.freedesktop.NetworkManager.Test.DeviceStatistics";
+
+        let _service = MockService::spawn(SERVICE, |f, _| {
+            let refresh_rate = Rc::new(Cell::new(0_u32));
+            let set_refresh_rate = refresh_rate.clone();
+
+            f.tree(()).add(
+                f.object_path(DEVICE_PATH, ()).add(
+                    f.interface(NM_STATISTICS_INTERFACE, ())
+                        .add_p(
+                            f.property::<u32, _>("RefreshRateMs", ())
+                                .access(Access::ReadWrite)
+                                .on_get(move |i, _| {
+                                    i.append(refresh_rate.get());
+                                    Ok(())
+                                })
+                                .on_set(move |i, _| {
+                                    set_refresh_rate.set(i.read()?);
+                                    Ok(())
+                                }),
+                        )
+                        .add_p(f.property::<u64, _>("TxBytes", ()).on_get(|i, _| {
+                            i.append(5_000_000_000_u64);
+                            Ok(())
+                        }))
+                        .add_p(f.property::<u64, _>("RxBytes", ()).on_get(|i, _| {
+                            i.append(12_345_u64);
+                            Ok(())
+                        })),
+                ),
+            )
+        });
+
+        let dbus_manager = DBusNetworkManager::with_mock_service(SERVICE);
+
+        dbus_manager
+            .enable_device_statistics(DEVICE_PATH, 1000)
+            .unwrap();
+
+        assert_eq!(
+            dbus_manager.get_device_statistics(DEVICE_PATH).unwrap(),
+            Stats {
+                tx_bytes: 5_000_000_000,
+                rx_bytes: 12_345,
+                refresh_rate_ms: 1000,
+            }
+        );
+    }
+
+    /// Serves an active connection which reads `Activating` and then signals a transition to
+    /// `state` for `reason`.
+    fn spawn_activating(service: &'static str, state: u32, reason: u32) -> MockService {
+        MockService::spawn(service, move |f, signals| {
+            let signals = signals.clone();
+
+            f.tree(()).add(f.object_path(ACTIVE_PATH, ()).add(
+                f.interface(NM_ACTIVE_INTERFACE, ())
+                    .add_p(f.property::<u32, _>("State", ()).on_get(move |i, _| {
+                        let signal =
+                            Message::new_signal(ACTIVE_PATH, NM_ACTIVE_INTERFACE, "StateChanged")
+                                .unwrap()
+                                .append2(state, reason);
+                        signals.emit(signal);
+
+                        i.append(1_u32);
+                        Ok(())
+                    }))
+                    .add_s(f.signal("StateChanged", ())),
+            ))
+        })
+    }
+
+    #[test]
+    fn test_wait_for_active_connection_activated() {
+        const SERVICE: &str = "org.freedesktop.NetworkManager.Test.WaitActiveActivated";
+
+        let _service = spawn_activating(SERVICE, 2, 1);
+        let dbus_manager = DBusNetworkManager::with_mock_service(SERVICE);
+
+        let state = dbus_manager
+            .wait_for_active_connection_state(
+                ACTIVE_PATH,
+                ConnectionState::Activated,
+                Duration::from_secs(5),
+            )
+            .unwrap();
+        assert_eq!(state, ConnectionState::Activated);
+    }
+
+    #[test]
+    fn test_wait_for_active_connection_deactivated() {
+        const SERVICE: &str = "org.freedesktop.NetworkManager.Test.WaitActiveDeactivated";
+
+        let _service = spawn_activating(SERVICE, 4, 9);
+        let dbus_manager = DBusNetworkManager::with_mock_service(SERVICE);
+
+        let result = dbus_manager.wait_for_active_connection_state(
+            ACTIVE_PATH,
+            ConnectionState::Activated,
+            Duration::from_secs(5),
+        );
+
+        match result {
+            Err(Error(ErrorKind::ActivationFailed(path, reason), _)) => {
+                assert_eq!(path, ACTIVE_PATH);
+                assert_eq!(reason, ActiveConnectionStateReason::NoSecrets);
+            },
+            other => panic!("Unexpected result: {:?}", other),
+        }
+    }
+}
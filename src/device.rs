@@ -1,8 +1,14 @@
+use std::collections::HashMap;
 use std::rc::Rc;
 use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
 
 use errors::*;
-use dbus_nm::DBusNetworkManager;
+use dbus_nm::{DBusNetworkManager, SettingsMap, VariantMap};
+use connection::{add_and_activate_connection, add_connection, create_gsm_connection, Connection,
+                 ConnectionState};
+use settings::{ConnectionBuilder, EthernetConfig, GsmConfig, SlaveType};
 
 use wifi::{new_wifi_device, WiFiDevice};
 
@@ -13,38 +19,54 @@ pub struct Device {
     interface: String,
     device_type: DeviceType,
     hardware_address: Option<String>,
-    ip4config_path: String,
 }
+
+/// The IPv4 configuration a device is currently using.
 #[derive(Default, Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct IP4Config {
-    pub address: String,
+    pub addresses: Vec<IP4Address>,
     pub gateway: String,
-    pub route1: String,
-    pub route2: String,
-    pub dns: String,
-    pub domain: String,
+    pub nameservers: Vec<String>,
+    pub domains: Vec<String>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct IP4Address {
+    pub address: String,
+    pub prefix: u32,
+}
+
+/// Traffic counters of a device, updated by NetworkManager at its statistics refresh rate.
+#[derive(Default, Debug, Clone, Copy, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Stats {
+    pub tx_bytes: u64,
+    pub rx_bytes: u64,
+    /// How often the counters are updated in milliseconds; `0` while updates are disabled.
+    pub refresh_rate_ms: u32,
 }
 
 impl Device {
     fn init(dbus_manager: &Rc<DBusNetworkManager>, path: &str) -> Result<Self> {
-        let interface = dbus_manager.get_device_interface(path)?;
+        let properties = dbus_manager.get_device_properties(path)?;
+
+        let interface: String = properties.get("Interface")?;
 
-        let device_type = dbus_manager.get_device_type(path)?;
+        let device_type: DeviceType = properties.get("DeviceType")?;
 
         let mut hardware_address= Option::None;
         if device_type == DeviceType::WiFi {
             hardware_address = Some(dbus_manager.get_hardware_address(path)?);
         }
 
-        let ip4config_path = dbus_manager.get_device_ip4config_path(path)?;
-
         Ok(Device {
             dbus_manager: Rc::clone(dbus_manager),
             path: path.to_string(),
             interface: interface,
             device_type: device_type,
             hardware_address: hardware_address,
-            ip4config_path: ip4config_path,
         })
     }
 
@@ -62,6 +84,53 @@ impl Device {
 
     pub fn get_hardware_address(&self) -> &Option<String> { &self.hardware_address }
 
+    /// Whether the connection currently active on the device is metered.
+    pub fn get_metered(&self) -> Result<Metered> {
+        self.dbus_manager.get_device_metered(&self.path)
+    }
+
+    /// Whether NetworkManager manages the device or leaves it to other tools.
+    pub fn is_managed(&self) -> Result<bool> {
+        self.dbus_manager.is_device_managed(&self.path)
+    }
+
+    /// Hands the device over to other tools with `false`, deactivating its connection, or
+    /// takes it back with `true`.
+    pub fn set_managed(&self, managed: bool) -> Result<()> {
+        self.dbus_manager.set_device_managed(&self.path, managed)
+    }
+
+    /// Whether the device may activate connections on its own.
+    pub fn is_autoconnect(&self) -> Result<bool> {
+        self.dbus_manager.is_device_autoconnect(&self.path)
+    }
+
+    /// Stops the device from activating connections on its own with `false`, without touching
+    /// the autoconnect setting of any profile.
+    pub fn set_autoconnect(&self, autoconnect: bool) -> Result<()> {
+        self.dbus_manager
+            .set_device_autoconnect(&self.path, autoconnect)
+    }
+
+    /// Waits until the device reaches `target`, failing with `ErrorKind::Timeout` if it does
+    /// not within `timeout`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    /// use network_manager::{DeviceState, NetworkManager};
+    /// let manager = NetworkManager::new();
+    /// let device = manager.get_device_by_interface("wlan0").unwrap();
+    /// device
+    ///     .wait_for_state(&DeviceState::Activated, Duration::from_secs(30))
+    ///     .unwrap();
+    /// ```
+    pub fn wait_for_state(&self, target: &DeviceState, timeout: Duration) -> Result<DeviceState> {
+        self.dbus_manager
+            .wait_for_device_state(&self.path, target, timeout)
+    }
+
     pub fn as_wifi_device(&self) -> Option<WiFiDevice> {
         if self.device_type == DeviceType::WiFi {
             Some(new_wifi_device(&self.dbus_manager, self))
@@ -115,7 +184,14 @@ impl Device {
         match state {
             DeviceState::Disconnected => Ok(DeviceState::Disconnected),
             _ => {
-                self.dbus_manager.disconnect_device(&self.path)?;
+                if let Err(e) = self.dbus_manager.disconnect_device(&self.path) {
+                    // Nothing left to disconnect, e.g. while the device is unavailable
+                    if let ErrorKind::DBusError(DBusError::NotActive(_)) = *e.kind() {
+                        return self.get_state();
+                    }
+
+                    return Err(e);
+                }
 
                 wait(
                     self,
@@ -126,12 +202,68 @@ impl Device {
         }
     }
 
+    /// Saves a profile enslaving this Ethernet device to the bond or bridge with the interface or
+    /// UUID `master`, which takes effect once the profile is activated.
+    pub fn attach_to_master(&self, master: &str, slave_type: SlaveType) -> Result<Connection> {
+        if self.device_type != DeviceType::Ethernet {
+            bail!(ErrorKind::Settings(format!(
+                "Only Ethernet devices can be attached to a master: {}",
+                self.interface
+            )));
+        }
+
+        let settings = ConnectionBuilder::new(&format!("{}-{}", master, self.interface))
+            .interface_name(&self.interface)
+            .ethernet(EthernetConfig::new())
+            .master(master, slave_type)
+            .build()?;
+
+        add_connection(&self.dbus_manager, &settings, true)
+    }
+
+    /// Creates and activates a mobile broadband connection on this modem device.
+    pub fn create_gsm_connection(
+        &self,
+        config: &GsmConfig,
+    ) -> Result<(Connection, ConnectionState)> {
+        create_gsm_connection(&self.dbus_manager, &self.path, config)
+    }
+
+    /// Adds a connection with `settings` and activates it on this device.
+    ///
+    /// `options` and the returned result map are those of `AddAndActivateConnection2`, e.g.
+    /// `persist` set to `"volatile"`; both are ignored by Network Manager older than 1.16.
+    pub fn add_and_activate_connection(
+        &self,
+        settings: &SettingsMap,
+        options: &VariantMap,
+    ) -> Result<(Connection, ConnectionState, VariantMap)> {
+        add_and_activate_connection(&self.dbus_manager, &self.path, settings, options)
+    }
+
     pub fn get_ip4_config(&self) -> Result<IP4Config> {
-        self.dbus_manager.get_ip4_config(&self.ip4config_path)
+        self.dbus_manager.get_ip4_config(&self.path)
+    }
+
+    /// Reads the options of the device's DHCP lease, which are empty without a lease.
+    pub fn get_dhcp4_config(&self) -> Result<HashMap<String, String>> {
+        self.dbus_manager.get_dhcp4_config(&self.path)
     }
 
     pub fn get_ip4config_gateway(&self) -> Result<String> {
-        self.dbus_manager.get_ip4config_gateway(&self.ip4config_path)
+        self.dbus_manager.get_ip4config_gateway(&self.path)
+    }
+
+    /// Makes NetworkManager update the traffic counters every `refresh_ms` milliseconds, or
+    /// stop updating them with `0`.
+    pub fn enable_statistics(&self, refresh_ms: u32) -> Result<()> {
+        self.dbus_manager
+            .enable_device_statistics(&self.path, refresh_ms)
+    }
+
+    /// Reads the traffic counters, which only advance while statistics are enabled.
+    pub fn get_statistics(&self) -> Result<Stats> {
+        self.dbus_manager.get_device_statistics(&self.path)
     }
 }
 
@@ -156,6 +288,8 @@ impl PathGetter for Device {
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
 pub enum DeviceType {
     Unknown,
     Ethernet,
@@ -180,6 +314,15 @@ pub enum DeviceType {
     Veth,
     Macsec,
     Dummy,
+    Ppp,
+    OvsInterface,
+    OvsPort,
+    OvsBridge,
+    Wpan,
+    SixLowpan,
+    WireGuard,
+    WiFiP2p,
+    Vrf,
 }
 
 impl From<i64> for DeviceType {
@@ -208,6 +351,15 @@ impl From<i64> for DeviceType {
             20 => DeviceType::Veth,
             21 => DeviceType::Macsec,
             22 => DeviceType::Dummy,
+            23 => DeviceType::Ppp,
+            24 => DeviceType::OvsInterface,
+            25 => DeviceType::OvsPort,
+            26 => DeviceType::OvsBridge,
+            27 => DeviceType::Wpan,
+            28 => DeviceType::SixLowpan,
+            29 => DeviceType::WireGuard,
+            30 => DeviceType::WiFiP2p,
+            31 => DeviceType::Vrf,
             _ => {
                 warn!("Undefined device type: {}", device_type);
                 DeviceType::Unknown
@@ -216,7 +368,109 @@ impl From<i64> for DeviceType {
     }
 }
 
+const DEVICE_TYPES: &[DeviceType] = &[
+    DeviceType::Unknown,
+    DeviceType::Ethernet,
+    DeviceType::WiFi,
+    DeviceType::Unused1,
+    DeviceType::Unused2,
+    DeviceType::Bt,
+    DeviceType::OlpcMesh,
+    DeviceType::Wimax,
+    DeviceType::Modem,
+    DeviceType::Infiniband,
+    DeviceType::Bond,
+    DeviceType::Vlan,
+    DeviceType::Adsl,
+    DeviceType::Bridge,
+    DeviceType::Generic,
+    DeviceType::Team,
+    DeviceType::Tun,
+    DeviceType::IpTunnel,
+    DeviceType::Macvlan,
+    DeviceType::Vxlan,
+    DeviceType::Veth,
+    DeviceType::Macsec,
+    DeviceType::Dummy,
+    DeviceType::Ppp,
+    DeviceType::OvsInterface,
+    DeviceType::OvsPort,
+    DeviceType::OvsBridge,
+    DeviceType::Wpan,
+    DeviceType::SixLowpan,
+    DeviceType::WireGuard,
+    DeviceType::WiFiP2p,
+    DeviceType::Vrf,
+];
+
+impl DeviceType {
+    fn as_str(&self) -> &'static str {
+        match *self {
+            DeviceType::Unknown => "unknown",
+            DeviceType::Ethernet => "ethernet",
+            DeviceType::WiFi => "wifi",
+            DeviceType::Unused1 => "unused1",
+            DeviceType::Unused2 => "unused2",
+            DeviceType::Bt => "bt",
+            DeviceType::OlpcMesh => "olpcmesh",
+            DeviceType::Wimax => "wimax",
+            DeviceType::Modem => "modem",
+            DeviceType::Infiniband => "infiniband",
+            DeviceType::Bond => "bond",
+            DeviceType::Vlan => "vlan",
+            DeviceType::Adsl => "adsl",
+            DeviceType::Bridge => "bridge",
+            DeviceType::Generic => "generic",
+            DeviceType::Team => "team",
+            DeviceType::Tun => "tun",
+            DeviceType::IpTunnel => "iptunnel",
+            DeviceType::Macvlan => "macvlan",
+            DeviceType::Vxlan => "vxlan",
+            DeviceType::Veth => "veth",
+            DeviceType::Macsec => "macsec",
+            DeviceType::Dummy => "dummy",
+            DeviceType::Ppp => "ppp",
+            DeviceType::OvsInterface => "ovsinterface",
+            DeviceType::OvsPort => "ovsport",
+            DeviceType::OvsBridge => "ovsbridge",
+            DeviceType::Wpan => "wpan",
+            DeviceType::SixLowpan => "sixlowpan",
+            DeviceType::WireGuard => "wireguard",
+            DeviceType::WiFiP2p => "wifip2p",
+            DeviceType::Vrf => "vrf",
+        }
+    }
+}
+
+/// Formats the lowercase variant name, e.g. `wifi`, as also parsed by `FromStr` regardless of
+/// case.
+impl fmt::Display for DeviceType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for DeviceType {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        DEVICE_TYPES
+            .iter()
+            .find(|variant| variant.as_str().eq_ignore_ascii_case(value))
+            .cloned()
+            .ok_or_else(|| ErrorKind::Parse(format!("Unknown device type: {}", value)).into())
+    }
+}
+
+impl From<u32> for DeviceType {
+    fn from(device_type: u32) -> Self {
+        DeviceType::from(i64::from(device_type))
+    }
+}
+
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
 pub enum DeviceState {
     Unknown,
     Unmanaged,
@@ -257,6 +511,116 @@ impl From<i64> for DeviceState {
     }
 }
 
+const DEVICE_STATES: &[DeviceState] = &[
+    DeviceState::Unknown,
+    DeviceState::Unmanaged,
+    DeviceState::Unavailable,
+    DeviceState::Disconnected,
+    DeviceState::Prepare,
+    DeviceState::Config,
+    DeviceState::NeedAuth,
+    DeviceState::IpConfig,
+    DeviceState::IpCheck,
+    DeviceState::Secondaries,
+    DeviceState::Activated,
+    DeviceState::Deactivating,
+    DeviceState::Failed,
+];
+
+impl DeviceState {
+    fn as_str(&self) -> &'static str {
+        match *self {
+            DeviceState::Unknown => "unknown",
+            DeviceState::Unmanaged => "unmanaged",
+            DeviceState::Unavailable => "unavailable",
+            DeviceState::Disconnected => "disconnected",
+            DeviceState::Prepare => "prepare",
+            DeviceState::Config => "config",
+            DeviceState::NeedAuth => "needauth",
+            DeviceState::IpConfig => "ipconfig",
+            DeviceState::IpCheck => "ipcheck",
+            DeviceState::Secondaries => "secondaries",
+            DeviceState::Activated => "activated",
+            DeviceState::Deactivating => "deactivating",
+            DeviceState::Failed => "failed",
+        }
+    }
+}
+
+impl fmt::Display for DeviceState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for DeviceState {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        DEVICE_STATES
+            .iter()
+            .find(|variant| variant.as_str().eq_ignore_ascii_case(value))
+            .cloned()
+            .ok_or_else(|| ErrorKind::Parse(format!("Unknown device state: {}", value)).into())
+    }
+}
+
+impl From<u32> for DeviceState {
+    fn from(state: u32) -> Self {
+        DeviceState::from(i64::from(state))
+    }
+}
+
+/// Whether traffic over a connection is billed by volume, e.g. on a cellular link.
+///
+/// The guessed values are only reported by devices, from heuristics of NetworkManager; a
+/// connection profile is set to `Yes`, `No` or `Unknown` to let NetworkManager decide.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum Metered {
+    Unknown,
+    Yes,
+    No,
+    GuessYes,
+    GuessNo,
+}
+
+impl Metered {
+    /// The value of the `connection.metered` setting and the device `Metered` property.
+    pub fn as_i32(&self) -> i32 {
+        match *self {
+            Metered::Unknown => 0,
+            Metered::Yes => 1,
+            Metered::No => 2,
+            Metered::GuessYes => 3,
+            Metered::GuessNo => 4,
+        }
+    }
+}
+
+impl From<i64> for Metered {
+    fn from(metered: i64) -> Self {
+        match metered {
+            0 => Metered::Unknown,
+            1 => Metered::Yes,
+            2 => Metered::No,
+            3 => Metered::GuessYes,
+            4 => Metered::GuessNo,
+            _ => {
+                warn!("Undefined metered value: {}", metered);
+                Metered::Unknown
+            },
+        }
+    }
+}
+
+impl From<u32> for Metered {
+    fn from(metered: u32) -> Self {
+        Metered::from(i64::from(metered))
+    }
+}
+
 pub fn get_devices(dbus_manager: &Rc<DBusNetworkManager>) -> Result<Vec<Device>> {
     let device_paths = dbus_manager.get_devices()?;
 
@@ -275,7 +639,7 @@ pub fn get_device_by_interface(
     dbus_manager: &Rc<DBusNetworkManager>,
     interface: &str,
 ) -> Result<Device> {
-    let path = dbus_manager.get_device_by_interface(interface)?;
+    let path = dbus_manager.get_device_by_ip_iface(interface)?;
 
     Device::init(dbus_manager, &path)
 }
@@ -338,10 +702,69 @@ fn wait(device: &Device, target_state: &DeviceState, timeout: u64) -> Result<Dev
 
 #[cfg(test)]
 mod tests {
+    use mock::MockService;
+
     use super::super::NetworkManager;
 
     use super::*;
 
+    #[test]
+    fn test_device_type_from_code() {
+        assert_eq!(DeviceType::from(1_u32), DeviceType::Ethernet);
+        assert_eq!(DeviceType::from(2_u32), DeviceType::WiFi);
+        assert_eq!(DeviceType::from(5_u32), DeviceType::Bt);
+        assert_eq!(DeviceType::from(8_u32), DeviceType::Modem);
+        assert_eq!(DeviceType::from(13_u32), DeviceType::Bridge);
+        assert_eq!(DeviceType::from(29_u32), DeviceType::WireGuard);
+        assert_eq!(DeviceType::from(30_u32), DeviceType::WiFiP2p);
+        assert_eq!(DeviceType::from(31_i64), DeviceType::Vrf);
+    }
+
+    #[test]
+    fn test_device_type_display_from_str() {
+        assert_eq!(DeviceType::WiFi.to_string(), "wifi");
+        assert_eq!("Wifi".parse::<DeviceType>().unwrap(), DeviceType::WiFi);
+        assert_eq!("ethernet".parse::<DeviceType>().unwrap(), DeviceType::Ethernet);
+        assert_eq!("WireGuard".parse::<DeviceType>().unwrap(), DeviceType::WireGuard);
+        assert!("nonsense".parse::<DeviceType>().is_err());
+
+        for device_type in DEVICE_TYPES {
+            let parsed = device_type.to_string().parse::<DeviceType>().unwrap();
+            assert_eq!(parsed, *device_type);
+        }
+    }
+
+    #[test]
+    fn test_device_state_display_from_str() {
+        assert_eq!(DeviceState::NeedAuth.to_string(), "needauth");
+        assert_eq!("Activated".parse::<DeviceState>().unwrap(), DeviceState::Activated);
+        assert!("up".parse::<DeviceState>().is_err());
+    }
+
+    #[test]
+    fn test_device_type_from_unknown_code() {
+        assert_eq!(DeviceType::from(1000_u32), DeviceType::Unknown);
+        assert_eq!(DeviceType::from(-1_i64), DeviceType::Unknown);
+    }
+
+    #[test]
+    fn test_metered_codes() {
+        let values = [
+            Metered::Unknown,
+            Metered::Yes,
+            Metered::No,
+            Metered::GuessYes,
+            Metered::GuessNo,
+        ];
+
+        for (code, metered) in values.iter().enumerate() {
+            assert_eq!(metered.as_i32(), code as i32);
+            assert_eq!(Metered::from(code as u32), *metered);
+        }
+
+        assert_eq!(Metered::from(5_u32), Metered::Unknown);
+    }
+
     #[test]
     fn test_ip4config() {
         let manager = NetworkManager::new();
@@ -395,4 +818,49 @@ mod tests {
             ::std::thread::sleep(::std::time::Duration::from_secs(5));
         }
     }
+
+    #[test]
+    fn test_as_wifi_device() {
+        const SERVICE: &str = "org.freedesktop.NetworkManager.Test.AsWiFiDevice";
+        const WIFI_PATH: &str = "/org/freedesktop/NetworkManager/Devices/1";
+        const ETHERNET_PATH: &str = "/org/freedesktop/NetworkManager/Devices/2";
+
+        let _service = MockService::spawn(SERVICE, |f, _| {
+            let device = |interface: &'static str, device_type: u32| {
+                f.interface("org.freedesktop.NetworkManager.Device", ())
+                    .add_p(f.property::<&str, _>("Interface", ()).on_get(move |i, _| {
+                        i.append(interface);
+                        Ok(())
+                    }))
+                    .add_p(f.property::<u32, _>("DeviceType", ()).on_get(move |i, _| {
+                        i.append(device_type);
+                        Ok(())
+                    }))
+            };
+
+            f.tree(())
+                .add(
+                    f.object_path(WIFI_PATH, ())
+                        .add(device("wlan0", 2))
+                        .add(
+                            f.interface("org.freedesktop.NetworkManager.Device.Wireless", ())
+                                .add_p(f.property::<&str, _>("HwAddress", ()).on_get(|i, _| {
+                                    i.append("A0:B1:C2:D3:E4:F5");
+                                    Ok(())
+                                })),
+                        ),
+                )
+                .add(f.object_path(ETHERNET_PATH, ()).add(device("eth0", 1)))
+        });
+
+        let dbus_manager = Rc::new(DBusNetworkManager::with_mock_service(SERVICE));
+
+        let wifi = Device::init(&dbus_manager, WIFI_PATH).unwrap();
+        assert_eq!(wifi.interface(), "wlan0");
+        assert!(wifi.as_wifi_device().is_some());
+
+        let ethernet = Device::init(&dbus_manager, ETHERNET_PATH).unwrap();
+        assert_eq!(*ethernet.device_type(), DeviceType::Ethernet);
+        assert!(ethernet.as_wifi_device().is_none());
+    }
 }
@@ -1,8 +1,12 @@
+use std::error;
+use std::fmt;
+
 error_chain!{
     foreign_links {
         Ascii(::ascii::AsAsciiStrError);
         Utf8(::std::str::Utf8Error);
         DBus(::dbus::Error);
+        DBusError(DBusError);
     }
 
     errors {
@@ -21,6 +25,31 @@ error_chain!{
             display("{}", info)
         }
 
+        ConnectionActive(path: String) {
+            description("Connection is active")
+            display("Connection {} is active", path)
+        }
+
+        ActivationFailed(path: String, reason: ::connection::ActiveConnectionStateReason) {
+            description("Connection activation failed")
+            display("Connection {} was deactivated: {:?}", path, reason)
+        }
+
+        Settings(info: String) {
+            description("Invalid connection settings")
+            display("{}", info)
+        }
+
+        Timeout(info: String) {
+            description("Timed out")
+            display("{}", info)
+        }
+
+        Parse(info: String) {
+            description("Unknown value")
+            display("{}", info)
+        }
+
         DBusAPI(info: String) {
             description("D-Bus API error")
             display("D-Bus failure: {}", info)
@@ -29,3 +58,219 @@ error_chain!{
         Service
     }
 }
+
+/// A failed D-Bus method call or property access, classified by the D-Bus error name.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DBusError {
+    Timeout(String),
+    ServiceUnknown(String),
+    UnknownMethod(String),
+    WrongType(String),
+    NotAuthorized(String),
+    NotFound(String),
+    /// The device has no active connection to act on.
+    NotActive(String),
+    /// The method call was only logged in dry-run mode, so there is no reply to decode.
+    DryRun(String),
+    Other(String),
+}
+
+impl DBusError {
+    /// Classifies an error reply by its D-Bus error name.
+    pub fn from_name(name: Option<&str>, message: String) -> Self {
+        match name {
+            Some("org.freedesktop.DBus.Error.NoReply")
+            | Some("org.freedesktop.DBus.Error.Timeout")
+            | Some("org.freedesktop.DBus.Error.TimedOut") => DBusError::Timeout(message),
+            Some("org.freedesktop.DBus.Error.ServiceUnknown")
+            | Some("org.freedesktop.DBus.Error.NameHasNoOwner") => {
+                DBusError::ServiceUnknown(message)
+            },
+            Some("org.freedesktop.DBus.Error.UnknownMethod")
+            | Some("org.freedesktop.DBus.Error.UnknownInterface")
+            | Some("org.freedesktop.DBus.Error.UnknownProperty") => {
+                DBusError::UnknownMethod(message)
+            },
+            Some("org.freedesktop.DBus.Error.InvalidArgs")
+            | Some("org.freedesktop.DBus.Error.InvalidSignature") => DBusError::WrongType(message),
+            Some("org.freedesktop.DBus.Error.AccessDenied")
+            | Some("org.freedesktop.DBus.Error.AuthFailed")
+            | Some("org.freedesktop.DBus.Error.InteractiveAuthorizationRequired") => {
+                DBusError::NotAuthorized(message)
+            },
+            Some(name)
+                if name.ends_with(".PermissionDenied") || name.ends_with(".NotAuthorized") =>
+            {
+                DBusError::NotAuthorized(message)
+            },
+            Some("org.freedesktop.DBus.Error.UnknownObject")
+            | Some("org.freedesktop.NetworkManager.UnknownDevice")
+            | Some("org.freedesktop.NetworkManager.UnknownConnection")
+            | Some("org.freedesktop.NetworkManager.Settings.InvalidConnection") => {
+                DBusError::NotFound(message)
+            },
+            Some("org.freedesktop.NetworkManager.Device.NotActive") => {
+                DBusError::NotActive(message)
+            },
+            _ => DBusError::Other(message),
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match *self {
+            DBusError::Timeout(ref message)
+            | DBusError::ServiceUnknown(ref message)
+            | DBusError::UnknownMethod(ref message)
+            | DBusError::WrongType(ref message)
+            | DBusError::NotAuthorized(ref message)
+            | DBusError::NotFound(ref message)
+            | DBusError::NotActive(ref message)
+            | DBusError::DryRun(ref message)
+            | DBusError::Other(ref message) => message,
+        }
+    }
+
+    /// Prefixes the error message with `context`, keeping the classification.
+    pub fn with_context(self, context: &str) -> Self {
+        let prefix = |message: String| format!("{}: {}", context, message);
+
+        match self {
+            DBusError::Timeout(message) => DBusError::Timeout(prefix(message)),
+            DBusError::ServiceUnknown(message) => DBusError::ServiceUnknown(prefix(message)),
+            DBusError::UnknownMethod(message) => DBusError::UnknownMethod(prefix(message)),
+            DBusError::WrongType(message) => DBusError::WrongType(prefix(message)),
+            DBusError::NotAuthorized(message) => DBusError::NotAuthorized(prefix(message)),
+            DBusError::NotFound(message) => DBusError::NotFound(prefix(message)),
+            DBusError::NotActive(message) => DBusError::NotActive(prefix(message)),
+            DBusError::DryRun(message) => DBusError::DryRun(prefix(message)),
+            DBusError::Other(message) => DBusError::Other(prefix(message)),
+        }
+    }
+}
+
+impl From<::dbus::Error> for DBusError {
+    fn from(error: ::dbus::Error) -> Self {
+        let details = error.message().unwrap_or("no details");
+
+        let message = match error.name() {
+            Some(name) => format!("[{}] {}", name, details),
+            None => details.to_string(),
+        };
+
+        DBusError::from_name(error.name(), message)
+    }
+}
+
+impl fmt::Display for DBusError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "D-Bus failure: {}", self.message())
+    }
+}
+
+impl error::Error for DBusError {
+    fn description(&self) -> &str {
+        match *self {
+            DBusError::Timeout(_) => "D-Bus method call timed out",
+            DBusError::ServiceUnknown(_) => "D-Bus service unknown",
+            DBusError::UnknownMethod(_) => "D-Bus method unknown",
+            DBusError::WrongType(_) => "D-Bus wrong type",
+            DBusError::NotAuthorized(_) => "D-Bus not authorized",
+            DBusError::NotFound(_) => "D-Bus object not found",
+            DBusError::NotActive(_) => "Device not active",
+            DBusError::DryRun(_) => "D-Bus method call not sent in dry-run mode",
+            DBusError::Other(_) => "D-Bus API error",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn from_name(name: &str, message: &str) -> DBusError {
+        DBusError::from(::dbus::Error::new_custom(name, message))
+    }
+
+    #[test]
+    fn test_dbus_error_from_name() {
+        let name = "org.freedesktop.DBus.Error.NoReply";
+        assert_eq!(
+            from_name(name, "no reply"),
+            DBusError::Timeout(format!("[{}] no reply", name))
+        );
+
+        let name = "org.freedesktop.DBus.Error.ServiceUnknown";
+        assert_eq!(
+            from_name(name, "not provided"),
+            DBusError::ServiceUnknown(format!("[{}] not provided", name))
+        );
+
+        let name = "org.freedesktop.DBus.Error.UnknownMethod";
+        assert_eq!(
+            from_name(name, "none"),
+            DBusError::UnknownMethod(format!("[{}] none", name))
+        );
+
+        let name = "org.freedesktop.NetworkManager.PermissionDenied";
+        assert_eq!(
+            from_name(name, "denied"),
+            DBusError::NotAuthorized(format!("[{}] denied", name))
+        );
+
+        let name = "org.freedesktop.NetworkManager.Settings.Connection.NotAuthorized";
+        assert_eq!(
+            from_name(name, "not authorized"),
+            DBusError::NotAuthorized(format!("[{}] not authorized", name))
+        );
+
+        let name = "org.freedesktop.NetworkManager.UnknownDevice";
+        assert_eq!(
+            from_name(name, "no device"),
+            DBusError::NotFound(format!("[{}] no device", name))
+        );
+
+        let name = "org.freedesktop.NetworkManager.Settings.InvalidConnection";
+        assert_eq!(
+            from_name(name, "no connection"),
+            DBusError::NotFound(format!("[{}] no connection", name))
+        );
+
+        let name = "org.freedesktop.NetworkManager.Device.NotActive";
+        assert_eq!(
+            from_name(name, "not active"),
+            DBusError::NotActive(format!("[{}] not active", name))
+        );
+
+        let name = "org.freedesktop.NetworkManager.Failed";
+        assert_eq!(
+            from_name(name, "failed"),
+            DBusError::Other(format!("[{}] failed", name))
+        );
+    }
+
+    #[test]
+    fn test_dbus_error_display() {
+        let error = DBusError::WrongType("Wrong response type".into());
+        assert_eq!(format!("{}", error), "D-Bus failure: Wrong response type");
+
+        let error = error.with_context("Get Foo::Bar property failed on /");
+        assert_eq!(
+            format!("{}", error),
+            "D-Bus failure: Get Foo::Bar property failed on /: Wrong response type"
+        );
+    }
+
+    #[test]
+    fn test_dbus_error_keeps_name() {
+        let error = from_name(
+            "org.freedesktop.NetworkManager.InvalidProperty",
+            "invalid property 'ssid'",
+        ).with_context("AddConnection failed");
+
+        assert_eq!(
+            format!("{}", error),
+            "D-Bus failure: AddConnection failed: \
+             [org.freedesktop.NetworkManager.InvalidProperty] invalid property 'ssid'"
+        );
+    }
+}
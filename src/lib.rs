@@ -16,6 +16,16 @@ extern crate dbus;
 
 extern crate ascii;
 
+#[cfg(feature = "serde")]
+#[macro_use]
+extern crate serde;
+
+#[cfg(all(test, feature = "serde"))]
+extern crate serde_json;
+
+#[cfg(feature = "tracing")]
+extern crate tracing;
+
 pub mod errors;
 
 mod dbus_nm;
@@ -26,9 +36,24 @@ mod connection;
 mod device;
 mod wifi;
 mod ssid;
-
-pub use manager::{Connectivity, NetworkManager};
-pub use connection::{Connection, ConnectionSettings, ConnectionState};
-pub use device::{Device, DeviceState, DeviceType};
-pub use wifi::{AccessPoint, AccessPointCredentials, Security};
+mod settings;
+#[cfg(feature = "serde")]
+mod serialization;
+
+#[cfg(test)]
+mod mock;
+
+pub use manager::{CheckpointFlags, Connectivity, DnsEntry, DnsManagerInfo, NetworkManager,
+                  NetworkManagerState, NetworkManagerStatus, StateChanges};
+pub use connection::{ActiveConnection, ActiveConnectionStateReason, Connection, ConnectionSettings,
+                     ConnectionState};
+pub use device::{Device, DeviceState, DeviceType, IP4Address, IP4Config, Metered, Stats};
+pub use wifi::{AccessPoint, AccessPointCredentials, ApMode, Security, WirelessCapabilities};
 pub use service::ServiceState;
+pub use dbus::BusType;
+pub use dbus_api::{Backoff, RetryDecision, SignalRouter};
+pub use dbus_nm::{SettingsMap, VariantMap};
+pub use settings::{BondConfig, BridgeConfig, ConnectionBuilder, EapMethod, Enterprise8021x,
+                   EthernetConfig, GsmConfig, Ipv4Config, Ipv4Method, Ipv6AddrGenMode, Ipv6Config,
+                   Ipv6Method, Route, SlaveType, VlanConfig, VlanFlags, WireGuardConfig,
+                   WireGuardPeer, WirelessConfig, WirelessPowersave, WirelessSecurity};
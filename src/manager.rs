@@ -1,10 +1,22 @@
+use std::collections::HashMap;
+use std::fmt;
 use std::rc::Rc;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use dbus::BusType;
+use dbus::Connection as DBusConnection;
 
 use errors::*;
-use dbus_nm::DBusNetworkManager;
+use dbus_api::{Backoff, RetryDecision, SignalRouter, Signals};
+use dbus_nm::{decode_state_changed, DBusNetworkManager, SettingsMap};
 
-use connection::{get_active_connections, get_connections, Connection};
-use device::{get_device_by_interface, get_devices, Device};
+use connection::{add_connection, add_vpn_connection, delete_connection_by_uuid,
+                 get_active_connections, get_connection_by_uuid, get_connections,
+                 list_active_connections, ActiveConnection, Connection, ConnectionState};
+use device::{get_device_by_interface, get_devices, Device, PathGetter};
+use wifi::{access_point_from_path, AccessPoint, Security};
+use settings::{BondConfig, BridgeConfig, VlanConfig, WireGuardConfig};
 use service::{get_service_state, start_service, stop_service, ServiceState};
 
 pub struct NetworkManager {
@@ -12,18 +24,243 @@ pub struct NetworkManager {
 }
 
 impl NetworkManager {
+    /// Connects to Network Manager over the system bus.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the system bus is not available; use `try_new` to handle that case.
     pub fn new() -> Self {
-        NetworkManager {
-            dbus_manager: Rc::new(DBusNetworkManager::new(None)),
-        }
+        NetworkManager::try_new().unwrap()
     }
 
+    /// Connects to Network Manager over the system bus, failing if the bus is not available.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use network_manager::NetworkManager;
+    /// match NetworkManager::try_new() {
+    ///     Ok(manager) => println!("{:?}", manager.get_state()),
+    ///     Err(e) => eprintln!("Network Manager unavailable: {}", e),
+    /// }
+    /// ```
+    pub fn try_new() -> Result<Self> {
+        Ok(NetworkManager {
+            dbus_manager: Rc::new(DBusNetworkManager::new(None)?),
+        })
+    }
+
+    /// Connects to Network Manager over the system bus, waiting `timeout` seconds for method
+    /// call replies.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the system bus is not available; use `try_with_method_timeout` to handle that
+    /// case.
     pub fn with_method_timeout(timeout: u64) -> Self {
+        NetworkManager::try_with_method_timeout(timeout).unwrap()
+    }
+
+    /// Like `with_method_timeout`, but fails if the system bus is not available.
+    pub fn try_with_method_timeout(timeout: u64) -> Result<Self> {
+        Ok(NetworkManager {
+            dbus_manager: Rc::new(DBusNetworkManager::new(Some(timeout))?),
+        })
+    }
+
+    /// Retries method calls that fail with a retryable error at most `max_retries` times,
+    /// sleeping according to `backoff` between attempts.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    /// use network_manager::{Backoff, NetworkManager};
+    /// let backoff = Backoff::Exponential {
+    ///     initial: Duration::from_millis(500),
+    ///     max: Duration::from_secs(8),
+    /// };
+    /// let manager = NetworkManager::with_retries(30, backoff).unwrap();
+    /// ```
+    pub fn with_retries(max_retries: usize, backoff: Backoff) -> Result<Self> {
+        Ok(NetworkManager {
+            dbus_manager: Rc::new(DBusNetworkManager::with_retries(max_retries, backoff)?),
+        })
+    }
+
+    /// Like `with_retries`, but lets `predicate` decide whether a failed method call is retried.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// extern crate dbus;
+    /// extern crate network_manager;
+    /// # fn main() {
+    /// use std::time::Duration;
+    /// use network_manager::{Backoff, NetworkManager, RetryDecision};
+    /// let manager = NetworkManager::with_retry_predicate(5, Backoff::default(), |error| {
+    ///     match error.name() {
+    ///         Some("org.freedesktop.DBus.Error.NoReply") => RetryDecision::Retry,
+    ///         Some("org.freedesktop.NetworkManager.Settings.Connection.ObjectPathInUse") => {
+    ///             RetryDecision::RetryAfter(Duration::from_millis(200))
+    ///         },
+    ///         _ => RetryDecision::Fail,
+    ///     }
+    /// }).unwrap();
+    /// # }
+    /// ```
+    pub fn with_retry_predicate<F>(
+        max_retries: usize,
+        backoff: Backoff,
+        predicate: F,
+    ) -> Result<Self>
+    where
+        F: Fn(&::dbus::Error) -> RetryDecision + 'static,
+    {
+        let mut dbus_manager = DBusNetworkManager::with_retries(max_retries, backoff)?;
+        dbus_manager.set_retry_predicate(predicate);
+
+        Ok(NetworkManager {
+            dbus_manager: Rc::new(dbus_manager),
+        })
+    }
+
+    /// Connects to Network Manager over the given D-Bus bus instead of the system one.
+    ///
+    /// Useful for running against a mock Network Manager service on the session bus.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use network_manager::{BusType, NetworkManager};
+    /// let manager = NetworkManager::with_bus_type(BusType::Session).unwrap();
+    /// let devices = manager.get_devices().unwrap();
+    /// println!("{:?}", devices);
+    /// ```
+    pub fn with_bus_type(bus_type: BusType) -> Result<Self> {
+        Ok(NetworkManager {
+            dbus_manager: Rc::new(DBusNetworkManager::with_bus_type(bus_type, None)?),
+        })
+    }
+
+    /// Uses an already open D-Bus connection instead of opening a new one.
+    ///
+    /// The connection can be shared with other managers or with the rest of the application.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// extern crate dbus;
+    /// extern crate network_manager;
+    /// # fn main() {
+    /// use std::rc::Rc;
+    /// use network_manager::{BusType, NetworkManager};
+    /// let connection = Rc::new(dbus::Connection::get_private(BusType::System).unwrap());
+    /// let manager = NetworkManager::from_connection(connection.clone());
+    /// let other_manager = NetworkManager::from_connection(connection);
+    /// # }
+    /// ```
+    pub fn from_connection(connection: Rc<DBusConnection>) -> Self {
         NetworkManager {
-            dbus_manager: Rc::new(DBusNetworkManager::new(Some(timeout))),
+            dbus_manager: Rc::new(DBusNetworkManager::from_connection(connection, None)),
         }
     }
 
+    /// Borrows the D-Bus connection for calls and match rules not wrapped by this crate, without
+    /// opening a second socket.
+    ///
+    /// The connection cannot outlive the manager. To hold on to it independently, open it
+    /// yourself and hand a clone of the `Rc` to `from_connection`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// extern crate dbus;
+    /// extern crate network_manager;
+    /// # fn main() {
+    /// use network_manager::NetworkManager;
+    /// let manager = NetworkManager::new();
+    /// let ping = dbus::Message::new_method_call(
+    ///     "org.freedesktop.NetworkManager",
+    ///     "/org/freedesktop/NetworkManager",
+    ///     "org.freedesktop.DBus.Peer",
+    ///     "Ping",
+    /// ).unwrap();
+    /// manager.connection().send_with_reply_and_block(ping, 2000).unwrap();
+    /// # }
+    /// ```
+    pub fn connection(&self) -> &DBusConnection {
+        self.dbus_manager.connection()
+    }
+
+    /// Creates a router that serves several signal subscriptions from one dispatch loop.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    /// use network_manager::NetworkManager;
+    /// let manager = NetworkManager::new();
+    /// let mut router = manager.signal_router();
+    /// router
+    ///     .register(
+    ///         "/org/freedesktop/NetworkManager",
+    ///         "org.freedesktop.NetworkManager",
+    ///         "StateChanged",
+    ///         |signal| println!("{:?}", signal.get1::<u32>()),
+    ///     )
+    ///     .unwrap();
+    /// router.run_timeout(Duration::from_secs(10));
+    /// ```
+    pub fn signal_router(&self) -> SignalRouter {
+        self.dbus_manager.signal_router()
+    }
+
+    /// Logs the method calls and property writes that would be sent to Network Manager instead
+    /// of sending them, for checking what a provisioning script does.
+    ///
+    /// Reads, e.g. of devices or connection settings, are still sent. Calls that change state
+    /// and return a result, e.g. `add_connection`, fail with `DBusError::DryRun` while enabled.
+    pub fn set_dry_run(&self, dry_run: bool) {
+        self.dbus_manager.set_dry_run(dry_run)
+    }
+
+    /// Connects to Network Manager over the system bus and enables the property cache, see
+    /// `enable_property_cache`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    /// use network_manager::NetworkManager;
+    /// let manager = NetworkManager::with_property_cache(Duration::from_millis(500)).unwrap();
+    /// ```
+    pub fn with_property_cache(ttl: Duration) -> Result<Self> {
+        let manager = NetworkManager::try_new()?;
+        manager.enable_property_cache(ttl)?;
+
+        Ok(manager)
+    }
+
+    /// Caches device and access point property reads for `ttl`, for callers that poll the same
+    /// properties many times per second.
+    ///
+    /// Network Manager reports property changes with `PropertiesChanged` signals, which drop
+    /// the changed values from the cache, so e.g. a device state read after `Device::connect`
+    /// is not served stale. Calling this again replaces the cache with an empty one.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    /// use network_manager::{Backoff, NetworkManager};
+    /// let manager = NetworkManager::with_retries(3, Backoff::default()).unwrap();
+    /// manager.enable_property_cache(Duration::from_millis(500)).unwrap();
+    /// ```
+    pub fn enable_property_cache(&self, ttl: Duration) -> Result<()> {
+        self.dbus_manager.enable_property_cache(ttl)
+    }
+
     /// Starts the Network Manager service.
     ///
     /// # Examples
@@ -77,10 +314,116 @@ impl NetworkManager {
         get_connections(&self.dbus_manager)
     }
 
+    /// Looks up a saved connection profile by its UUID.
+    ///
+    /// Fails with a `DBusError::NotFound` error if there is no such profile.
+    pub fn get_connection_by_uuid(&self, uuid: &str) -> Result<Connection> {
+        get_connection_by_uuid(&self.dbus_manager, uuid)
+    }
+
+    /// Adds a connection profile without activating it.
+    ///
+    /// The profile is saved to disk if `save` is set and otherwise only kept in memory until
+    /// NetworkManager restarts.
+    pub fn add_connection(&self, settings: &SettingsMap, save: bool) -> Result<Connection> {
+        add_connection(&self.dbus_manager, settings, save)
+    }
+
+    /// Saves a WireGuard connection profile without activating it.
+    pub fn create_wireguard_connection(&self, config: &WireGuardConfig) -> Result<Connection> {
+        add_connection(&self.dbus_manager, &config.to_settings()?, true)
+    }
+
+    /// Saves a bond connection profile without activating it.
+    ///
+    /// See `Device::attach_to_master` for enslaving devices to it.
+    pub fn create_bond_connection(&self, config: &BondConfig) -> Result<Connection> {
+        add_connection(&self.dbus_manager, &config.to_settings()?, true)
+    }
+
+    /// Saves a bridge connection profile without activating it.
+    pub fn create_bridge_connection(&self, config: &BridgeConfig) -> Result<Connection> {
+        add_connection(&self.dbus_manager, &config.to_settings()?, true)
+    }
+
+    /// Saves a VLAN connection profile without activating it.
+    pub fn create_vlan_connection(&self, config: &VlanConfig) -> Result<Connection> {
+        add_connection(&self.dbus_manager, &config.to_settings()?, true)
+    }
+
+    /// Saves a VPN profile handled by the `service_type` plugin without activating it.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::collections::HashMap;
+    /// use network_manager::NetworkManager;
+    ///
+    /// let mut data = HashMap::new();
+    /// data.insert("remote".to_string(), "vpn.example.com".to_string());
+    /// data.insert("connection-type".to_string(), "password".to_string());
+    /// data.insert("username".to_string(), "alice".to_string());
+    ///
+    /// let mut secrets = HashMap::new();
+    /// secrets.insert("password".to_string(), "hunter22".to_string());
+    ///
+    /// let manager = NetworkManager::new();
+    /// manager
+    ///     .add_vpn_connection("office", "org.freedesktop.NetworkManager.openvpn", data, secrets)
+    ///     .unwrap();
+    /// ```
+    pub fn add_vpn_connection(
+        &self,
+        id: &str,
+        service_type: &str,
+        data: HashMap<String, String>,
+        secrets: HashMap<String, String>,
+    ) -> Result<Connection> {
+        add_vpn_connection(&self.dbus_manager, id, service_type, data, secrets)
+    }
+
+    /// Deletes the saved connection profile with the given UUID, deactivating it first if it
+    /// is active.
+    pub fn delete_connection_by_uuid(&self, uuid: &str) -> Result<()> {
+        delete_connection_by_uuid(&self.dbus_manager, uuid)
+    }
+
     pub fn get_active_connections(&self) -> Result<Vec<Connection>> {
         get_active_connections(&self.dbus_manager)
     }
 
+    /// Describes the connections NetworkManager is currently activating, using or tearing down.
+    pub fn list_active_connections(&self) -> Result<Vec<ActiveConnection>> {
+        list_active_connections(&self.dbus_manager)
+    }
+
+    /// Describes the active connection carrying the default route, if any.
+    pub fn get_primary_connection(&self) -> Result<Option<ActiveConnection>> {
+        match self.dbus_manager.get_primary_connection()? {
+            Some(path) => Ok(Some(self.dbus_manager.get_active_connection(&path)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// The type of the primary connection, e.g. `802-3-ethernet`, if any.
+    pub fn get_primary_connection_type(&self) -> Result<Option<String>> {
+        self.dbus_manager.get_primary_connection_type()
+    }
+
+    /// Waits until the active connection at `active_path` reaches `target`.
+    ///
+    /// Fails with `ErrorKind::ActivationFailed` if the connection is deactivated on the way and
+    /// with `ErrorKind::Timeout` if `target` is not reached within `timeout`.
+    pub fn wait_for_active_connection_state(
+        &self,
+        active_path: &str,
+        target: ConnectionState,
+        timeout: Duration,
+    ) -> Result<ConnectionState> {
+        self.dbus_manager
+            .wait_for_active_connection_state(active_path, target, timeout)
+    }
+
     /// Get a list of Network Manager devices.
     ///
     /// # Examples
@@ -99,14 +442,128 @@ impl NetworkManager {
         get_device_by_interface(&self.dbus_manager, interface)
     }
 
+    /// Reads the security supported by the access point at `path`, e.g. the path of
+    /// `AccessPoint::path` or of a device's active access point.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use network_manager::{NetworkManager, Security};
+    /// let manager = NetworkManager::new();
+    /// let security = manager
+    ///     .get_ap_security("/org/freedesktop/NetworkManager/AccessPoint/1")
+    ///     .unwrap();
+    /// println!("requires a password: {}", security != Security::NONE);
+    /// ```
+    pub fn get_ap_security(&self, path: &str) -> Result<Security> {
+        self.dbus_manager.get_ap_security(path)
+    }
+
+    /// Reads the access point at `path`, see `AccessPoint::from_path`.
+    pub fn get_access_point(&self, path: &str) -> Result<AccessPoint> {
+        access_point_from_path(&self.dbus_manager, path)
+    }
+
     pub fn get_state(&self) -> Result<NetworkManagerState> {
         self.dbus_manager.get_state()
     }
 
+    /// Subscribes to Network Manager state transitions.
+    ///
+    /// The D-Bus connection is not shareable between threads, so the subscription has to be
+    /// consumed on the thread that owns the `NetworkManager`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use network_manager::NetworkManager;
+    /// let manager = NetworkManager::new();
+    /// let mut changes = manager.state_changes().unwrap();
+    /// while let Some(state) = changes.next_state() {
+    ///     println!("{:?}", state);
+    /// }
+    /// ```
+    pub fn state_changes(&self) -> Result<StateChanges> {
+        Ok(StateChanges {
+            signals: self.dbus_manager.state_changed()?,
+        })
+    }
+
+    /// Returns the version of the running daemon, e.g. `1.42.4`.
+    pub fn get_version(&self) -> Result<String> {
+        self.dbus_manager.get_version()
+    }
+
+    /// Returns the major, minor and micro version of the running daemon.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use network_manager::NetworkManager;
+    /// let manager = NetworkManager::new();
+    /// if manager.version_tuple().unwrap() >= (1, 16, 0) {
+    ///     println!("AddAndActivateConnection2 is supported");
+    /// }
+    /// ```
+    pub fn version_tuple(&self) -> Result<(u32, u32, u32)> {
+        let version = self.get_version()?;
+
+        match parse_version(&version) {
+            Some(tuple) => Ok(tuple),
+            None => bail!(ErrorKind::NetworkManager(format!(
+                "Unable to parse version '{}'",
+                version
+            ))),
+        }
+    }
+
+    /// Reads the resolver configuration of all connections as passed to the DNS backend.
+    pub fn get_dns_configuration(&self) -> Result<Vec<DnsEntry>> {
+        self.dbus_manager.get_dns_configuration()
+    }
+
+    /// Reads which resolver plugin and `resolv.conf` manager NetworkManager uses.
+    pub fn get_dns_manager_info(&self) -> Result<DnsManagerInfo> {
+        self.dbus_manager.get_dns_manager_info()
+    }
+
+    /// Reads the global switches, state and connectivity in a single round-trip.
+    pub fn get_status(&self) -> Result<NetworkManagerStatus> {
+        self.dbus_manager.get_status()
+    }
+
+    /// Returns the connectivity found by NetworkManager's last periodic check.
     pub fn get_connectivity(&self) -> Result<Connectivity> {
+        self.dbus_manager.get_connectivity()
+    }
+
+    /// Checks connectivity right away, e.g. to detect a captive portal after connecting.
+    pub fn check_connectivity(&self) -> Result<Connectivity> {
         self.dbus_manager.check_connectivity()
     }
 
+    /// Waits until connectivity is at least `target`, failing with `ErrorKind::Timeout` if it
+    /// is not within `timeout`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    /// use network_manager::{Connectivity, NetworkManager};
+    ///
+    /// let manager = NetworkManager::new();
+    /// manager
+    ///     .wait_for_connectivity(Connectivity::Full, Duration::from_secs(30))
+    ///     .unwrap();
+    /// ```
+    pub fn wait_for_connectivity(
+        &self,
+        target: Connectivity,
+        timeout: Duration,
+    ) -> Result<Connectivity> {
+        self.dbus_manager.wait_for_connectivity(target, timeout)
+    }
+
     pub fn is_networking_enabled(&self) -> Result<bool> {
         self.dbus_manager.is_networking_enabled()
     }
@@ -114,6 +571,89 @@ impl NetworkManager {
     pub fn is_wireless_enabled(&self) -> Result<bool> {
         self.dbus_manager.is_wireless_enabled()
     }
+
+    /// Turns all WiFi radios off or back on, like `nmcli radio wifi off|on`.
+    pub fn set_wireless_enabled(&self, enabled: bool) -> Result<()> {
+        self.dbus_manager.set_wireless_enabled(enabled)
+    }
+
+    pub fn is_wwan_enabled(&self) -> Result<bool> {
+        self.dbus_manager.is_wwan_enabled()
+    }
+
+    /// Turns all mobile broadband radios off or back on, like `nmcli radio wwan off|on`.
+    pub fn set_wwan_enabled(&self, enabled: bool) -> Result<()> {
+        self.dbus_manager.set_wwan_enabled(enabled)
+    }
+
+    /// Takes all of networking down or brings it back up, like `nmcli networking off|on`.
+    pub fn enable_networking(&self, enable: bool) -> Result<()> {
+        self.dbus_manager.enable(enable)
+    }
+
+    /// Snapshots the configuration of `devices`, or of all devices if empty, returning the
+    /// checkpoint path.
+    ///
+    /// NetworkManager rolls back to the checkpoint by itself after `rollback_timeout` seconds
+    /// unless it is destroyed first, which recovers remote access lost through a bad change.
+    /// A timeout of `0` disables the automatic rollback.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use network_manager::{CheckpointFlags, NetworkManager};
+    ///
+    /// let manager = NetworkManager::new();
+    /// let checkpoint = manager
+    ///     .create_checkpoint(&[], 60, CheckpointFlags::NONE)
+    ///     .unwrap();
+    ///
+    /// // Reconfigure, then keep the changes if the link survived
+    /// manager.destroy_checkpoint(&checkpoint).unwrap();
+    /// ```
+    pub fn create_checkpoint(
+        &self,
+        devices: &[Device],
+        rollback_timeout: u32,
+        flags: CheckpointFlags,
+    ) -> Result<String> {
+        let paths = devices.iter().map(|device| device.path()).collect::<Vec<_>>();
+
+        self.dbus_manager
+            .create_checkpoint(&paths, rollback_timeout, flags)
+    }
+
+    /// Keeps the current configuration by discarding the checkpoint.
+    pub fn destroy_checkpoint(&self, checkpoint: &str) -> Result<()> {
+        self.dbus_manager.destroy_checkpoint(checkpoint)
+    }
+
+    /// Resets the automatic rollback of the checkpoint to happen `add_timeout` seconds from now,
+    /// for reconfigurations taking longer than anticipated. A timeout of `0` disables it.
+    pub fn checkpoint_adjust_rollback_timeout(
+        &self,
+        checkpoint: &str,
+        add_timeout: u32,
+    ) -> Result<()> {
+        self.dbus_manager
+            .checkpoint_adjust_rollback_timeout(checkpoint, add_timeout)
+    }
+
+    /// Restores the configuration of the checkpoint and destroys it.
+    ///
+    /// Returns the result per device path: `0` on success, `1` if the device is gone, `2` if it
+    /// is no longer managed and `3` on other failures.
+    pub fn rollback_checkpoint(&self, checkpoint: &str) -> Result<HashMap<String, u32>> {
+        self.dbus_manager.rollback_checkpoint(checkpoint)
+    }
+
+    /// Reloads NetworkManager's configuration without restarting the daemon.
+    ///
+    /// `flags` selects what to reload, e.g. `0x01` for `NetworkManager.conf`, `0x02` for the DNS
+    /// configuration and `0x04` for the DNS plugin; `0` reloads everything.
+    pub fn reload(&self, flags: u32) -> Result<()> {
+        self.dbus_manager.reload(flags)
+    }
 }
 
 impl Default for NetworkManager {
@@ -123,6 +663,8 @@ impl Default for NetworkManager {
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
 pub enum NetworkManagerState {
     Unknown,
     Asleep,
@@ -153,7 +695,73 @@ impl From<u32> for NetworkManagerState {
     }
 }
 
-#[derive(Clone, Debug, PartialEq)]
+/// A subscription to the Network Manager `StateChanged` signal.
+pub struct StateChanges<'a> {
+    signals: Signals<'a>,
+}
+
+impl<'a> StateChanges<'a> {
+    /// Blocks until Network Manager changes its state.
+    pub fn next_state(&mut self) -> Option<NetworkManagerState> {
+        self.next()
+    }
+
+    /// Waits at most `timeout` for Network Manager to change its state.
+    pub fn next_state_timeout(&mut self, timeout: Duration) -> Option<NetworkManagerState> {
+        let deadline = Instant::now() + timeout;
+
+        loop {
+            let now = Instant::now();
+            if now >= deadline {
+                return None;
+            }
+
+            let signal = self.signals.next_timeout(deadline - now)?;
+
+            if let Some(state) = decode_state_changed(&signal) {
+                return Some(state);
+            }
+        }
+    }
+}
+
+impl<'a> Iterator for StateChanges<'a> {
+    type Item = NetworkManagerState;
+
+    fn next(&mut self) -> Option<NetworkManagerState> {
+        loop {
+            let signal = self.signals.next()?;
+
+            if let Some(state) = decode_state_changed(&signal) {
+                return Some(state);
+            }
+        }
+    }
+}
+
+/// Parses the leading `major.minor.micro` of a version, ignoring suffixes like `-1`.
+///
+/// A missing micro version is read as `0`.
+pub fn parse_version(version: &str) -> Option<(u32, u32, u32)> {
+    let mut parts = version.split('.').map(|part| {
+        let digits: String = part.chars().take_while(|c| c.is_numeric()).collect();
+        digits.parse::<u32>().ok()
+    });
+
+    let major = parts.next()??;
+    let minor = parts.next()??;
+    let micro = match parts.next() {
+        Some(micro) => micro?,
+        None => 0,
+    };
+
+    Some((major, minor, micro))
+}
+
+/// The connectivity levels, ordered from none to full connectivity.
+#[derive(Clone, Debug, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
 pub enum Connectivity {
     Unknown,
     None,
@@ -178,10 +786,155 @@ impl From<u32> for Connectivity {
     }
 }
 
+const CONNECTIVITIES: &[Connectivity] = &[
+    Connectivity::Unknown,
+    Connectivity::None,
+    Connectivity::Portal,
+    Connectivity::Limited,
+    Connectivity::Full,
+];
+
+impl Connectivity {
+    fn as_str(&self) -> &'static str {
+        match *self {
+            Connectivity::Unknown => "unknown",
+            Connectivity::None => "none",
+            Connectivity::Portal => "portal",
+            Connectivity::Limited => "limited",
+            Connectivity::Full => "full",
+        }
+    }
+}
+
+impl fmt::Display for Connectivity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Connectivity {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        CONNECTIVITIES
+            .iter()
+            .find(|variant| variant.as_str().eq_ignore_ascii_case(value))
+            .cloned()
+            .ok_or_else(|| ErrorKind::Parse(format!("Unknown connectivity: {}", value)).into())
+    }
+}
+
+bitflags! {
+    /// Options of a checkpoint, see `NetworkManager::create_checkpoint`.
+    pub struct CheckpointFlags: u32 {
+        const NONE                   = 0x0000_0000;
+        // destroy all existing checkpoints first
+        const DESTROY_ALL            = 0x0000_0001;
+        // delete connections added after the checkpoint on rollback
+        const DELETE_NEW_CONNECTIONS = 0x0000_0002;
+        // disconnect devices added after the checkpoint on rollback
+        const DISCONNECT_NEW_DEVICES = 0x0000_0004;
+        // allow checkpoints sharing devices, since NetworkManager 1.20
+        const ALLOW_OVERLAPPING      = 0x0000_0008;
+    }
+}
+
+/// A snapshot of the global switches and state of NetworkManager.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct NetworkManagerStatus {
+    pub networking_enabled: bool,
+    pub wireless_enabled: bool,
+    pub wwan_enabled: bool,
+    /// Whether connectivity is checked periodically; `None` before Network Manager 1.10.
+    pub connectivity_check_enabled: Option<bool>,
+    pub state: NetworkManagerState,
+    pub connectivity: Connectivity,
+}
+
+/// The DNS configuration NetworkManager derived from a single connection.
+#[derive(Default, Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DnsEntry {
+    pub nameservers: Vec<String>,
+    pub domains: Vec<String>,
+    /// The interface of the connection, empty for global entries.
+    pub interface: String,
+    /// Entries with a lower value are preferred.
+    pub priority: i32,
+    /// Whether the entry comes from a VPN connection.
+    pub vpn: bool,
+}
+
+/// Which backends NetworkManager passes the DNS configuration to.
+#[derive(Default, Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DnsManagerInfo {
+    /// The resolver plugin, e.g. `default`, `dnsmasq` or `systemd-resolved`.
+    pub mode: String,
+    /// How `/etc/resolv.conf` is written, e.g. `symlink`, `file` or `resolvconf`.
+    pub rc_manager: String,
+}
+
 #[cfg(test)]
 mod tests {
+    use std::env;
+    use std::process::Command;
+    use std::sync::mpsc;
+    use std::thread;
+
+    use dbus::{Connection as DBusConnection, Message};
+
     use super::*;
 
+    #[test]
+    fn test_state_changes() {
+        let (ready_tx, ready_rx) = mpsc::channel();
+        let (emit_tx, emit_rx) = mpsc::channel::<()>();
+        let (done_tx, done_rx) = mpsc::channel::<()>();
+
+        // Stands in for Network Manager on the session bus and emits a single state change
+        let service = thread::spawn(move || {
+            let connection = DBusConnection::get_private(BusType::Session).unwrap();
+            connection
+                .register_name("org.freedesktop.NetworkManager", 0)
+                .unwrap();
+            ready_tx.send(()).unwrap();
+
+            emit_rx.recv().unwrap();
+            let signal = Message::new_signal(
+                "/org/freedesktop/NetworkManager",
+                "org.freedesktop.NetworkManager",
+                "StateChanged",
+            ).unwrap()
+                .append1(70_u32);
+            connection.send(signal).unwrap();
+
+            // Keep the connection open until the signal has been received
+            for _ in connection.iter(50) {
+                if let Err(mpsc::TryRecvError::Empty) = done_rx.try_recv() {
+                    continue;
+                }
+                break;
+            }
+        });
+
+        ready_rx.recv().unwrap();
+
+        let manager = NetworkManager::with_bus_type(BusType::Session).unwrap();
+        let mut changes = manager.state_changes().unwrap();
+
+        emit_tx.send(()).unwrap();
+
+        assert_eq!(
+            changes.next_state_timeout(Duration::from_secs(5)),
+            Some(NetworkManagerState::ConnectedGlobal)
+        );
+
+        done_tx.send(()).unwrap();
+        service.join().unwrap();
+    }
+
     #[test]
     fn test_get_connections() {
         let manager = NetworkManager::new();
@@ -196,6 +949,33 @@ mod tests {
         assert!(devices.len() > 0);
     }
 
+    #[test]
+    fn test_connectivity_display_from_str() {
+        assert_eq!(Connectivity::Portal.to_string(), "portal");
+        assert_eq!("FULL".parse::<Connectivity>().unwrap(), Connectivity::Full);
+        assert!("online".parse::<Connectivity>().is_err());
+    }
+
+    #[test]
+    fn test_parse_version() {
+        assert_eq!(parse_version("1.42.4"), Some((1, 42, 4)));
+        assert_eq!(parse_version("1.40.0-1"), Some((1, 40, 0)));
+        assert_eq!(parse_version("1.10"), Some((1, 10, 0)));
+        assert_eq!(parse_version("1.22.10-dev.git"), Some((1, 22, 10)));
+        assert_eq!(parse_version(""), None);
+        assert_eq!(parse_version("dev"), None);
+    }
+
+    #[test]
+    fn test_connectivity_from_code() {
+        assert_eq!(Connectivity::from(0), Connectivity::Unknown);
+        assert_eq!(Connectivity::from(1), Connectivity::None);
+        assert_eq!(Connectivity::from(2), Connectivity::Portal);
+        assert_eq!(Connectivity::from(3), Connectivity::Limited);
+        assert_eq!(Connectivity::from(4), Connectivity::Full);
+        assert_eq!(Connectivity::from(42), Connectivity::Unknown);
+    }
+
     #[test]
     fn test_get_connectivity() {
         let manager = NetworkManager::new();
@@ -239,4 +1019,40 @@ mod tests {
             _ => (),
         }
     }
+
+    #[test]
+    fn test_try_new_without_bus() {
+        const CHILD: &str = "NETWORK_MANAGER_TEST_BOGUS_SYSTEM_BUS";
+
+        if env::var_os(CHILD).is_some() {
+            assert!(NetworkManager::try_new().is_err());
+            assert!(NetworkManager::try_with_method_timeout(5).is_err());
+            assert!(NetworkManager::with_retries(3, Backoff::default()).is_err());
+            assert!(
+                NetworkManager::with_retry_predicate(3, Backoff::default(), |_| {
+                    RetryDecision::Fail
+                }).is_err()
+            );
+            assert!(NetworkManager::with_bus_type(BusType::System).is_err());
+            assert!(NetworkManager::with_property_cache(Duration::from_secs(1)).is_err());
+            return;
+        }
+
+        // libdbus reads the bus addresses once per process, so the bogus address has to be
+        // set for a fresh test process
+        let output = Command::new(env::current_exe().unwrap())
+            .arg("--exact")
+            .arg("manager::tests::test_try_new_without_bus")
+            .env(CHILD, "1")
+            .env(
+                "DBUS_SYSTEM_BUS_ADDRESS",
+                "unix:path=/nonexistent/network-manager-test",
+            )
+            .output()
+            .unwrap();
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(output.status.success(), "{}", stdout);
+        assert!(stdout.contains("1 passed"), "{}", stdout);
+    }
 }
@@ -0,0 +1,99 @@
+//! A stand-in D-Bus service on the session bus for exercising the wrappers in tests.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+
+use dbus::{BusType, Connection, ConnectionItem, Message};
+use dbus::tree::{Factory, MTFn, Tree};
+
+pub type MockFactory = Factory<MTFn<()>, ()>;
+pub type MockTree = Tree<MTFn<()>, ()>;
+
+/// Queues signals from within method and property handlers of a mock tree.
+#[derive(Clone, Default)]
+pub struct MockSignals(Rc<RefCell<Vec<Message>>>);
+
+impl MockSignals {
+    /// Sends `signal` once the current message has been handled.
+    pub fn emit(&self, signal: Message) {
+        self.0.borrow_mut().push(signal);
+    }
+
+    fn take(&self) -> Vec<Message> {
+        self.0.borrow_mut().drain(..).collect()
+    }
+}
+
+/// Serves the tree returned by `build` under a well-known name until dropped.
+pub struct MockService {
+    done: mpsc::Sender<()>,
+    handle: Option<thread::JoinHandle<()>>,
+    method_calls: Arc<AtomicUsize>,
+}
+
+impl MockService {
+    pub fn spawn<F>(name: &'static str, build: F) -> Self
+    where
+        F: FnOnce(&MockFactory, &MockSignals) -> MockTree + Send + 'static,
+    {
+        let (ready_tx, ready_rx) = mpsc::channel();
+        let (done_tx, done_rx) = mpsc::channel::<()>();
+        let method_calls = Arc::new(AtomicUsize::new(0));
+        let counter = method_calls.clone();
+
+        let handle = thread::spawn(move || {
+            let connection = Connection::get_private(BusType::Session).unwrap();
+            connection.register_name(name, 0).unwrap();
+
+            let signals = MockSignals::default();
+            let tree = build(&Factory::new_fn::<()>(), &signals);
+            tree.set_registered(&connection, true).unwrap();
+
+            ready_tx.send(()).unwrap();
+
+            let items = connection.iter(50).inspect(|item| {
+                if let ConnectionItem::MethodCall(_) = *item {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                }
+            });
+
+            for _ in tree.run(&connection, items) {
+                for signal in signals.take() {
+                    connection.send(signal).unwrap();
+                }
+
+                if let Err(mpsc::TryRecvError::Empty) = done_rx.try_recv() {
+                    continue;
+                }
+                break;
+            }
+        });
+
+        ready_rx.recv().unwrap();
+
+        MockService {
+            done: done_tx,
+            handle: Some(handle),
+            method_calls: method_calls,
+        }
+    }
+
+    /// The number of method calls received so far, including property reads.
+    pub fn method_calls(&self) -> usize {
+        self.method_calls.load(Ordering::SeqCst)
+    }
+}
+
+impl Drop for MockService {
+    fn drop(&mut self) {
+        let _ = self.done.send(());
+
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
@@ -0,0 +1,185 @@
+//! Serde support for the types whose D-Bus representation has no natural serialized form.
+
+use std::fmt;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde::de::{self, SeqAccess, Visitor};
+use serde::ser::SerializeSeq;
+
+use ssid::Ssid;
+use wifi::{security_from_name, security_names, Security};
+
+/// Serializes as a string if the SSID is valid UTF-8 and as a sequence of bytes otherwise.
+impl Serialize for Ssid {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.as_str() {
+            Ok(ssid) => serializer.serialize_str(ssid),
+            Err(_) => serializer.serialize_bytes(self.as_bytes()),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Ssid {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(SsidVisitor)
+    }
+}
+
+struct SsidVisitor;
+
+impl<'de> Visitor<'de> for SsidVisitor {
+    type Value = Ssid;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("an SSID string or byte sequence")
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Ssid, E> {
+        self.visit_bytes(value.as_bytes())
+    }
+
+    fn visit_bytes<E: de::Error>(self, value: &[u8]) -> Result<Ssid, E> {
+        Ssid::from_bytes(value.to_vec()).map_err(|e| E::custom(e.to_string()))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Ssid, A::Error> {
+        let mut bytes = Vec::new();
+
+        while let Some(byte) = seq.next_element::<u8>()? {
+            bytes.push(byte);
+        }
+
+        self.visit_bytes(&bytes)
+    }
+}
+
+/// Serializes as the list of lowercase names of the set flags, e.g. `["wpa", "wpa2"]`.
+impl Serialize for Security {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let names = security_names(*self);
+
+        let mut seq = serializer.serialize_seq(Some(names.len()))?;
+        for name in names {
+            seq.serialize_element(name)?;
+        }
+        seq.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for Security {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let names = Vec::<String>::deserialize(deserializer)?;
+
+        let mut security = Security::NONE;
+
+        for name in &names {
+            match security_from_name(name) {
+                Some(flag) => security |= flag,
+                None => {
+                    let known = security_names(Security::all())
+                        .iter()
+                        .map(|known| format!("`{}`", known))
+                        .collect::<Vec<_>>();
+
+                    return Err(de::Error::custom(format_args!(
+                        "unknown variant `{}`, expected one of {}",
+                        name,
+                        known.join(", ")
+                    )));
+                },
+            }
+        }
+
+        Ok(security)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json;
+
+    use device::{DeviceState, DeviceType, Metered, Stats};
+    use manager::Connectivity;
+    use wifi::{AccessPoint, ApMode};
+
+    use super::*;
+
+    #[test]
+    fn test_access_point_json_round_trip() {
+        let access_point = AccessPoint {
+            path: "/org/freedesktop/NetworkManager/AccessPoint/1".to_string(),
+            ssid: Ssid::from_bytes("CoffeeShop").unwrap(),
+            strength: 72,
+            security: Security::WPA | Security::WPA2,
+            frequency: 2412,
+            hw_address: "A0:B1:C2:D3:E4:F5".to_string(),
+            mode: ApMode::Infra,
+            max_bitrate_kbps: 54_000,
+        };
+
+        let json = serde_json::to_string(&access_point).unwrap();
+        assert!(json.contains(r#""ssid":"CoffeeShop""#));
+        assert!(json.contains(r#""security":["wpa","wpa2"]"#));
+
+        let decoded: AccessPoint = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded.path, access_point.path);
+        assert_eq!(decoded.ssid, access_point.ssid);
+        assert_eq!(decoded.strength, access_point.strength);
+        assert_eq!(decoded.security, access_point.security);
+        assert_eq!(decoded.frequency, access_point.frequency);
+        assert_eq!(decoded.hw_address, access_point.hw_address);
+        assert_eq!(decoded.mode, access_point.mode);
+        assert_eq!(decoded.max_bitrate_kbps, access_point.max_bitrate_kbps);
+    }
+
+    #[test]
+    fn test_ssid_json_non_utf8() {
+        let ssid = Ssid::from_bytes(vec![0xff, 0x00, 0x41]).unwrap();
+
+        let json = serde_json::to_string(&ssid).unwrap();
+        assert_eq!(json, "[255,0,65]");
+
+        let decoded: Ssid = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded, ssid);
+    }
+
+    #[test]
+    fn test_security_json_unknown_name() {
+        assert_eq!(
+            serde_json::from_str::<Security>(r#"["WPA2"]"#).unwrap(),
+            Security::WPA2
+        );
+
+        let error = serde_json::from_str::<Security>(r#"["wpa3"]"#).unwrap_err();
+        assert!(error.to_string().starts_with(
+            "unknown variant `wpa3`, expected one of `wep`, `wpa`, `wpa2`, `enterprise`"
+        ));
+    }
+
+    #[test]
+    fn test_stats_json_round_trip() {
+        let stats = Stats {
+            tx_bytes: 1024,
+            rx_bytes: 4096,
+            refresh_rate_ms: 1000,
+        };
+
+        let json = serde_json::to_string(&stats).unwrap();
+        assert_eq!(json, r#"{"tx_bytes":1024,"rx_bytes":4096,"refresh_rate_ms":1000}"#);
+        assert_eq!(serde_json::from_str::<Stats>(&json).unwrap(), stats);
+    }
+
+    #[test]
+    fn test_enums_json_lowercase() {
+        assert_eq!(serde_json::to_string(&DeviceType::WiFi).unwrap(), r#""wifi""#);
+        assert_eq!(
+            serde_json::to_string(&DeviceState::Activated).unwrap(),
+            r#""activated""#
+        );
+        assert_eq!(
+            serde_json::from_str::<Connectivity>(r#""portal""#).unwrap(),
+            Connectivity::Portal
+        );
+        assert_eq!(serde_json::to_string(&Metered::GuessYes).unwrap(), r#""guessyes""#);
+    }
+}
@@ -0,0 +1,2475 @@
+//! Builders for the setting dictionaries of a connection profile.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use errors::*;
+use dbus_nm::{add_str, add_val, verify_ascii_password, SettingsMap, VariantMap,
+              NM_WEP_KEY_TYPE_PASSPHRASE};
+use ssid::AsSsidSlice;
+
+/// How a connection obtains its IPv4 configuration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ipv4Method {
+    Auto,
+    Manual,
+    Link,
+    Shared,
+    Disabled,
+}
+
+impl Ipv4Method {
+    fn as_str(&self) -> &'static str {
+        match *self {
+            Ipv4Method::Auto => "auto",
+            Ipv4Method::Manual => "manual",
+            Ipv4Method::Link => "link-local",
+            Ipv4Method::Shared => "shared",
+            Ipv4Method::Disabled => "disabled",
+        }
+    }
+}
+
+/// Builds the `ipv4` setting of a connection profile.
+///
+/// # Examples
+///
+/// ```
+/// use std::net::Ipv4Addr;
+/// use network_manager::{Ipv4Config, Ipv4Method};
+///
+/// let ipv4 = Ipv4Config::new()
+///     .method(Ipv4Method::Manual)
+///     .address(Ipv4Addr::new(192, 168, 1, 10), 24)
+///     .gateway(Ipv4Addr::new(192, 168, 1, 1))
+///     .dns(vec![Ipv4Addr::new(192, 168, 1, 1)])
+///     .to_settings()
+///     .unwrap();
+///
+/// assert!(ipv4.contains_key("addresses"));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ipv4Config {
+    method: Ipv4Method,
+    addresses: Vec<(Ipv4Addr, u8)>,
+    gateway: Option<Ipv4Addr>,
+    dns: Vec<Ipv4Addr>,
+    dns_priority: Option<i32>,
+    dns_search: Vec<String>,
+    dns_options: Vec<String>,
+    routes: Vec<Route>,
+    never_default: bool,
+}
+
+impl Ipv4Config {
+    pub fn new() -> Self {
+        Ipv4Config {
+            method: Ipv4Method::Auto,
+            addresses: Vec::new(),
+            gateway: None,
+            dns: Vec::new(),
+            dns_priority: None,
+            dns_search: Vec::new(),
+            dns_options: Vec::new(),
+            routes: Vec::new(),
+            never_default: false,
+        }
+    }
+
+    pub fn method(mut self, method: Ipv4Method) -> Self {
+        self.method = method;
+        self
+    }
+
+    /// Adds a static address, only used with `Ipv4Method::Manual`.
+    pub fn address(mut self, address: Ipv4Addr, prefix: u8) -> Self {
+        self.addresses.push((address, prefix));
+        self
+    }
+
+    /// Sets the default gateway, only used with `Ipv4Method::Manual`.
+    pub fn gateway(mut self, gateway: Ipv4Addr) -> Self {
+        self.gateway = Some(gateway);
+        self
+    }
+
+    pub fn dns(mut self, servers: Vec<Ipv4Addr>) -> Self {
+        self.dns = servers;
+        self
+    }
+
+    /// Sets the priority of the DNS servers against those of other connections; lower values
+    /// are preferred, and a negative value excludes the servers of connections with higher ones.
+    pub fn dns_priority(mut self, priority: i32) -> Self {
+        self.dns_priority = Some(priority);
+        self
+    }
+
+    /// Sets the search domains, also routing lookups of these domains to this connection's DNS
+    /// servers in split DNS setups.
+    pub fn dns_search(mut self, domains: Vec<String>) -> Self {
+        self.dns_search = domains;
+        self
+    }
+
+    /// Sets resolver options as in `resolv.conf`, e.g. `"rotate"` or `"timeout:2"`.
+    pub fn dns_options(mut self, options: Vec<String>) -> Self {
+        self.dns_options = options;
+        self
+    }
+
+    /// Sets static routes added besides the default route.
+    pub fn routes(mut self, routes: Vec<Route>) -> Self {
+        self.routes = routes;
+        self
+    }
+
+    /// Keeps the connection from becoming the default route, e.g. for a management network
+    /// next to the uplink.
+    pub fn never_default(mut self, never_default: bool) -> Self {
+        self.never_default = never_default;
+        self
+    }
+
+    /// Emits the setting for inclusion under the `ipv4` key of a settings map.
+    ///
+    /// Addresses, gateway, DNS servers and routes use NetworkManager's legacy encoding as
+    /// integers in network byte order, which the `addresses`, `dns` and `routes` properties
+    /// expect.
+    pub fn to_settings(&self) -> Result<VariantMap> {
+        let mut settings: VariantMap = HashMap::new();
+        add_str(&mut settings, "method", self.method.as_str());
+
+        if self.method == Ipv4Method::Manual {
+            if self.addresses.is_empty() {
+                bail!(ErrorKind::Settings(
+                    "Manual IPv4 configuration requires an address".into()
+                ));
+            }
+
+            let gateway = self.gateway.map_or(0, ipv4_to_u32);
+
+            let mut addresses = Vec::with_capacity(self.addresses.len());
+            for &(address, prefix) in &self.addresses {
+                if prefix > 32 {
+                    bail!(ErrorKind::Settings(format!(
+                        "Invalid IPv4 prefix length {} for {}",
+                        prefix, address
+                    )));
+                }
+
+                // Only the first address carries the gateway
+                let gateway = if addresses.is_empty() { gateway } else { 0 };
+
+                addresses.push(vec![ipv4_to_u32(address), u32::from(prefix), gateway]);
+            }
+
+            add_val(&mut settings, "addresses", addresses);
+        }
+
+        if !self.dns.is_empty() {
+            let dns: Vec<u32> = self.dns.iter().cloned().map(ipv4_to_u32).collect();
+            add_val(&mut settings, "dns", dns);
+        }
+
+        if !self.routes.is_empty() {
+            let mut routes = Vec::with_capacity(self.routes.len());
+            for route in &self.routes {
+                let (dest, next_hop) = match (route.dest, route.next_hop) {
+                    (IpAddr::V4(dest), None) => (dest, Ipv4Addr::new(0, 0, 0, 0)),
+                    (IpAddr::V4(dest), Some(IpAddr::V4(next_hop))) => (dest, next_hop),
+                    _ => bail!(ErrorKind::Settings(format!("Invalid IPv4 route {}", route))),
+                };
+
+                if route.prefix > 32 {
+                    bail!(ErrorKind::Settings(format!("Invalid IPv4 route {}", route)));
+                }
+
+                routes.push(vec![
+                    ipv4_to_u32(dest),
+                    u32::from(route.prefix),
+                    ipv4_to_u32(next_hop),
+                    route.metric.unwrap_or(0),
+                ]);
+            }
+
+            add_val(&mut settings, "routes", routes);
+        }
+
+        add_dns_extras(
+            &mut settings,
+            self.dns_priority,
+            &self.dns_search,
+            &self.dns_options,
+        );
+
+        if self.never_default {
+            add_val(&mut settings, "never-default", true);
+        }
+
+        Ok(settings)
+    }
+}
+
+impl Default for Ipv4Config {
+    fn default() -> Self {
+        Ipv4Config::new()
+    }
+}
+
+/// A static route of an `Ipv4Config` or `Ipv6Config`, whose addresses must match its family.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Route {
+    pub dest: IpAddr,
+    pub prefix: u8,
+    /// The gateway to route through, or `None` for an on-link destination.
+    pub next_hop: Option<IpAddr>,
+    /// The route metric, or `None` to use the metric of the device.
+    pub metric: Option<u32>,
+}
+
+impl fmt::Display for Route {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.dest, self.prefix)?;
+
+        if let Some(next_hop) = self.next_hop {
+            write!(f, " via {}", next_hop)?;
+        }
+
+        if let Some(metric) = self.metric {
+            write!(f, " metric {}", metric)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Adds the DNS settings shared by the `ipv4` and `ipv6` settings.
+fn add_dns_extras(
+    settings: &mut VariantMap,
+    priority: Option<i32>,
+    search: &[String],
+    options: &[String],
+) {
+    if let Some(priority) = priority {
+        add_val(settings, "dns-priority", priority);
+    }
+
+    if !search.is_empty() {
+        add_val(settings, "dns-search", search.to_vec());
+    }
+
+    if !options.is_empty() {
+        add_val(settings, "dns-options", options.to_vec());
+    }
+}
+
+/// Encodes `address` the way NetworkManager stores an `in_addr_t`.
+fn ipv4_to_u32(address: Ipv4Addr) -> u32 {
+    u32::from(address).to_be()
+}
+
+/// How a connection obtains its IPv6 configuration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ipv6Method {
+    Auto,
+    Dhcp,
+    Manual,
+    LinkLocal,
+    Ignore,
+    Disabled,
+}
+
+impl Ipv6Method {
+    fn as_str(&self) -> &'static str {
+        match *self {
+            Ipv6Method::Auto => "auto",
+            Ipv6Method::Dhcp => "dhcp",
+            Ipv6Method::Manual => "manual",
+            Ipv6Method::LinkLocal => "link-local",
+            Ipv6Method::Ignore => "ignore",
+            Ipv6Method::Disabled => "disabled",
+        }
+    }
+}
+
+/// How the interface identifier of SLAAC addresses is generated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ipv6AddrGenMode {
+    Eui64,
+    StablePrivacy,
+}
+
+/// Builds the `ipv6` setting of a connection profile.
+///
+/// # Examples
+///
+/// ```
+/// use std::net::Ipv6Addr;
+/// use network_manager::{Ipv6Config, Ipv6Method};
+///
+/// let ipv6 = Ipv6Config::new()
+///     .method(Ipv6Method::Manual)
+///     .address("2001:db8::10".parse().unwrap(), 64)
+///     .gateway("2001:db8::1".parse().unwrap())
+///     .to_settings()
+///     .unwrap();
+///
+/// assert!(ipv6.contains_key("addresses"));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ipv6Config {
+    method: Ipv6Method,
+    addresses: Vec<(Ipv6Addr, u8)>,
+    gateway: Option<Ipv6Addr>,
+    dns: Vec<Ipv6Addr>,
+    dns_priority: Option<i32>,
+    dns_search: Vec<String>,
+    dns_options: Vec<String>,
+    routes: Vec<Route>,
+    never_default: bool,
+    addr_gen_mode: Option<Ipv6AddrGenMode>,
+}
+
+impl Ipv6Config {
+    pub fn new() -> Self {
+        Ipv6Config {
+            method: Ipv6Method::Auto,
+            addresses: Vec::new(),
+            gateway: None,
+            dns: Vec::new(),
+            dns_priority: None,
+            dns_search: Vec::new(),
+            dns_options: Vec::new(),
+            routes: Vec::new(),
+            never_default: false,
+            addr_gen_mode: None,
+        }
+    }
+
+    pub fn method(mut self, method: Ipv6Method) -> Self {
+        self.method = method;
+        self
+    }
+
+    /// Adds a static address, only used with `Ipv6Method::Manual`.
+    pub fn address(mut self, address: Ipv6Addr, prefix: u8) -> Self {
+        self.addresses.push((address, prefix));
+        self
+    }
+
+    /// Sets the default gateway, only used with `Ipv6Method::Manual`.
+    pub fn gateway(mut self, gateway: Ipv6Addr) -> Self {
+        self.gateway = Some(gateway);
+        self
+    }
+
+    pub fn dns(mut self, servers: Vec<Ipv6Addr>) -> Self {
+        self.dns = servers;
+        self
+    }
+
+    /// Sets the priority of the DNS servers against those of other connections; lower values
+    /// are preferred, and a negative value excludes the servers of connections with higher ones.
+    pub fn dns_priority(mut self, priority: i32) -> Self {
+        self.dns_priority = Some(priority);
+        self
+    }
+
+    /// Sets the search domains, also routing lookups of these domains to this connection's DNS
+    /// servers in split DNS setups.
+    pub fn dns_search(mut self, domains: Vec<String>) -> Self {
+        self.dns_search = domains;
+        self
+    }
+
+    /// Sets resolver options as in `resolv.conf`, e.g. `"rotate"` or `"timeout:2"`.
+    pub fn dns_options(mut self, options: Vec<String>) -> Self {
+        self.dns_options = options;
+        self
+    }
+
+    /// Sets static routes added besides the default route.
+    pub fn routes(mut self, routes: Vec<Route>) -> Self {
+        self.routes = routes;
+        self
+    }
+
+    /// Keeps the connection from becoming the default route, e.g. for a management network
+    /// next to the uplink.
+    pub fn never_default(mut self, never_default: bool) -> Self {
+        self.never_default = never_default;
+        self
+    }
+
+    pub fn addr_gen_mode(mut self, mode: Ipv6AddrGenMode) -> Self {
+        self.addr_gen_mode = Some(mode);
+        self
+    }
+
+    /// Emits the setting for inclusion under the `ipv6` key of a settings map.
+    ///
+    /// Addresses use NetworkManager's legacy `a(ayuay)` encoding of address, prefix length and
+    /// gateway, routes the `a(ayuayu)` encoding which adds the metric, and DNS servers are
+    /// encoded as `aay`.
+    pub fn to_settings(&self) -> Result<VariantMap> {
+        let mut settings: VariantMap = HashMap::new();
+        add_str(&mut settings, "method", self.method.as_str());
+
+        if self.method == Ipv6Method::Manual {
+            if self.addresses.is_empty() {
+                bail!(ErrorKind::Settings(
+                    "Manual IPv6 configuration requires an address".into()
+                ));
+            }
+
+            let gateway = self.gateway
+                .unwrap_or_else(|| Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 0));
+
+            let mut addresses = Vec::with_capacity(self.addresses.len());
+            for &(address, prefix) in &self.addresses {
+                if prefix > 128 {
+                    bail!(ErrorKind::Settings(format!(
+                        "Invalid IPv6 prefix length {} for {}",
+                        prefix, address
+                    )));
+                }
+
+                addresses.push((
+                    address.octets().to_vec(),
+                    u32::from(prefix),
+                    gateway.octets().to_vec(),
+                ));
+            }
+
+            add_val(&mut settings, "addresses", addresses);
+        }
+
+        if !self.dns.is_empty() {
+            let dns: Vec<Vec<u8>> = self.dns.iter().map(|dns| dns.octets().to_vec()).collect();
+            add_val(&mut settings, "dns", dns);
+        }
+
+        if !self.routes.is_empty() {
+            let mut routes = Vec::with_capacity(self.routes.len());
+            for route in &self.routes {
+                let (dest, next_hop) = match (route.dest, route.next_hop) {
+                    (IpAddr::V6(dest), None) => (dest, Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 0)),
+                    (IpAddr::V6(dest), Some(IpAddr::V6(next_hop))) => (dest, next_hop),
+                    _ => bail!(ErrorKind::Settings(format!("Invalid IPv6 route {}", route))),
+                };
+
+                if route.prefix > 128 {
+                    bail!(ErrorKind::Settings(format!("Invalid IPv6 route {}", route)));
+                }
+
+                routes.push((
+                    dest.octets().to_vec(),
+                    u32::from(route.prefix),
+                    next_hop.octets().to_vec(),
+                    route.metric.unwrap_or(0),
+                ));
+            }
+
+            add_val(&mut settings, "routes", routes);
+        }
+
+        add_dns_extras(
+            &mut settings,
+            self.dns_priority,
+            &self.dns_search,
+            &self.dns_options,
+        );
+
+        if self.never_default {
+            add_val(&mut settings, "never-default", true);
+        }
+
+        if let Some(mode) = self.addr_gen_mode {
+            let mode: i32 = match mode {
+                Ipv6AddrGenMode::Eui64 => 0,
+                Ipv6AddrGenMode::StablePrivacy => 1,
+            };
+            add_val(&mut settings, "addr-gen-mode", mode);
+        }
+
+        Ok(settings)
+    }
+}
+
+impl Default for Ipv6Config {
+    fn default() -> Self {
+        Ipv6Config::new()
+    }
+}
+
+/// Builds the `802-11-wireless` setting of a connection profile.
+///
+/// # Examples
+///
+/// ```
+/// use network_manager::WirelessConfig;
+///
+/// let wireless = WirelessConfig::new("CoffeeShop")
+///     .unwrap()
+///     .cloned_mac_address("random")
+///     .to_settings()
+///     .unwrap();
+///
+/// assert!(wireless.contains_key("cloned-mac-address"));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WirelessConfig {
+    ssid: Vec<u8>,
+    hidden: bool,
+    cloned_mac_address: Option<String>,
+    mtu: Option<u32>,
+    powersave: Option<WirelessPowersave>,
+    bssid: Option<String>,
+}
+
+impl WirelessConfig {
+    pub fn new<T>(ssid: &T) -> Result<Self>
+    where
+        T: AsSsidSlice + ?Sized,
+    {
+        Ok(WirelessConfig {
+            ssid: ssid.as_ssid_slice()?.as_bytes().to_vec(),
+            hidden: false,
+            cloned_mac_address: None,
+            mtu: None,
+            powersave: None,
+            bssid: None,
+        })
+    }
+
+    /// Marks the network as not broadcasting its SSID.
+    pub fn hidden(mut self, hidden: bool) -> Self {
+        self.hidden = hidden;
+        self
+    }
+
+    /// Sets the MAC address used on the network; see `cloned_mac_address_value`.
+    pub fn cloned_mac_address(mut self, mac: &str) -> Self {
+        self.cloned_mac_address = Some(mac.to_string());
+        self
+    }
+
+    /// Sets the MTU in bytes, or `0` to use the default of the device.
+    pub fn mtu(mut self, mtu: u32) -> Self {
+        self.mtu = Some(mtu);
+        self
+    }
+
+    pub fn powersave(mut self, powersave: WirelessPowersave) -> Self {
+        self.powersave = Some(powersave);
+        self
+    }
+
+    /// Only associates with the access point of the BSSID `bssid`, e.g. `a0:b1:c2:d3:e4:f5`,
+    /// instead of any access point of the network.
+    pub fn bssid(mut self, bssid: &str) -> Self {
+        self.bssid = Some(bssid.to_string());
+        self
+    }
+
+    /// Emits the setting for inclusion under the `802-11-wireless` key of a settings map.
+    pub fn to_settings(&self) -> Result<VariantMap> {
+        let mut settings: VariantMap = HashMap::new();
+        add_val(&mut settings, "ssid", self.ssid.clone());
+
+        if self.hidden {
+            add_val(&mut settings, "hidden", true);
+        }
+
+        if let Some(ref mac) = self.cloned_mac_address {
+            add_str(&mut settings, "cloned-mac-address", cloned_mac_address_value(mac)?);
+        }
+
+        if let Some(mtu) = self.mtu {
+            add_val(&mut settings, "mtu", mtu);
+        }
+
+        if let Some(powersave) = self.powersave {
+            add_val(&mut settings, "powersave", powersave.as_u32());
+        }
+
+        if let Some(ref bssid) = self.bssid {
+            add_val(&mut settings, "bssid", mac_address_to_bytes(bssid)?);
+        }
+
+        Ok(settings)
+    }
+}
+
+/// Whether the Wi-Fi power saving of a connection is forced on or off.
+///
+/// Power saving lengthens battery life at the cost of latency.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WirelessPowersave {
+    /// Use the global default of NetworkManager.
+    Default,
+    /// Leave the current setting of the device untouched.
+    Ignore,
+    Disable,
+    Enable,
+}
+
+impl WirelessPowersave {
+    /// The value of the `802-11-wireless.powersave` setting.
+    pub fn as_u32(&self) -> u32 {
+        match *self {
+            WirelessPowersave::Default => 0,
+            WirelessPowersave::Ignore => 1,
+            WirelessPowersave::Disable => 2,
+            WirelessPowersave::Enable => 3,
+        }
+    }
+}
+
+/// The `802-11-wireless-security` setting of a Wi-Fi connection profile.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WirelessSecurity {
+    /// WEP with a passphrase.
+    Wep(String),
+    /// WPA or WPA2 with a pre-shared key, whichever the access point offers.
+    WpaPsk(String),
+    /// WPA2 only (RSN with CCMP) with a pre-shared key.
+    Wpa2Psk(String),
+    /// WPA-Enterprise, authenticating with the `802-1x` setting.
+    WpaEap,
+}
+
+impl WirelessSecurity {
+    /// Emits the setting for inclusion under the `802-11-wireless-security` key of a settings
+    /// map.
+    pub fn to_settings(&self) -> Result<VariantMap> {
+        let mut settings: VariantMap = HashMap::new();
+
+        match *self {
+            WirelessSecurity::Wep(ref passphrase) => {
+                add_str(&mut settings, "key-mgmt", "none");
+                add_val(&mut settings, "wep-key-type", NM_WEP_KEY_TYPE_PASSPHRASE);
+                add_str(&mut settings, "wep-key0", verify_ascii_password(passphrase)?);
+            },
+            WirelessSecurity::WpaPsk(ref psk) => {
+                add_str(&mut settings, "key-mgmt", "wpa-psk");
+                add_str(&mut settings, "psk", verify_ascii_password(psk)?);
+            },
+            WirelessSecurity::Wpa2Psk(ref psk) => {
+                add_str(&mut settings, "key-mgmt", "wpa-psk");
+                add_val(&mut settings, "proto", vec!["rsn".to_string()]);
+                add_val(&mut settings, "pairwise", vec!["ccmp".to_string()]);
+                add_val(&mut settings, "group", vec!["ccmp".to_string()]);
+                add_str(&mut settings, "psk", verify_ascii_password(psk)?);
+            },
+            WirelessSecurity::WpaEap => {
+                add_str(&mut settings, "key-mgmt", "wpa-eap");
+            },
+        }
+
+        Ok(settings)
+    }
+}
+
+/// Builds the `802-3-ethernet` setting of a connection profile.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EthernetConfig {
+    cloned_mac_address: Option<String>,
+    mtu: Option<u32>,
+}
+
+impl EthernetConfig {
+    pub fn new() -> Self {
+        EthernetConfig {
+            cloned_mac_address: None,
+            mtu: None,
+        }
+    }
+
+    /// Sets the MAC address used on the link; see `cloned_mac_address_value`.
+    pub fn cloned_mac_address(mut self, mac: &str) -> Self {
+        self.cloned_mac_address = Some(mac.to_string());
+        self
+    }
+
+    /// Sets the MTU in bytes, or `0` to use the default of the device.
+    pub fn mtu(mut self, mtu: u32) -> Self {
+        self.mtu = Some(mtu);
+        self
+    }
+
+    /// Emits the setting for inclusion under the `802-3-ethernet` key of a settings map.
+    pub fn to_settings(&self) -> Result<VariantMap> {
+        let mut settings: VariantMap = HashMap::new();
+
+        if let Some(ref mac) = self.cloned_mac_address {
+            add_str(&mut settings, "cloned-mac-address", cloned_mac_address_value(mac)?);
+        }
+
+        if let Some(mtu) = self.mtu {
+            add_val(&mut settings, "mtu", mtu);
+        }
+
+        Ok(settings)
+    }
+}
+
+impl Default for EthernetConfig {
+    fn default() -> Self {
+        EthernetConfig::new()
+    }
+}
+
+/// Checks a cloned MAC address: either a literal address such as `02:00:5e:10:00:01`, or one
+/// of the keywords `random` (new address on every activation), `stable` (address derived from
+/// the connection), `preserve` (keep the current address) and `permanent` (hardware address).
+fn cloned_mac_address_value(mac: &str) -> Result<&str> {
+    match mac {
+        "random" | "stable" | "preserve" | "permanent" => Ok(mac),
+        _ => verify_mac_address(mac),
+    }
+}
+
+/// Checks that `mac` is six colon-separated pairs of hex digits.
+fn verify_mac_address(mac: &str) -> Result<&str> {
+    let octets = mac.split(':').collect::<Vec<_>>();
+
+    let valid = octets.len() == 6
+        && octets
+            .iter()
+            .all(|octet| octet.len() == 2 && u8::from_str_radix(octet, 16).is_ok());
+
+    if !valid {
+        bail!(ErrorKind::Settings(format!("Invalid MAC address: {}", mac)));
+    }
+
+    Ok(mac)
+}
+
+/// Encodes a MAC address as the byte array the `bssid` and `mac-address` properties expect.
+fn mac_address_to_bytes(mac: &str) -> Result<Vec<u8>> {
+    Ok(verify_mac_address(mac)?
+        .split(':')
+        .map(|octet| u8::from_str_radix(octet, 16).unwrap())
+        .collect())
+}
+
+/// A peer of a WireGuard connection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WireGuardPeer {
+    public_key: String,
+    endpoint: Option<String>,
+    allowed_ips: Vec<String>,
+    persistent_keepalive: Option<u32>,
+}
+
+impl WireGuardPeer {
+    pub fn new(public_key: &str) -> Self {
+        WireGuardPeer {
+            public_key: public_key.to_string(),
+            endpoint: None,
+            allowed_ips: Vec::new(),
+            persistent_keepalive: None,
+        }
+    }
+
+    /// Sets the `host:port` the peer is reachable at.
+    pub fn endpoint(mut self, endpoint: &str) -> Self {
+        self.endpoint = Some(endpoint.to_string());
+        self
+    }
+
+    /// Sets the networks routed to the peer, e.g. `10.0.0.0/24` or `::/0`.
+    pub fn allowed_ips(mut self, allowed_ips: Vec<String>) -> Self {
+        self.allowed_ips = allowed_ips;
+        self
+    }
+
+    /// Sets the interval in seconds of keepalive packets sent to the peer.
+    pub fn persistent_keepalive(mut self, seconds: u32) -> Self {
+        self.persistent_keepalive = Some(seconds);
+        self
+    }
+
+    fn to_settings(&self) -> VariantMap {
+        let mut settings: VariantMap = HashMap::new();
+        add_str(&mut settings, "public-key", self.public_key.as_str());
+
+        if let Some(ref endpoint) = self.endpoint {
+            add_str(&mut settings, "endpoint", endpoint.as_str());
+        }
+
+        if !self.allowed_ips.is_empty() {
+            add_val(&mut settings, "allowed-ips", self.allowed_ips.clone());
+        }
+
+        if let Some(seconds) = self.persistent_keepalive {
+            add_val(&mut settings, "persistent-keepalive", seconds);
+        }
+
+        settings
+    }
+}
+
+/// Builds the settings of a WireGuard connection profile.
+///
+/// IPv4 and IPv6 are disabled unless configured, since a WireGuard interface has no DHCP.
+///
+/// # Examples
+///
+/// ```
+/// use std::net::Ipv4Addr;
+/// use network_manager::{Ipv4Config, Ipv4Method, WireGuardConfig, WireGuardPeer};
+///
+/// let private_key = "yAnz5TF+lXXJte14tji3zlMNq+hd2rYUIgJBgB3fBmk=";
+///
+/// let settings = WireGuardConfig::new("office-vpn", "wg0", private_key)
+///     .listen_port(51820)
+///     .peer(
+///         WireGuardPeer::new("xTIBA5rboUvnH4htodjb6e697QjLERt1NAB4mZqp8Dg=")
+///             .endpoint("vpn.example.com:51820")
+///             .allowed_ips(vec!["10.0.0.0/24".to_string()]),
+///     )
+///     .ipv4(
+///         Ipv4Config::new()
+///             .method(Ipv4Method::Manual)
+///             .address(Ipv4Addr::new(10, 0, 0, 2), 24),
+///     )
+///     .to_settings()
+///     .unwrap();
+///
+/// assert!(settings.contains_key("wireguard"));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WireGuardConfig {
+    id: String,
+    interface_name: String,
+    private_key: String,
+    listen_port: Option<u16>,
+    peers: Vec<WireGuardPeer>,
+    ipv4: Ipv4Config,
+    ipv6: Ipv6Config,
+}
+
+impl WireGuardConfig {
+    pub fn new(id: &str, interface_name: &str, private_key: &str) -> Self {
+        WireGuardConfig {
+            id: id.to_string(),
+            interface_name: interface_name.to_string(),
+            private_key: private_key.to_string(),
+            listen_port: None,
+            peers: Vec::new(),
+            ipv4: Ipv4Config::new().method(Ipv4Method::Disabled),
+            ipv6: Ipv6Config::new().method(Ipv6Method::Disabled),
+        }
+    }
+
+    pub fn listen_port(mut self, port: u16) -> Self {
+        self.listen_port = Some(port);
+        self
+    }
+
+    pub fn peer(mut self, peer: WireGuardPeer) -> Self {
+        self.peers.push(peer);
+        self
+    }
+
+    pub fn ipv4(mut self, ipv4: Ipv4Config) -> Self {
+        self.ipv4 = ipv4;
+        self
+    }
+
+    pub fn ipv6(mut self, ipv6: Ipv6Config) -> Self {
+        self.ipv6 = ipv6;
+        self
+    }
+
+    /// Emits the settings map of the connection profile, e.g. for `NetworkManager::add_connection`.
+    pub fn to_settings(&self) -> Result<SettingsMap> {
+        if self.private_key.is_empty() {
+            bail!(ErrorKind::Settings(
+                "A WireGuard connection requires a private key".into()
+            ));
+        }
+
+        let mut connection: VariantMap = HashMap::new();
+        add_str(&mut connection, "id", self.id.as_str());
+        add_str(&mut connection, "type", "wireguard");
+        add_str(&mut connection, "interface-name", self.interface_name.as_str());
+
+        let mut wireguard: VariantMap = HashMap::new();
+        add_str(&mut wireguard, "private-key", self.private_key.as_str());
+
+        if let Some(port) = self.listen_port {
+            add_val(&mut wireguard, "listen-port", u32::from(port));
+        }
+
+        if !self.peers.is_empty() {
+            let peers: Vec<VariantMap> = self.peers.iter().map(|peer| peer.to_settings()).collect();
+            add_val(&mut wireguard, "peers", peers);
+        }
+
+        let mut settings: SettingsMap = HashMap::new();
+        settings.insert("connection".to_string(), connection);
+        settings.insert("wireguard".to_string(), wireguard);
+        settings.insert("ipv4".to_string(), self.ipv4.to_settings()?);
+        settings.insert("ipv6".to_string(), self.ipv6.to_settings()?);
+
+        Ok(settings)
+    }
+}
+
+/// The kind of master a slave connection joins.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SlaveType {
+    Bond,
+    Bridge,
+}
+
+impl SlaveType {
+    fn as_str(&self) -> &'static str {
+        match *self {
+            SlaveType::Bond => "bond",
+            SlaveType::Bridge => "bridge",
+        }
+    }
+}
+
+/// Builds the settings of a bond connection profile, which aggregates the links of its slaves.
+///
+/// Slaves join the bond by naming its `interface_name` as their master, e.g. with
+/// `ConnectionBuilder::master`.
+///
+/// # Examples
+///
+/// ```
+/// use network_manager::BondConfig;
+///
+/// let settings = BondConfig::new("uplink", "bond0")
+///     .mode("active-backup")
+///     .option("miimon", "100")
+///     .to_settings()
+///     .unwrap();
+///
+/// assert!(settings.contains_key("bond"));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BondConfig {
+    id: String,
+    interface_name: String,
+    options: HashMap<String, String>,
+    ipv4: Option<Ipv4Config>,
+    ipv6: Option<Ipv6Config>,
+}
+
+impl BondConfig {
+    pub fn new(id: &str, interface_name: &str) -> Self {
+        let mut options = HashMap::new();
+        options.insert("mode".to_string(), "balance-rr".to_string());
+
+        BondConfig {
+            id: id.to_string(),
+            interface_name: interface_name.to_string(),
+            options: options,
+            ipv4: None,
+            ipv6: None,
+        }
+    }
+
+    /// Sets the bonding mode, e.g. `active-backup` or `802.3ad`; `balance-rr` by default.
+    pub fn mode(self, mode: &str) -> Self {
+        self.option("mode", mode)
+    }
+
+    /// Sets a bonding driver option, e.g. `miimon`.
+    pub fn option(mut self, name: &str, value: &str) -> Self {
+        self.options.insert(name.to_string(), value.to_string());
+        self
+    }
+
+    pub fn ipv4(mut self, ipv4: Ipv4Config) -> Self {
+        self.ipv4 = Some(ipv4);
+        self
+    }
+
+    pub fn ipv6(mut self, ipv6: Ipv6Config) -> Self {
+        self.ipv6 = Some(ipv6);
+        self
+    }
+
+    /// Emits the settings map of the connection profile, e.g. for `NetworkManager::add_connection`.
+    pub fn to_settings(&self) -> Result<SettingsMap> {
+        let mut bond: VariantMap = HashMap::new();
+        add_val(&mut bond, "options", self.options.clone());
+
+        master_settings(
+            &self.id,
+            &self.interface_name,
+            "bond",
+            bond,
+            self.ipv4.as_ref(),
+            self.ipv6.as_ref(),
+        )
+    }
+}
+
+/// Builds the settings of a bridge connection profile, which forwards between its slaves.
+///
+/// Slaves join the bridge by naming its `interface_name` as their master.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BridgeConfig {
+    id: String,
+    interface_name: String,
+    stp: Option<bool>,
+    priority: Option<u16>,
+    ipv4: Option<Ipv4Config>,
+    ipv6: Option<Ipv6Config>,
+}
+
+impl BridgeConfig {
+    pub fn new(id: &str, interface_name: &str) -> Self {
+        BridgeConfig {
+            id: id.to_string(),
+            interface_name: interface_name.to_string(),
+            stp: None,
+            priority: None,
+            ipv4: None,
+            ipv6: None,
+        }
+    }
+
+    /// Enables or disables the Spanning Tree Protocol, which NetworkManager enables by default.
+    pub fn stp(mut self, stp: bool) -> Self {
+        self.stp = Some(stp);
+        self
+    }
+
+    /// Sets the STP priority; the bridge with the lowest one becomes the root bridge.
+    pub fn priority(mut self, priority: u16) -> Self {
+        self.priority = Some(priority);
+        self
+    }
+
+    pub fn ipv4(mut self, ipv4: Ipv4Config) -> Self {
+        self.ipv4 = Some(ipv4);
+        self
+    }
+
+    pub fn ipv6(mut self, ipv6: Ipv6Config) -> Self {
+        self.ipv6 = Some(ipv6);
+        self
+    }
+
+    /// Emits the settings map of the connection profile, e.g. for `NetworkManager::add_connection`.
+    pub fn to_settings(&self) -> Result<SettingsMap> {
+        let mut bridge: VariantMap = HashMap::new();
+
+        if let Some(stp) = self.stp {
+            add_val(&mut bridge, "stp", stp);
+        }
+
+        if let Some(priority) = self.priority {
+            add_val(&mut bridge, "priority", u32::from(priority));
+        }
+
+        master_settings(
+            &self.id,
+            &self.interface_name,
+            "bridge",
+            bridge,
+            self.ipv4.as_ref(),
+            self.ipv6.as_ref(),
+        )
+    }
+}
+
+bitflags! {
+    /// Options of a VLAN interface.
+    pub struct VlanFlags: u32 {
+        const NONE            = 0x0000_0000;
+        // strip the VLAN header from packets passed up the stack
+        const REORDER_HEADERS = 0x0000_0001;
+        // register the VLAN with switches through GVRP
+        const GVRP            = 0x0000_0002;
+        // keep the VLAN up regardless of the state of its parent
+        const LOOSE_BINDING   = 0x0000_0004;
+        // register the VLAN with switches through MVRP
+        const MVRP            = 0x0000_0008;
+    }
+}
+
+/// Builds the settings of a VLAN connection profile, tagging the traffic on `parent` with the
+/// VLAN ID.
+///
+/// # Examples
+///
+/// ```
+/// use network_manager::VlanConfig;
+///
+/// let settings = VlanConfig::new("sensors", "eth0", 20)
+///     .to_settings()
+///     .unwrap();
+///
+/// assert!(settings.contains_key("vlan"));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VlanConfig {
+    id: String,
+    parent: String,
+    vlan_id: u16,
+    interface_name: Option<String>,
+    flags: Option<VlanFlags>,
+    ipv4: Option<Ipv4Config>,
+    ipv6: Option<Ipv6Config>,
+}
+
+impl VlanConfig {
+    /// `parent` is the interface or connection UUID carrying the tagged traffic.
+    pub fn new(id: &str, parent: &str, vlan_id: u16) -> Self {
+        VlanConfig {
+            id: id.to_string(),
+            parent: parent.to_string(),
+            vlan_id: vlan_id,
+            interface_name: None,
+            flags: None,
+            ipv4: None,
+            ipv6: None,
+        }
+    }
+
+    /// Names the VLAN interface, which NetworkManager otherwise derives from the parent.
+    pub fn interface_name(mut self, interface_name: &str) -> Self {
+        self.interface_name = Some(interface_name.to_string());
+        self
+    }
+
+    /// Replaces the flags, `REORDER_HEADERS` by default.
+    pub fn flags(mut self, flags: VlanFlags) -> Self {
+        self.flags = Some(flags);
+        self
+    }
+
+    pub fn ipv4(mut self, ipv4: Ipv4Config) -> Self {
+        self.ipv4 = Some(ipv4);
+        self
+    }
+
+    pub fn ipv6(mut self, ipv6: Ipv6Config) -> Self {
+        self.ipv6 = Some(ipv6);
+        self
+    }
+
+    /// Emits the settings map of the connection profile, e.g. for `NetworkManager::add_connection`.
+    pub fn to_settings(&self) -> Result<SettingsMap> {
+        if self.vlan_id > 4094 {
+            bail!(ErrorKind::Settings(format!(
+                "Invalid VLAN ID {}: expected 0 to 4094",
+                self.vlan_id
+            )));
+        }
+
+        let mut vlan: VariantMap = HashMap::new();
+        add_str(&mut vlan, "parent", self.parent.as_str());
+        add_val(&mut vlan, "id", u32::from(self.vlan_id));
+
+        if let Some(flags) = self.flags {
+            add_val(&mut vlan, "flags", flags.bits());
+        }
+
+        let mut connection: VariantMap = HashMap::new();
+        add_str(&mut connection, "id", self.id.as_str());
+        add_str(&mut connection, "type", "vlan");
+
+        if let Some(ref interface_name) = self.interface_name {
+            add_str(&mut connection, "interface-name", interface_name.as_str());
+        }
+
+        let mut settings: SettingsMap = HashMap::new();
+        settings.insert("connection".to_string(), connection);
+        settings.insert("vlan".to_string(), vlan);
+
+        if let Some(ref ipv4) = self.ipv4 {
+            settings.insert("ipv4".to_string(), ipv4.to_settings()?);
+        }
+
+        if let Some(ref ipv6) = self.ipv6 {
+            settings.insert("ipv6".to_string(), ipv6.to_settings()?);
+        }
+
+        Ok(settings)
+    }
+}
+
+/// Emits the settings of a master connection with its `kind` setting.
+fn master_settings(
+    id: &str,
+    interface_name: &str,
+    kind: &str,
+    setting: VariantMap,
+    ipv4: Option<&Ipv4Config>,
+    ipv6: Option<&Ipv6Config>,
+) -> Result<SettingsMap> {
+    let mut connection: VariantMap = HashMap::new();
+    add_str(&mut connection, "id", id);
+    add_str(&mut connection, "type", kind);
+    add_str(&mut connection, "interface-name", interface_name);
+
+    let mut settings: SettingsMap = HashMap::new();
+    settings.insert("connection".to_string(), connection);
+    settings.insert(kind.to_string(), setting);
+
+    if let Some(ipv4) = ipv4 {
+        settings.insert("ipv4".to_string(), ipv4.to_settings()?);
+    }
+
+    if let Some(ipv6) = ipv6 {
+        settings.insert("ipv6".to_string(), ipv6.to_settings()?);
+    }
+
+    Ok(settings)
+}
+
+/// Builds the `gsm` setting of a mobile broadband connection profile.
+///
+/// # Examples
+///
+/// ```
+/// use network_manager::GsmConfig;
+///
+/// let gsm = GsmConfig::new("internet")
+///     .pin("1234")
+///     .to_settings()
+///     .unwrap();
+///
+/// assert!(gsm.contains_key("apn"));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GsmConfig {
+    apn: String,
+    number: String,
+    username: Option<String>,
+    password: Option<String>,
+    pin: Option<String>,
+    network_id: Option<String>,
+}
+
+impl GsmConfig {
+    pub fn new(apn: &str) -> Self {
+        GsmConfig {
+            apn: apn.to_string(),
+            number: "*99#".to_string(),
+            username: None,
+            password: None,
+            pin: None,
+            network_id: None,
+        }
+    }
+
+    /// Overrides the number dialed by modems which need one, `*99#` by default.
+    pub fn number(mut self, number: &str) -> Self {
+        self.number = number.to_string();
+        self
+    }
+
+    pub fn username(mut self, username: &str) -> Self {
+        self.username = Some(username.to_string());
+        self
+    }
+
+    pub fn password(mut self, password: &str) -> Self {
+        self.password = Some(password.to_string());
+        self
+    }
+
+    /// Sets the PIN which unlocks the SIM card.
+    pub fn pin(mut self, pin: &str) -> Self {
+        self.pin = Some(pin.to_string());
+        self
+    }
+
+    /// Restricts the connection to the operator with the MCC/MNC code `network_id`, e.g.
+    /// `26201`.
+    pub fn network_id(mut self, network_id: &str) -> Self {
+        self.network_id = Some(network_id.to_string());
+        self
+    }
+
+    /// Emits the setting for inclusion under the `gsm` key of a settings map.
+    pub fn to_settings(&self) -> Result<VariantMap> {
+        let mut settings: VariantMap = HashMap::new();
+        add_str(&mut settings, "apn", self.apn.as_str());
+        add_str(&mut settings, "number", self.number.as_str());
+
+        if let Some(ref username) = self.username {
+            add_str(&mut settings, "username", username.as_str());
+        }
+
+        if let Some(ref password) = self.password {
+            add_str(&mut settings, "password", password.as_str());
+        }
+
+        if let Some(ref pin) = self.pin {
+            if pin.len() < 4 || pin.len() > 8 || !pin.chars().all(|c| c.is_numeric()) {
+                bail!(ErrorKind::Settings(
+                    "A SIM PIN must consist of 4 to 8 digits".into()
+                ));
+            }
+
+            add_str(&mut settings, "pin", pin.as_str());
+        }
+
+        if let Some(ref network_id) = self.network_id {
+            if (network_id.len() != 5 && network_id.len() != 6)
+                || !network_id.chars().all(|c| c.is_numeric())
+            {
+                bail!(ErrorKind::Settings(format!(
+                    "Invalid network ID: {}",
+                    network_id
+                )));
+            }
+
+            add_str(&mut settings, "network-id", network_id.as_str());
+        }
+
+        Ok(settings)
+    }
+}
+
+/// Builds the settings of a mobile broadband connection profile named after the APN.
+pub fn gsm_settings(gsm: &GsmConfig) -> Result<SettingsMap> {
+    let mut connection: VariantMap = HashMap::new();
+    add_str(&mut connection, "id", gsm.apn.as_str());
+    add_str(&mut connection, "type", "gsm");
+
+    let mut settings: SettingsMap = HashMap::new();
+    settings.insert("connection".to_string(), connection);
+    settings.insert("gsm".to_string(), gsm.to_settings()?);
+    settings.insert("ipv4".to_string(), Ipv4Config::new().to_settings()?);
+    settings.insert("ipv6".to_string(), Ipv6Config::new().to_settings()?);
+
+    Ok(settings)
+}
+
+/// The outer authentication method of an 802.1X connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EapMethod {
+    Peap,
+    Tls,
+    Ttls,
+}
+
+impl EapMethod {
+    fn as_str(&self) -> &'static str {
+        match *self {
+            EapMethod::Peap => "peap",
+            EapMethod::Tls => "tls",
+            EapMethod::Ttls => "ttls",
+        }
+    }
+}
+
+/// Builds the `802-1x` setting of a WPA-Enterprise connection profile.
+///
+/// Certificates and keys are given as absolute paths of PEM or DER files.
+///
+/// # Examples
+///
+/// ```
+/// use network_manager::{EapMethod, Enterprise8021x};
+///
+/// let eap = Enterprise8021x::new(EapMethod::Peap)
+///     .identity("alice")
+///     .password("hunter22")
+///     .ca_cert("/etc/ssl/certs/corp-ca.pem")
+///     .phase2_auth("mschapv2")
+///     .to_settings()
+///     .unwrap();
+///
+/// assert!(eap.contains_key("ca-cert"));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Enterprise8021x {
+    method: EapMethod,
+    identity: Option<String>,
+    password: Option<String>,
+    ca_cert: Option<String>,
+    client_cert: Option<String>,
+    private_key: Option<String>,
+    private_key_password: Option<String>,
+    phase2_auth: Option<String>,
+}
+
+impl Enterprise8021x {
+    pub fn new(method: EapMethod) -> Self {
+        Enterprise8021x {
+            method: method,
+            identity: None,
+            password: None,
+            ca_cert: None,
+            client_cert: None,
+            private_key: None,
+            private_key_password: None,
+            phase2_auth: None,
+        }
+    }
+
+    pub fn identity(mut self, identity: &str) -> Self {
+        self.identity = Some(identity.to_string());
+        self
+    }
+
+    pub fn password(mut self, password: &str) -> Self {
+        self.password = Some(password.to_string());
+        self
+    }
+
+    pub fn ca_cert(mut self, path: &str) -> Self {
+        self.ca_cert = Some(path.to_string());
+        self
+    }
+
+    /// Sets the client certificate, required by `EapMethod::Tls`.
+    pub fn client_cert(mut self, path: &str) -> Self {
+        self.client_cert = Some(path.to_string());
+        self
+    }
+
+    /// Sets the private key of the client certificate, required by `EapMethod::Tls`.
+    pub fn private_key(mut self, path: &str) -> Self {
+        self.private_key = Some(path.to_string());
+        self
+    }
+
+    pub fn private_key_password(mut self, password: &str) -> Self {
+        self.private_key_password = Some(password.to_string());
+        self
+    }
+
+    /// Sets the inner authentication of PEAP and TTLS, e.g. `mschapv2` or `pap`.
+    pub fn phase2_auth(mut self, phase2_auth: &str) -> Self {
+        self.phase2_auth = Some(phase2_auth.to_string());
+        self
+    }
+
+    /// Emits the setting for inclusion under the `802-1x` key of a settings map.
+    pub fn to_settings(&self) -> Result<VariantMap> {
+        if self.method == EapMethod::Tls
+            && (self.client_cert.is_none() || self.private_key.is_none())
+        {
+            bail!(ErrorKind::Settings(
+                "EAP-TLS requires a client certificate and a private key".into()
+            ));
+        }
+
+        let mut settings: VariantMap = HashMap::new();
+        add_val(&mut settings, "eap", vec![self.method.as_str().to_string()]);
+
+        if let Some(ref identity) = self.identity {
+            add_str(&mut settings, "identity", identity.as_str());
+        }
+
+        if let Some(ref password) = self.password {
+            add_str(&mut settings, "password", password.as_str());
+        }
+
+        if let Some(ref path) = self.ca_cert {
+            add_val(&mut settings, "ca-cert", certificate_path(path)?);
+        }
+
+        if let Some(ref path) = self.client_cert {
+            add_val(&mut settings, "client-cert", certificate_path(path)?);
+        }
+
+        if let Some(ref path) = self.private_key {
+            add_val(&mut settings, "private-key", certificate_path(path)?);
+        }
+
+        if let Some(ref password) = self.private_key_password {
+            add_str(&mut settings, "private-key-password", password.as_str());
+        }
+
+        if let Some(ref phase2_auth) = self.phase2_auth {
+            add_str(&mut settings, "phase2-auth", phase2_auth.as_str());
+        }
+
+        Ok(settings)
+    }
+}
+
+/// Encodes a certificate file the way NetworkManager expects it: as a NUL-terminated
+/// `file://` URI.
+fn certificate_path(path: &str) -> Result<Vec<u8>> {
+    if !path.starts_with('/') {
+        bail!(ErrorKind::Settings(format!(
+            "Certificate path must be absolute: {}",
+            path
+        )));
+    }
+
+    let mut uri = format!("file://{}", path).into_bytes();
+    uri.push(0);
+
+    Ok(uri)
+}
+
+/// Builds the complete settings map of a connection profile from the individual settings.
+///
+/// The connection type follows from the wireless, ethernet or GSM setting, exactly one of
+/// which must be given. A random UUID is generated unless one is set.
+///
+/// # Examples
+///
+/// ```
+/// use std::net::Ipv4Addr;
+/// use network_manager::{ConnectionBuilder, Ipv4Config, Ipv4Method, WirelessConfig,
+///                       WirelessSecurity};
+///
+/// let settings = ConnectionBuilder::new("office")
+///     .wireless(WirelessConfig::new("OfficeNet").unwrap())
+///     .security(WirelessSecurity::Wpa2Psk("hunter22".to_string()))
+///     .ipv4(
+///         Ipv4Config::new()
+///             .method(Ipv4Method::Manual)
+///             .address(Ipv4Addr::new(192, 168, 1, 10), 24),
+///     )
+///     .build()
+///     .unwrap();
+///
+/// assert!(settings.contains_key("802-11-wireless-security"));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConnectionBuilder {
+    id: String,
+    uuid: Option<String>,
+    interface_name: Option<String>,
+    autoconnect: Option<bool>,
+    master: Option<(String, SlaveType)>,
+    wireless: Option<WirelessConfig>,
+    ethernet: Option<EthernetConfig>,
+    gsm: Option<GsmConfig>,
+    security: Option<WirelessSecurity>,
+    eap: Option<Enterprise8021x>,
+    ipv4: Option<Ipv4Config>,
+    ipv6: Option<Ipv6Config>,
+}
+
+impl ConnectionBuilder {
+    pub fn new(id: &str) -> Self {
+        ConnectionBuilder {
+            id: id.to_string(),
+            uuid: None,
+            interface_name: None,
+            autoconnect: None,
+            master: None,
+            wireless: None,
+            ethernet: None,
+            gsm: None,
+            security: None,
+            eap: None,
+            ipv4: None,
+            ipv6: None,
+        }
+    }
+
+    pub fn uuid(mut self, uuid: &str) -> Self {
+        self.uuid = Some(uuid.to_string());
+        self
+    }
+
+    /// Binds the profile to the interface `interface_name`, e.g. `wlan0`.
+    pub fn interface_name(mut self, interface_name: &str) -> Self {
+        self.interface_name = Some(interface_name.to_string());
+        self
+    }
+
+    pub fn autoconnect(mut self, autoconnect: bool) -> Self {
+        self.autoconnect = Some(autoconnect);
+        self
+    }
+
+    /// Makes the profile a slave of the bond or bridge with the interface or UUID `master`,
+    /// which then holds the IP configuration.
+    pub fn master(mut self, master: &str, slave_type: SlaveType) -> Self {
+        self.master = Some((master.to_string(), slave_type));
+        self
+    }
+
+    pub fn wireless(mut self, wireless: WirelessConfig) -> Self {
+        self.wireless = Some(wireless);
+        self
+    }
+
+    pub fn ethernet(mut self, ethernet: EthernetConfig) -> Self {
+        self.ethernet = Some(ethernet);
+        self
+    }
+
+    pub fn gsm(mut self, gsm: GsmConfig) -> Self {
+        self.gsm = Some(gsm);
+        self
+    }
+
+    /// Secures a Wi-Fi connection; implied as `WirelessSecurity::WpaEap` by `eap`.
+    pub fn security(mut self, security: WirelessSecurity) -> Self {
+        self.security = Some(security);
+        self
+    }
+
+    /// Authenticates with 802.1X, over Wi-Fi as WPA-Enterprise or on a wired port.
+    pub fn eap(mut self, eap: Enterprise8021x) -> Self {
+        self.eap = Some(eap);
+        self
+    }
+
+    pub fn ipv4(mut self, ipv4: Ipv4Config) -> Self {
+        self.ipv4 = Some(ipv4);
+        self
+    }
+
+    pub fn ipv6(mut self, ipv6: Ipv6Config) -> Self {
+        self.ipv6 = Some(ipv6);
+        self
+    }
+
+    /// Emits the settings map of the connection profile, e.g. for `NetworkManager::add_connection`.
+    pub fn build(&self) -> Result<SettingsMap> {
+        let mut settings: SettingsMap = HashMap::new();
+
+        let kind = match (self.wireless.as_ref(), self.ethernet.as_ref(), self.gsm.as_ref()) {
+            (Some(wireless), None, None) => {
+                let mut wireless = wireless.to_settings()?;
+
+                let security = match (self.security.as_ref(), self.eap.as_ref()) {
+                    (Some(security), _) => Some(security.clone()),
+                    (None, Some(_)) => Some(WirelessSecurity::WpaEap),
+                    (None, None) => None,
+                };
+
+                if let Some(security) = security {
+                    add_str(&mut wireless, "security", "802-11-wireless-security");
+                    settings.insert(
+                        "802-11-wireless-security".to_string(),
+                        security.to_settings()?,
+                    );
+                }
+
+                settings.insert("802-11-wireless".to_string(), wireless);
+                "802-11-wireless"
+            },
+            (None, Some(ethernet), None) => {
+                settings.insert("802-3-ethernet".to_string(), ethernet.to_settings()?);
+                "802-3-ethernet"
+            },
+            (None, None, Some(gsm)) => {
+                settings.insert("gsm".to_string(), gsm.to_settings()?);
+                "gsm"
+            },
+            _ => bail!(ErrorKind::Settings(
+                "A connection needs exactly one of a wireless, ethernet or GSM setting".into()
+            )),
+        };
+
+        if self.security.is_some() && self.wireless.is_none() {
+            bail!(ErrorKind::Settings(
+                "Wireless security requires a wireless setting".into()
+            ));
+        }
+
+        let uuid = match self.uuid {
+            Some(ref uuid) => uuid.clone(),
+            None => generate_uuid()?,
+        };
+
+        let mut connection: VariantMap = HashMap::new();
+        add_str(&mut connection, "id", self.id.as_str());
+        add_str(&mut connection, "uuid", uuid);
+        add_str(&mut connection, "type", kind);
+
+        if let Some(ref interface_name) = self.interface_name {
+            add_str(&mut connection, "interface-name", interface_name.as_str());
+        }
+
+        if let Some(autoconnect) = self.autoconnect {
+            add_val(&mut connection, "autoconnect", autoconnect);
+        }
+
+        if let Some((ref master, slave_type)) = self.master {
+            if self.ipv4.is_some() || self.ipv6.is_some() {
+                bail!(ErrorKind::Settings(
+                    "A slave connection has no IP configuration of its own".into()
+                ));
+            }
+
+            add_str(&mut connection, "master", master.as_str());
+            add_str(&mut connection, "slave-type", slave_type.as_str());
+        }
+
+        settings.insert("connection".to_string(), connection);
+
+        if let Some(ref eap) = self.eap {
+            settings.insert("802-1x".to_string(), eap.to_settings()?);
+        }
+
+        if let Some(ref ipv4) = self.ipv4 {
+            settings.insert("ipv4".to_string(), ipv4.to_settings()?);
+        }
+
+        if let Some(ref ipv6) = self.ipv6 {
+            settings.insert("ipv6".to_string(), ipv6.to_settings()?);
+        }
+
+        Ok(settings)
+    }
+}
+
+/// Generates a random (version 4) UUID for a new connection profile.
+fn generate_uuid() -> Result<String> {
+    let mut bytes = [0_u8; 16];
+
+    File::open("/dev/urandom")
+        .and_then(|mut random| random.read_exact(&mut bytes))
+        .chain_err(|| ErrorKind::Settings("Generating a connection UUID failed".into()))?;
+
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+
+    let hex = bytes
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<String>();
+
+    Ok(format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    ))
+}
+
+/// Builds the settings of a VPN connection profile handled by the `service_type` plugin, e.g.
+/// `org.freedesktop.NetworkManager.openvpn`.
+///
+/// `data` and `secrets` hold the plugin-specific options, e.g. `remote` and `password` for
+/// OpenVPN.
+pub fn vpn_settings(
+    id: &str,
+    service_type: &str,
+    data: HashMap<String, String>,
+    secrets: HashMap<String, String>,
+) -> SettingsMap {
+    let mut connection: VariantMap = HashMap::new();
+    add_str(&mut connection, "id", id);
+    add_str(&mut connection, "type", "vpn");
+
+    let mut vpn: VariantMap = HashMap::new();
+    add_str(&mut vpn, "service-type", service_type);
+    add_val(&mut vpn, "data", data);
+    if !secrets.is_empty() {
+        add_val(&mut vpn, "secrets", secrets);
+    }
+
+    let mut settings: SettingsMap = HashMap::new();
+    settings.insert("connection".to_string(), connection);
+    settings.insert("vpn".to_string(), vpn);
+
+    settings
+}
+
+#[cfg(test)]
+mod tests {
+    use dbus::Message;
+    use dbus::arg::Variant;
+
+    use dbus_api::{DBusApi, VariantTo};
+
+    use super::*;
+
+    /// An address, prefix length and gateway as encoded in the `ipv6` setting.
+    type Ipv6Address = (Vec<u8>, u32, Vec<u8>);
+
+    /// A destination, prefix length, next hop and metric as encoded in the `ipv6` setting.
+    type Ipv6Route = (Vec<u8>, u32, Vec<u8>, u32);
+
+    #[test]
+    fn test_cloned_mac_address() {
+        let wireless = WirelessConfig::new("CoffeeShop")
+            .unwrap()
+            .cloned_mac_address("02:00:5e:10:00:01")
+            .to_settings()
+            .unwrap();
+        let mac: Option<String> = DBusApi::variant_to(&wireless["cloned-mac-address"]);
+        assert_eq!(mac, Some("02:00:5e:10:00:01".to_string()));
+
+        let ethernet = EthernetConfig::new()
+            .cloned_mac_address("random")
+            .to_settings()
+            .unwrap();
+        let mac: Option<String> = DBusApi::variant_to(&ethernet["cloned-mac-address"]);
+        assert_eq!(mac, Some("random".to_string()));
+
+        assert!(!EthernetConfig::new()
+            .to_settings()
+            .unwrap()
+            .contains_key("cloned-mac-address"));
+    }
+
+    #[test]
+    fn test_mtu() {
+        let settings = ConnectionBuilder::new("jumbo")
+            .ethernet(EthernetConfig::new().mtu(9000))
+            .build()
+            .unwrap();
+        assert_eq!(&*settings["802-3-ethernet"]["mtu"].0.signature(), "u");
+        let mtu: Option<u32> = DBusApi::variant_to(&settings["802-3-ethernet"]["mtu"]);
+        assert_eq!(mtu, Some(9000));
+
+        let settings = ConnectionBuilder::new("CoffeeShop")
+            .wireless(WirelessConfig::new("CoffeeShop").unwrap().mtu(1492))
+            .build()
+            .unwrap();
+        let mtu: Option<u32> = DBusApi::variant_to(&settings["802-11-wireless"]["mtu"]);
+        assert_eq!(mtu, Some(1492));
+
+        assert!(!EthernetConfig::new().to_settings().unwrap().contains_key("mtu"));
+    }
+
+    #[test]
+    fn test_wireless_powersave() {
+        for &(powersave, value) in &[
+            (WirelessPowersave::Default, 0),
+            (WirelessPowersave::Ignore, 1),
+            (WirelessPowersave::Disable, 2),
+            (WirelessPowersave::Enable, 3),
+        ] {
+            let settings = ConnectionBuilder::new("CoffeeShop")
+                .wireless(
+                    WirelessConfig::new("CoffeeShop")
+                        .unwrap()
+                        .powersave(powersave),
+                )
+                .build()
+                .unwrap();
+            let mode: Option<u32> = DBusApi::variant_to(&settings["802-11-wireless"]["powersave"]);
+            assert_eq!(mode, Some(value));
+        }
+
+        let wireless = WirelessConfig::new("CoffeeShop")
+            .unwrap()
+            .to_settings()
+            .unwrap();
+        assert!(!wireless.contains_key("powersave"));
+    }
+
+    #[test]
+    fn test_wireless_bssid() {
+        let wireless = WirelessConfig::new("Mesh")
+            .unwrap()
+            .bssid("A0:b1:C2:d3:E4:f5")
+            .to_settings()
+            .unwrap();
+        assert_eq!(&*wireless["bssid"].0.signature(), "ay");
+        let bssid: Option<Vec<u8>> = DBusApi::variant_to(&wireless["bssid"]);
+        assert_eq!(bssid, Some(vec![0xa0, 0xb1, 0xc2, 0xd3, 0xe4, 0xf5]));
+
+        for bssid in &["a0:b1:c2:d3:e4", "a0:b1:c2:d3:e4:g5", "a0-b1-c2-d3-e4-f5", "random"] {
+            let result = ConnectionBuilder::new("Mesh")
+                .wireless(WirelessConfig::new("Mesh").unwrap().bssid(bssid))
+                .build();
+            assert!(result.is_err(), "{} accepted", bssid);
+        }
+    }
+
+    #[test]
+    fn test_cloned_mac_address_invalid() {
+        for mac in &["02:00:5e:10:00", "02:00:5e:10:00:0g", "0200.5e10.0001", "shuffle"] {
+            let result = EthernetConfig::new().cloned_mac_address(mac).to_settings();
+            assert!(result.is_err(), "{} accepted", mac);
+        }
+    }
+
+    #[test]
+    fn test_bond_config() {
+        let settings = BondConfig::new("uplink", "bond0")
+            .mode("active-backup")
+            .option("miimon", "100")
+            .to_settings()
+            .unwrap();
+
+        let kind: Option<String> = DBusApi::variant_to(&settings["connection"]["type"]);
+        assert_eq!(kind, Some("bond".to_string()));
+        let interface: Option<String> =
+            DBusApi::variant_to(&settings["connection"]["interface-name"]);
+        assert_eq!(interface, Some("bond0".to_string()));
+
+        let options: Option<HashMap<String, String>> =
+            DBusApi::variant_to(&settings["bond"]["options"]);
+        let options = options.unwrap();
+        assert_eq!(options["mode"], "active-backup");
+        assert_eq!(options["miimon"], "100");
+
+        let bridge = BridgeConfig::new("lan", "br0")
+            .stp(false)
+            .to_settings()
+            .unwrap();
+        let stp: Option<bool> = DBusApi::variant_to(&bridge["bridge"]["stp"]);
+        assert_eq!(stp, Some(false));
+        assert!(!bridge.contains_key("ipv4"));
+    }
+
+    #[test]
+    fn test_vlan_config() {
+        let settings = VlanConfig::new("sensors", "eth0", 20)
+            .flags(VlanFlags::REORDER_HEADERS | VlanFlags::LOOSE_BINDING)
+            .interface_name("eth0.sensors")
+            .to_settings()
+            .unwrap();
+
+        let kind: Option<String> = DBusApi::variant_to(&settings["connection"]["type"]);
+        assert_eq!(kind, Some("vlan".to_string()));
+
+        let parent: Option<String> = DBusApi::variant_to(&settings["vlan"]["parent"]);
+        assert_eq!(parent, Some("eth0".to_string()));
+        let id: Option<u32> = DBusApi::variant_to(&settings["vlan"]["id"]);
+        assert_eq!(id, Some(20));
+        let flags: Option<u32> = DBusApi::variant_to(&settings["vlan"]["flags"]);
+        assert_eq!(flags, Some(0x5));
+
+        assert!(VlanConfig::new("sensors", "eth0", 4095).to_settings().is_err());
+    }
+
+    #[test]
+    fn test_connection_builder_slave() {
+        let settings = ConnectionBuilder::new("uplink-eth0")
+            .interface_name("eth0")
+            .ethernet(EthernetConfig::new())
+            .master("bond0", SlaveType::Bond)
+            .build()
+            .unwrap();
+
+        let master: Option<String> = DBusApi::variant_to(&settings["connection"]["master"]);
+        assert_eq!(master, Some("bond0".to_string()));
+        let slave_type: Option<String> =
+            DBusApi::variant_to(&settings["connection"]["slave-type"]);
+        assert_eq!(slave_type, Some("bond".to_string()));
+
+        assert!(ConnectionBuilder::new("uplink-eth0")
+            .ethernet(EthernetConfig::new())
+            .master("bond0", SlaveType::Bond)
+            .ipv4(Ipv4Config::new())
+            .build()
+            .is_err());
+    }
+
+    #[test]
+    fn test_gsm_settings() {
+        let gsm = GsmConfig::new("internet")
+            .username("web")
+            .password("web")
+            .pin("1234")
+            .network_id("26201");
+
+        let settings = gsm_settings(&gsm).unwrap();
+
+        let kind: Option<String> = DBusApi::variant_to(&settings["connection"]["type"]);
+        assert_eq!(kind, Some("gsm".to_string()));
+
+        let apn: Option<String> = DBusApi::variant_to(&settings["gsm"]["apn"]);
+        assert_eq!(apn, Some("internet".to_string()));
+        let number: Option<String> = DBusApi::variant_to(&settings["gsm"]["number"]);
+        assert_eq!(number, Some("*99#".to_string()));
+        let network_id: Option<String> = DBusApi::variant_to(&settings["gsm"]["network-id"]);
+        assert_eq!(network_id, Some("26201".to_string()));
+
+        assert!(settings.contains_key("ipv4"));
+    }
+
+    #[test]
+    fn test_gsm_config_invalid() {
+        assert!(GsmConfig::new("internet").pin("12").to_settings().is_err());
+        assert!(GsmConfig::new("internet").pin("12a4").to_settings().is_err());
+        assert!(
+            GsmConfig::new("internet")
+                .network_id("262")
+                .to_settings()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_connection_builder_wpa2_static_ipv4() {
+        let settings = ConnectionBuilder::new("office")
+            .wireless(WirelessConfig::new("OfficeNet").unwrap())
+            .security(WirelessSecurity::Wpa2Psk("hunter22".to_string()))
+            .ipv4(
+                Ipv4Config::new()
+                    .method(Ipv4Method::Manual)
+                    .address(Ipv4Addr::new(192, 168, 1, 10), 24)
+                    .gateway(Ipv4Addr::new(192, 168, 1, 1)),
+            )
+            .build()
+            .unwrap();
+
+        let mut keys = settings.keys().cloned().collect::<Vec<_>>();
+        keys.sort();
+        assert_eq!(
+            keys,
+            vec![
+                "802-11-wireless",
+                "802-11-wireless-security",
+                "connection",
+                "ipv4",
+            ]
+        );
+
+        let kind: Option<String> = DBusApi::variant_to(&settings["connection"]["type"]);
+        assert_eq!(kind, Some("802-11-wireless".to_string()));
+
+        let uuid: Option<String> = DBusApi::variant_to(&settings["connection"]["uuid"]);
+        let uuid = uuid.unwrap();
+        assert_eq!(uuid.len(), 36);
+        assert_eq!(uuid.chars().nth(14), Some('4'));
+
+        let proto: Option<Vec<String>> =
+            DBusApi::variant_to(&settings["802-11-wireless-security"]["proto"]);
+        assert_eq!(proto, Some(vec!["rsn".to_string()]));
+
+        let method: Option<String> = DBusApi::variant_to(&settings["ipv4"]["method"]);
+        assert_eq!(method, Some("manual".to_string()));
+    }
+
+    #[test]
+    fn test_connection_builder_ethernet_eap() {
+        let settings = ConnectionBuilder::new("wired")
+            .uuid("8f2d1c2e-0b6a-4a1e-9d4b-2f7c4e1a9b10")
+            .ethernet(EthernetConfig::new())
+            .eap(
+                Enterprise8021x::new(EapMethod::Peap)
+                    .identity("alice")
+                    .password("hunter22"),
+            )
+            .build()
+            .unwrap();
+
+        let uuid: Option<String> = DBusApi::variant_to(&settings["connection"]["uuid"]);
+        assert_eq!(uuid, Some("8f2d1c2e-0b6a-4a1e-9d4b-2f7c4e1a9b10".to_string()));
+        let kind: Option<String> = DBusApi::variant_to(&settings["connection"]["type"]);
+        assert_eq!(kind, Some("802-3-ethernet".to_string()));
+
+        assert!(settings.contains_key("802-1x"));
+        assert!(!settings.contains_key("802-11-wireless-security"));
+    }
+
+    #[test]
+    fn test_connection_builder_invalid() {
+        assert!(ConnectionBuilder::new("nothing").build().is_err());
+
+        let both = ConnectionBuilder::new("both")
+            .wireless(WirelessConfig::new("OfficeNet").unwrap())
+            .ethernet(EthernetConfig::new());
+        assert!(both.build().is_err());
+
+        let wired_psk = ConnectionBuilder::new("wired")
+            .ethernet(EthernetConfig::new())
+            .security(WirelessSecurity::WpaPsk("hunter22".to_string()));
+        assert!(wired_psk.build().is_err());
+    }
+
+    #[test]
+    fn test_ipv4_config_manual() {
+        let settings = Ipv4Config::new()
+            .method(Ipv4Method::Manual)
+            .address(Ipv4Addr::new(192, 168, 1, 10), 24)
+            .address(Ipv4Addr::new(10, 0, 0, 2), 8)
+            .gateway(Ipv4Addr::new(192, 168, 1, 1))
+            .dns(vec![Ipv4Addr::new(8, 8, 8, 8)])
+            .to_settings()
+            .unwrap();
+
+        let method: Option<String> = DBusApi::variant_to(&settings["method"]);
+        assert_eq!(method, Some("manual".to_string()));
+
+        let addresses = settings["addresses"]
+            .0
+            .as_iter()
+            .unwrap()
+            .map(|address| {
+                address
+                    .as_iter()
+                    .unwrap()
+                    .map(|value| value.as_i64().unwrap() as u32)
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            addresses,
+            vec![
+                vec![
+                    u32::from(Ipv4Addr::new(192, 168, 1, 10)).to_be(),
+                    24,
+                    u32::from(Ipv4Addr::new(192, 168, 1, 1)).to_be(),
+                ],
+                vec![u32::from(Ipv4Addr::new(10, 0, 0, 2)).to_be(), 8, 0],
+            ]
+        );
+
+        let dns: Option<Vec<u32>> = DBusApi::variant_to(&settings["dns"]);
+        assert_eq!(dns, Some(vec![u32::from(Ipv4Addr::new(8, 8, 8, 8)).to_be()]));
+    }
+
+    #[test]
+    fn test_ipv4_config_auto() {
+        let settings = Ipv4Config::new()
+            .address(Ipv4Addr::new(192, 168, 1, 10), 24)
+            .gateway(Ipv4Addr::new(192, 168, 1, 1))
+            .to_settings()
+            .unwrap();
+
+        let method: Option<String> = DBusApi::variant_to(&settings["method"]);
+        assert_eq!(method, Some("auto".to_string()));
+        assert!(!settings.contains_key("addresses"));
+        assert!(!settings.contains_key("dns"));
+    }
+
+    #[test]
+    fn test_ipv4_config_invalid() {
+        assert!(
+            Ipv4Config::new()
+                .method(Ipv4Method::Manual)
+                .to_settings()
+                .is_err()
+        );
+        assert!(
+            Ipv4Config::new()
+                .method(Ipv4Method::Manual)
+                .address(Ipv4Addr::new(192, 168, 1, 10), 33)
+                .to_settings()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_ipv4_config_routes() {
+        let mut settings = Ipv4Config::new()
+            .routes(vec![
+                Route {
+                    dest: IpAddr::V4(Ipv4Addr::new(10, 10, 0, 0)),
+                    prefix: 16,
+                    next_hop: Some(IpAddr::V4(Ipv4Addr::new(192, 168, 1, 254))),
+                    metric: Some(200),
+                },
+            ])
+            .to_settings()
+            .unwrap();
+
+        assert_eq!(&*settings["routes"].0.signature(), "aau");
+
+        let message = Message::new_method_call("org.example", "/", "org.example", "Check")
+            .unwrap()
+            .append1(settings.remove("routes").unwrap());
+        let routes: Variant<Vec<Vec<u32>>> = message.read1().unwrap();
+        assert_eq!(
+            routes.0,
+            vec![
+                vec![
+                    u32::from(Ipv4Addr::new(10, 10, 0, 0)).to_be(),
+                    16,
+                    u32::from(Ipv4Addr::new(192, 168, 1, 254)).to_be(),
+                    200,
+                ],
+            ]
+        );
+
+        let v6_route = Route {
+            dest: "2001:db8::".parse().unwrap(),
+            prefix: 32,
+            next_hop: None,
+            metric: None,
+        };
+        assert!(Ipv4Config::new().routes(vec![v6_route]).to_settings().is_err());
+    }
+
+    #[test]
+    fn test_never_default() {
+        let ipv4 = Ipv4Config::new().never_default(true).to_settings().unwrap();
+        assert_eq!(&*ipv4["never-default"].0.signature(), "b");
+        let never_default: Option<bool> = DBusApi::variant_to(&ipv4["never-default"]);
+        assert_eq!(never_default, Some(true));
+
+        let ipv6 = Ipv6Config::new().never_default(true).to_settings().unwrap();
+        let never_default: Option<bool> = DBusApi::variant_to(&ipv6["never-default"]);
+        assert_eq!(never_default, Some(true));
+
+        let ipv4 = Ipv4Config::new().never_default(false).to_settings().unwrap();
+        assert!(!ipv4.contains_key("never-default"));
+    }
+
+    #[test]
+    fn test_ipv4_config_split_dns() {
+        let settings = Ipv4Config::new()
+            .dns(vec![Ipv4Addr::new(10, 0, 0, 53)])
+            .dns_priority(-50)
+            .dns_search(vec!["corp.example.com".to_string(), "example.net".to_string()])
+            .dns_options(vec!["rotate".to_string()])
+            .to_settings()
+            .unwrap();
+
+        assert_eq!(&*settings["dns-priority"].0.signature(), "i");
+        let priority: Option<i32> = DBusApi::variant_to(&settings["dns-priority"]);
+        assert_eq!(priority, Some(-50));
+
+        assert_eq!(&*settings["dns-search"].0.signature(), "as");
+        let search: Option<Vec<String>> = DBusApi::variant_to(&settings["dns-search"]);
+        assert_eq!(
+            search,
+            Some(vec!["corp.example.com".to_string(), "example.net".to_string()])
+        );
+
+        let options: Option<Vec<String>> = DBusApi::variant_to(&settings["dns-options"]);
+        assert_eq!(options, Some(vec!["rotate".to_string()]));
+
+        let settings = Ipv4Config::new().to_settings().unwrap();
+        assert!(!settings.contains_key("dns-priority"));
+        assert!(!settings.contains_key("dns-search"));
+        assert!(!settings.contains_key("dns-options"));
+    }
+
+    #[test]
+    fn test_ipv6_config_manual() {
+        let address: Ipv6Addr = "2001:db8::10".parse().unwrap();
+        let gateway: Ipv6Addr = "2001:db8::1".parse().unwrap();
+
+        let mut settings = Ipv6Config::new()
+            .method(Ipv6Method::Manual)
+            .address(address, 64)
+            .gateway(gateway)
+            .addr_gen_mode(Ipv6AddrGenMode::StablePrivacy)
+            .to_settings()
+            .unwrap();
+
+        let method: Option<String> = DBusApi::variant_to(&settings["method"]);
+        assert_eq!(method, Some("manual".to_string()));
+
+        assert_eq!(&*settings["addresses"].0.signature(), "a(ayuay)");
+
+        let message = Message::new_method_call("org.example", "/", "org.example", "Check")
+            .unwrap()
+            .append1(settings.remove("addresses").unwrap());
+        let addresses: Variant<Vec<Ipv6Address>> = message.read1().unwrap();
+        assert_eq!(
+            addresses.0,
+            vec![(address.octets().to_vec(), 64, gateway.octets().to_vec())]
+        );
+
+        let mode: Option<i32> = DBusApi::variant_to(&settings["addr-gen-mode"]);
+        assert_eq!(mode, Some(1));
+    }
+
+    #[test]
+    fn test_ipv6_config_routes() {
+        let dest: Ipv6Addr = "2001:db8:1::".parse().unwrap();
+        let next_hop: Ipv6Addr = "fe80::1".parse().unwrap();
+
+        let mut settings = Ipv6Config::new()
+            .routes(vec![
+                Route {
+                    dest: IpAddr::V6(dest),
+                    prefix: 48,
+                    next_hop: Some(IpAddr::V6(next_hop)),
+                    metric: Some(1024),
+                },
+            ])
+            .to_settings()
+            .unwrap();
+
+        assert_eq!(&*settings["routes"].0.signature(), "a(ayuayu)");
+
+        let message = Message::new_method_call("org.example", "/", "org.example", "Check")
+            .unwrap()
+            .append1(settings.remove("routes").unwrap());
+        let routes: Variant<Vec<Ipv6Route>> = message.read1().unwrap();
+        assert_eq!(
+            routes.0,
+            vec![(dest.octets().to_vec(), 48, next_hop.octets().to_vec(), 1024)]
+        );
+
+        let route = Route {
+            dest: IpAddr::V6(dest),
+            prefix: 129,
+            next_hop: None,
+            metric: None,
+        };
+        assert!(Ipv6Config::new().routes(vec![route]).to_settings().is_err());
+    }
+
+    #[test]
+    fn test_ipv6_config_auto() {
+        let dns: Ipv6Addr = "2001:4860:4860::8888".parse().unwrap();
+
+        let settings = Ipv6Config::new()
+            .address("2001:db8::10".parse().unwrap(), 64)
+            .dns(vec![dns])
+            .dns_priority(100)
+            .dns_search(vec!["example.com".to_string()])
+            .to_settings()
+            .unwrap();
+
+        let method: Option<String> = DBusApi::variant_to(&settings["method"]);
+        assert_eq!(method, Some("auto".to_string()));
+        assert!(!settings.contains_key("addresses"));
+
+        let servers: Option<Vec<Vec<u8>>> = DBusApi::variant_to(&settings["dns"]);
+        assert_eq!(servers, Some(vec![dns.octets().to_vec()]));
+
+        let priority: Option<i32> = DBusApi::variant_to(&settings["dns-priority"]);
+        assert_eq!(priority, Some(100));
+        let search: Option<Vec<String>> = DBusApi::variant_to(&settings["dns-search"]);
+        assert_eq!(search, Some(vec!["example.com".to_string()]));
+    }
+
+    #[test]
+    fn test_ipv6_config_invalid_prefix() {
+        assert!(
+            Ipv6Config::new()
+                .method(Ipv6Method::Manual)
+                .address("2001:db8::10".parse().unwrap(), 129)
+                .to_settings()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_wireguard_config() {
+        let mut settings = WireGuardConfig::new("office-vpn", "wg0", "cHJpdmF0ZQ==")
+            .listen_port(51820)
+            .peer(
+                WireGuardPeer::new("cHVibGlj")
+                    .endpoint("vpn.example.com:51820")
+                    .allowed_ips(vec!["10.0.0.0/24".to_string(), "fd00::/64".to_string()])
+                    .persistent_keepalive(25),
+            )
+            .to_settings()
+            .unwrap();
+
+        let kind: Option<String> = DBusApi::variant_to(&settings["connection"]["type"]);
+        assert_eq!(kind, Some("wireguard".to_string()));
+
+        let wireguard = settings.get_mut("wireguard").unwrap();
+        let port: Option<u32> = DBusApi::variant_to(&wireguard["listen-port"]);
+        assert_eq!(port, Some(51820));
+        assert_eq!(&*wireguard["peers"].0.signature(), "aa{sv}");
+
+        let message = Message::new_method_call("org.example", "/", "org.example", "Check")
+            .unwrap()
+            .append1(wireguard.remove("peers").unwrap());
+        let peers: Variant<Vec<VariantMap>> = message.read1().unwrap();
+        assert_eq!(peers.0.len(), 1);
+
+        let public_key: Option<String> = DBusApi::variant_to(&peers.0[0]["public-key"]);
+        assert_eq!(public_key, Some("cHVibGlj".to_string()));
+        let allowed_ips: Option<Vec<String>> = DBusApi::variant_to(&peers.0[0]["allowed-ips"]);
+        assert_eq!(
+            allowed_ips,
+            Some(vec!["10.0.0.0/24".to_string(), "fd00::/64".to_string()])
+        );
+
+        let method: Option<String> = DBusApi::variant_to(&settings["ipv4"]["method"]);
+        assert_eq!(method, Some("disabled".to_string()));
+    }
+
+    #[test]
+    fn test_wireguard_config_requires_private_key() {
+        assert!(WireGuardConfig::new("vpn", "wg0", "").to_settings().is_err());
+    }
+
+    #[test]
+    fn test_vpn_settings() {
+        let mut data = HashMap::new();
+        data.insert("remote".to_string(), "vpn.example.com".to_string());
+        data.insert("connection-type".to_string(), "password".to_string());
+
+        let mut secrets = HashMap::new();
+        secrets.insert("password".to_string(), "hunter22".to_string());
+
+        let settings = vpn_settings(
+            "office",
+            "org.freedesktop.NetworkManager.openvpn",
+            data.clone(),
+            secrets.clone(),
+        );
+
+        let kind: Option<String> = DBusApi::variant_to(&settings["connection"]["type"]);
+        assert_eq!(kind, Some("vpn".to_string()));
+
+        let vpn = &settings["vpn"];
+        let service_type: Option<String> = DBusApi::variant_to(&vpn["service-type"]);
+        assert_eq!(
+            service_type,
+            Some("org.freedesktop.NetworkManager.openvpn".to_string())
+        );
+        assert_eq!(&*vpn["data"].0.signature(), "a{ss}");
+        let vpn_data: Option<HashMap<String, String>> = DBusApi::variant_to(&vpn["data"]);
+        assert_eq!(vpn_data, Some(data));
+        let vpn_secrets: Option<HashMap<String, String>> = DBusApi::variant_to(&vpn["secrets"]);
+        assert_eq!(vpn_secrets, Some(secrets));
+    }
+
+    #[test]
+    fn test_enterprise_8021x_peap() {
+        let settings = Enterprise8021x::new(EapMethod::Peap)
+            .identity("alice")
+            .password("hunter22")
+            .ca_cert("/etc/ssl/certs/corp-ca.pem")
+            .phase2_auth("mschapv2")
+            .to_settings()
+            .unwrap();
+
+        let eap: Option<Vec<String>> = DBusApi::variant_to(&settings["eap"]);
+        assert_eq!(eap, Some(vec!["peap".to_string()]));
+        let identity: Option<String> = DBusApi::variant_to(&settings["identity"]);
+        assert_eq!(identity, Some("alice".to_string()));
+        let password: Option<String> = DBusApi::variant_to(&settings["password"]);
+        assert_eq!(password, Some("hunter22".to_string()));
+        let phase2_auth: Option<String> = DBusApi::variant_to(&settings["phase2-auth"]);
+        assert_eq!(phase2_auth, Some("mschapv2".to_string()));
+
+        let ca_cert: Option<Vec<u8>> = DBusApi::variant_to(&settings["ca-cert"]);
+        assert_eq!(ca_cert, Some(b"file:///etc/ssl/certs/corp-ca.pem\0".to_vec()));
+        assert!(!settings.contains_key("client-cert"));
+    }
+
+    #[test]
+    fn test_enterprise_8021x_tls() {
+        assert!(
+            Enterprise8021x::new(EapMethod::Tls)
+                .identity("alice")
+                .client_cert("/etc/ssl/alice.pem")
+                .to_settings()
+                .is_err()
+        );
+
+        let settings = Enterprise8021x::new(EapMethod::Tls)
+            .identity("alice")
+            .client_cert("/etc/ssl/alice.pem")
+            .private_key("/etc/ssl/private/alice.key")
+            .private_key_password("secret")
+            .to_settings()
+            .unwrap();
+
+        assert!(settings.contains_key("client-cert"));
+        assert!(settings.contains_key("private-key"));
+        assert!(!settings.contains_key("password"));
+
+        assert!(
+            Enterprise8021x::new(EapMethod::Peap)
+                .ca_cert("corp-ca.pem")
+                .to_settings()
+                .is_err()
+        );
+    }
+}
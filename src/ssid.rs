@@ -71,6 +71,11 @@ impl SsidSlice {
     pub fn as_bytes(&self) -> &[u8] {
         unsafe { mem::transmute(&self.slice) }
     }
+
+    /// Decodes the SSID as UTF-8, replacing invalid sequences with `U+FFFD`.
+    pub fn to_string_lossy(&self) -> String {
+        String::from_utf8_lossy(self.as_bytes()).into_owned()
+    }
 }
 
 impl fmt::Debug for SsidSlice {
@@ -142,6 +147,13 @@ mod tests {
         assert_eq!(slice_from_str, slice_from_u8);
     }
 
+    #[test]
+    fn test_ssid_to_string_lossy() {
+        let ssid = Ssid::from_bytes(b"caf\xe9".to_vec()).unwrap();
+        assert_eq!(ssid.to_string_lossy(), "caf\u{fffd}");
+        assert!(ssid.as_str().is_err());
+    }
+
     #[test]
     fn test_ssid_debug() {
         let ssid = Ssid::from_bytes(b"hello\0\x7F".to_vec()).unwrap();
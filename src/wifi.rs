@@ -1,12 +1,19 @@
+use std::fmt;
 use std::rc::Rc;
 use std::net::Ipv4Addr;
+use std::str::FromStr;
+use std::time::Duration;
 
 use errors::*;
-use dbus_nm::DBusNetworkManager;
+use dbus_api::Properties;
+use dbus_nm::{scan_options, DBusNetworkManager};
 
-use connection::{connect_to_access_point, create_hotspot, Connection, ConnectionState};
+use connection::{connect_to_access_point, connect_to_hidden_ssid, create_hotspot, Connection,
+                 ConnectionState};
 use device::{Device, PathGetter};
+use manager::NetworkManager;
 use ssid::{AsSsidSlice, Ssid, SsidSlice};
+use settings::Enterprise8021x;
 
 pub struct WiFiDevice<'a> {
     dbus_manager: Rc<DBusNetworkManager>,
@@ -32,7 +39,7 @@ impl<'a> WiFiDevice<'a> {
         let mut access_points = Vec::new();
 
         let paths = self.dbus_manager
-            .get_device_access_points(self.device.path())?;
+            .get_access_points(self.device.path())?;
 
         for path in paths {
             if let Some(access_point) = get_access_point(&self.dbus_manager, &path)? {
@@ -46,12 +53,56 @@ impl<'a> WiFiDevice<'a> {
         Ok(access_points)
     }
 
+    /// Calls `callback` with the signal strength of `access_point` as it changes, until the
+    /// callback returns `false` or `timeout` elapses.
+    pub fn watch_strength<F>(
+        &self,
+        access_point: &AccessPoint,
+        timeout: Duration,
+        callback: F,
+    ) -> Result<()>
+    where
+        F: FnMut(u8) -> bool,
+    {
+        self.dbus_manager
+            .watch_access_point_strength(&access_point.path, timeout, callback)
+    }
+
     pub fn request_scan(&self) -> Result<()> {
         self.dbus_manager
             .request_access_point_scan(self.device.path())?;
         Ok(())
     }
 
+    /// Requests a scan and waits until it completes, failing if it does not within `timeout`.
+    pub fn wait_for_scan(&self, timeout: Duration) -> Result<()> {
+        self.dbus_manager
+            .wait_for_scan(self.device.path(), timeout)
+    }
+
+    /// Requests a scan that also probes for the given SSIDs, which finds hidden networks.
+    ///
+    /// Scanning happens in the background. The `LastScan` property of the device is updated
+    /// once it completes, so it can be polled to know when the access point list is fresh.
+    pub fn request_scan_ssids<T>(&self, ssids: &[&T]) -> Result<()>
+    where
+        T: AsSsidSlice + ?Sized,
+    {
+        let mut slices = Vec::with_capacity(ssids.len());
+        for ssid in ssids {
+            slices.push(ssid.as_ssid_slice()?);
+        }
+
+        self.dbus_manager
+            .request_scan(self.device.path(), scan_options(&slices))
+    }
+
+    /// Reads the ciphers, modes and bands supported by the device.
+    pub fn get_capabilities(&self) -> Result<WirelessCapabilities> {
+        self.dbus_manager
+            .get_wireless_capabilities(self.device.path())
+    }
+
     pub fn connect(
         &self,
         access_point: &AccessPoint,
@@ -65,38 +116,146 @@ impl<'a> WiFiDevice<'a> {
         )
     }
 
+    /// Connects to a network which does not broadcast its SSID, using WPA if a `password` is
+    /// given.
+    pub fn connect_hidden<T>(
+        &self,
+        ssid: &T,
+        password: Option<&str>,
+    ) -> Result<(Connection, ConnectionState)>
+    where
+        T: AsSsidSlice + ?Sized,
+    {
+        connect_to_hidden_ssid(&self.dbus_manager, self.device.path(), ssid, password)
+    }
+
+    /// Shares a connection through an access point on this device.
+    ///
+    /// `band` is either `a` (5 GHz) or `bg` (2.4 GHz, the default); a `channel` requires a band.
+    /// A `band` the device does not support is rejected before anything is created.
     pub fn create_hotspot<T>(
         &self,
         ssid: &T,
         password: Option<&str>,
         address: Option<Ipv4Addr>,
+        band: Option<&str>,
+        channel: Option<u32>,
     ) -> Result<(Connection, ConnectionState)>
     where
         T: AsSsidSlice + ?Sized,
     {
+        if let Some(band) = band {
+            if !self.get_capabilities()?.supports_band(band) {
+                bail!(ErrorKind::NetworkManager(format!(
+                    "Device {} does not support the hotspot band '{}'",
+                    self.device.interface(),
+                    band
+                )));
+            }
+        }
+
         create_hotspot(
             &self.dbus_manager,
             self.device.path(),
-            self.device.interface(),
             ssid,
             password,
             address,
+            band,
+            channel,
         )
     }
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct AccessPoint {
     pub path: String,
     pub ssid: Ssid,
-    pub strength: u32,
+    /// Signal quality in percent.
+    pub strength: u8,
     pub security: Security,
+    /// Radio channel frequency in MHz.
+    pub frequency: u32,
+    /// BSSID of the access point.
+    pub hw_address: String,
+    pub mode: ApMode,
+    /// Highest bitrate supported by the access point, in kbit/s.
+    pub max_bitrate_kbps: u32,
+}
+
+/// The operating mode of an access point, from its `Mode` property.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum ApMode {
+    Unknown,
+    /// A peer-to-peer (IBSS) network without an access point.
+    Adhoc,
+    /// A regular network served by an access point.
+    Infra,
+    /// A hotspot run by a device of this host.
+    Ap,
+    Mesh,
+}
+
+impl ApMode {
+    pub fn from_u32(mode: u32) -> Self {
+        match mode {
+            0 => ApMode::Unknown,
+            1 => ApMode::Adhoc,
+            2 => ApMode::Infra,
+            3 => ApMode::Ap,
+            4 => ApMode::Mesh,
+            _ => {
+                warn!("Undefined access point mode: {}", mode);
+                ApMode::Unknown
+            },
+        }
+    }
 }
 
 impl AccessPoint {
+    /// Reads the access point at `path`, e.g. the active access point of a device.
+    ///
+    /// Fails if the access point is gone or its SSID is longer than 32 bytes.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use network_manager::{AccessPoint, NetworkManager};
+    /// let manager = NetworkManager::new();
+    /// let access_point =
+    ///     AccessPoint::from_path(&manager, "/org/freedesktop/NetworkManager/AccessPoint/1")
+    ///         .unwrap();
+    /// println!("{} {}%", access_point.ssid_lossy(), access_point.strength);
+    /// ```
+    pub fn from_path(manager: &NetworkManager, path: &str) -> Result<Self> {
+        manager.get_access_point(path)
+    }
+
     pub fn ssid(&self) -> &SsidSlice {
         &self.ssid
     }
+
+    /// The SSID as text, with bytes which are not valid UTF-8 replaced by `U+FFFD`.
+    pub fn ssid_lossy(&self) -> String {
+        self.ssid.to_string_lossy()
+    }
+
+    /// The SSID as text, or `None` if it is not valid UTF-8, e.g. when encoded as Latin-1.
+    pub fn ssid_utf8(&self) -> Option<String> {
+        self.ssid.as_str().ok().map(|ssid| ssid.to_string())
+    }
+
+    /// The SSID as broadcast by the access point.
+    pub fn ssid_bytes(&self) -> Vec<u8> {
+        self.ssid.as_bytes().to_vec()
+    }
+
+    /// Highest bitrate supported by the access point, in Mbit/s.
+    pub fn max_bitrate_mbps(&self) -> f64 {
+        f64::from(self.max_bitrate_kbps) / 1000.0
+    }
 }
 
 bitflags! {
@@ -109,6 +268,68 @@ bitflags! {
     }
 }
 
+const SECURITY_NAMES: &[(Security, &str)] = &[
+    (Security::WEP, "wep"),
+    (Security::WPA, "wpa"),
+    (Security::WPA2, "wpa2"),
+    (Security::ENTERPRISE, "enterprise"),
+];
+
+/// The lowercase names of the flags set in `security`, e.g. `["wpa", "wpa2"]`.
+pub fn security_names(security: Security) -> Vec<&'static str> {
+    SECURITY_NAMES
+        .iter()
+        .filter(|&&(flag, _)| security.contains(flag))
+        .map(|&(_, name)| name)
+        .collect()
+}
+
+/// The flag called `name`, ignoring case.
+pub fn security_from_name(name: &str) -> Option<Security> {
+    SECURITY_NAMES
+        .iter()
+        .find(|&&(_, known)| known.eq_ignore_ascii_case(name))
+        .map(|&(flag, _)| flag)
+}
+
+/// Formats the lowercase names of the set flags separated by spaces, or `none`.
+impl fmt::Display for Security {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let names = security_names(*self);
+
+        if names.is_empty() {
+            f.write_str("none")
+        } else {
+            f.write_str(&names.join(" "))
+        }
+    }
+}
+
+/// Parses flag names separated by spaces, commas or `|`, ignoring case.
+impl FromStr for Security {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        let mut security = Security::NONE;
+
+        for name in value
+            .split(|c: char| c.is_whitespace() || c == ',' || c == '|')
+            .filter(|name| !name.is_empty())
+        {
+            if name.eq_ignore_ascii_case("none") {
+                continue;
+            }
+
+            match security_from_name(name) {
+                Some(flag) => security |= flag,
+                None => bail!(ErrorKind::Parse(format!("Unknown security: {}", name))),
+            }
+        }
+
+        Ok(security)
+    }
+}
+
 #[derive(Debug)]
 pub enum AccessPointCredentials {
     None,
@@ -122,6 +343,7 @@ pub enum AccessPointCredentials {
         identity: String,
         passphrase: String,
     },
+    Eap(Enterprise8021x),
 }
 
 bitflags! {
@@ -166,6 +388,45 @@ bitflags! {
     }
 }
 
+bitflags! {
+    /// What a wireless device supports, as reported by its driver.
+    pub struct WirelessCapabilities: u32 {
+        const NONE          = 0x0000_0000;
+        const CIPHER_WEP40  = 0x0000_0001;
+        const CIPHER_WEP104 = 0x0000_0002;
+        const CIPHER_TKIP   = 0x0000_0004;
+        const CIPHER_CCMP   = 0x0000_0008;
+        const WPA           = 0x0000_0010;
+        const RSN           = 0x0000_0020;
+        const AP            = 0x0000_0040;
+        const ADHOC         = 0x0000_0080;
+        // the band flags below are only meaningful if this one is set
+        const FREQ_VALID    = 0x0000_0100;
+        const FREQ_2GHZ     = 0x0000_0200;
+        const FREQ_5GHZ     = 0x0000_0400;
+        const FREQ_6GHZ     = 0x0000_0800;
+        const MESH          = 0x0000_1000;
+        const IBSS_RSN      = 0x0000_2000;
+    }
+}
+
+impl WirelessCapabilities {
+    /// Whether the device can operate in a hotspot `band`, `a` (5 GHz) or `bg` (2.4 GHz).
+    ///
+    /// Devices which do not report their frequencies are assumed to support both bands.
+    pub fn supports_band(&self, band: &str) -> bool {
+        if !self.contains(WirelessCapabilities::FREQ_VALID) {
+            return true;
+        }
+
+        match band {
+            "a" => self.contains(WirelessCapabilities::FREQ_5GHZ),
+            "bg" => self.contains(WirelessCapabilities::FREQ_2GHZ),
+            _ => false,
+        }
+    }
+}
+
 pub fn new_wifi_device<'a>(
     dbus_manager: &Rc<DBusNetworkManager>,
     device: &'a Device,
@@ -177,31 +438,54 @@ pub fn new_wifi_device<'a>(
 }
 
 fn get_access_point(manager: &DBusNetworkManager, path: &str) -> Result<Option<AccessPoint>> {
-    if let Some(ssid) = manager.get_access_point_ssid(path) {
-        let strength = manager.get_access_point_strength(path)?;
-
-        let security = get_access_point_security(manager, path)?;
+    // The access point may have disappeared since the scan that listed it
+    let properties = match manager.get_access_point_properties(path) {
+        Ok(properties) => properties,
+        Err(_) => return Ok(None),
+    };
 
-        let access_point = AccessPoint {
-            path: path.to_string(),
-            ssid: ssid,
-            strength: strength,
-            security: security,
-        };
+    let ssid = match properties.get::<Vec<u8>>("Ssid").map(Ssid::from_bytes) {
+        Ok(Ok(ssid)) => ssid,
+        _ => return Ok(None),
+    };
 
-        Ok(Some(access_point))
-    } else {
-        Ok(None)
-    }
+    decode_access_point(path, &properties, ssid).map(Some)
 }
 
-fn get_access_point_security(manager: &DBusNetworkManager, path: &str) -> Result<Security> {
-    let flags = manager.get_access_point_flags(path)?;
+/// Reads the access point at `path`, failing instead of skipping it if it is gone.
+pub fn access_point_from_path(manager: &DBusNetworkManager, path: &str) -> Result<AccessPoint> {
+    let properties = manager.get_access_point_properties(path)?;
+    let ssid = Ssid::from_bytes(properties.get::<Vec<u8>>("Ssid")?)?;
 
-    let wpa_flags = manager.get_access_point_wpa_flags(path)?;
+    decode_access_point(path, &properties, ssid)
+}
 
-    let rsn_flags = manager.get_access_point_rsn_flags(path)?;
+fn decode_access_point(path: &str, properties: &Properties, ssid: Ssid) -> Result<AccessPoint> {
+    let security = security_from_flags(
+        properties.get("Flags")?,
+        properties.get("WpaFlags")?,
+        properties.get("RsnFlags")?,
+    );
 
+    Ok(AccessPoint {
+        path: path.to_string(),
+        ssid: ssid,
+        strength: properties.get("Strength")?,
+        security: security,
+        frequency: properties.get("Frequency")?,
+        hw_address: properties.get("HwAddress")?,
+        mode: ApMode::from_u32(properties.get("Mode")?),
+        max_bitrate_kbps: properties.get("MaxBitrate")?,
+    })
+}
+
+/// Derives the supported security from the `Flags`, `WpaFlags` and `RsnFlags` of an access
+/// point.
+pub fn security_from_flags(
+    flags: NM80211ApFlags,
+    wpa_flags: NM80211ApSecurityFlags,
+    rsn_flags: NM80211ApSecurityFlags,
+) -> Security {
     let mut security = Security::NONE;
 
     if flags.contains(NM80211ApFlags::AP_FLAGS_PRIVACY)
@@ -225,5 +509,281 @@ fn get_access_point_security(manager: &DBusNetworkManager, path: &str) -> Result
         security |= Security::ENTERPRISE;
     }
 
-    Ok(security)
+    security
+}
+
+#[cfg(test)]
+mod tests {
+    use mock::MockService;
+
+    use super::*;
+
+    #[test]
+    fn test_wireless_capabilities_bands() {
+        let dual_band = WirelessCapabilities::from_bits_truncate(0x07ff);
+        assert!(dual_band.contains(WirelessCapabilities::AP | WirelessCapabilities::RSN));
+        assert!(dual_band.supports_band("a"));
+        assert!(dual_band.supports_band("bg"));
+        assert!(!dual_band.supports_band("6"));
+
+        let single_band = WirelessCapabilities::from_bits_truncate(0x0340);
+        assert!(single_band.contains(WirelessCapabilities::AP));
+        assert!(!single_band.contains(WirelessCapabilities::ADHOC));
+        assert!(!single_band.supports_band("a"));
+        assert!(single_band.supports_band("bg"));
+
+        let unreported = WirelessCapabilities::from_bits_truncate(0x003f);
+        assert!(!unreported.contains(WirelessCapabilities::AP));
+        assert!(unreported.supports_band("a"));
+        assert!(unreported.supports_band("bg"));
+
+        let future = WirelessCapabilities::from_bits_truncate(0x8000_0000 | 0x1000);
+        assert_eq!(future, WirelessCapabilities::MESH);
+    }
+
+    const AP_PATH: &str = "/org/freedesktop/NetworkManager/AccessPoint/1";
+    const AP_INTERFACE: &str = "org.freedesktop.NetworkManager.AccessPoint";
+
+    /// Serves a single access point with the given SSID and security flags.
+    fn spawn_access_point(
+        service: &'static str,
+        ssid: &'static [u8],
+        flags: u32,
+        wpa_flags: u32,
+        rsn_flags: u32,
+    ) -> MockService {
+        MockService::spawn(service, move |f, _| {
+            f.tree(()).add(f.object_path(AP_PATH, ()).add(
+                f.interface(AP_INTERFACE, ())
+                    .add_p(f.property::<Vec<u8>, _>("Ssid", ()).on_get(move |i, _| {
+                        i.append(ssid.to_vec());
+                        Ok(())
+                    }))
+                    .add_p(f.property::<u8, _>("Strength", ()).on_get(|i, _| {
+                        i.append(72_u8);
+                        Ok(())
+                    }))
+                    .add_p(f.property::<u32, _>("Frequency", ()).on_get(|i, _| {
+                        i.append(5180_u32);
+                        Ok(())
+                    }))
+                    .add_p(f.property::<&str, _>("HwAddress", ()).on_get(|i, _| {
+                        i.append("A0:B1:C2:D3:E4:F5");
+                        Ok(())
+                    }))
+                    .add_p(f.property::<u32, _>("Flags", ()).on_get(move |i, _| {
+                        i.append(flags);
+                        Ok(())
+                    }))
+                    .add_p(f.property::<u32, _>("WpaFlags", ()).on_get(move |i, _| {
+                        i.append(wpa_flags);
+                        Ok(())
+                    }))
+                    .add_p(f.property::<u32, _>("RsnFlags", ()).on_get(move |i, _| {
+                        i.append(rsn_flags);
+                        Ok(())
+                    }))
+                    .add_p(f.property::<u32, _>("Mode", ()).on_get(|i, _| {
+                        i.append(2_u32);
+                        Ok(())
+                    }))
+                    .add_p(f.property::<u32, _>("MaxBitrate", ()).on_get(|i, _| {
+                        i.append(54_000_u32);
+                        Ok(())
+                    })),
+            ))
+        })
+    }
+
+    fn access_point_with_ssid(ssid: &[u8]) -> AccessPoint {
+        AccessPoint {
+            path: AP_PATH.to_string(),
+            ssid: Ssid::from_bytes(ssid.to_vec()).unwrap(),
+            strength: 72,
+            security: Security::NONE,
+            frequency: 2412,
+            hw_address: "A0:B1:C2:D3:E4:F5".to_string(),
+            mode: ApMode::Infra,
+            max_bitrate_kbps: 54_000,
+        }
+    }
+
+    #[test]
+    fn test_access_point_ssid_utf8() {
+        let access_point = access_point_with_ssid("Café ☕".as_bytes());
+
+        assert_eq!(access_point.ssid_utf8(), Some("Café ☕".to_string()));
+        assert_eq!(access_point.ssid_lossy(), "Café ☕");
+        assert_eq!(access_point.ssid_bytes(), "Café ☕".as_bytes().to_vec());
+    }
+
+    #[test]
+    fn test_access_point_ssid_invalid_utf8() {
+        // "Café" encoded as Latin-1
+        let access_point = access_point_with_ssid(b"Caf\xe9");
+
+        assert_eq!(access_point.ssid_utf8(), None);
+        assert_eq!(access_point.ssid_lossy(), "Caf\u{fffd}");
+        assert_eq!(access_point.ssid_bytes(), b"Caf\xe9".to_vec());
+    }
+
+    #[test]
+    fn test_access_point_ssid_embedded_nul() {
+        let access_point = access_point_with_ssid(b"lab\0net");
+
+        assert_eq!(access_point.ssid_utf8(), Some("lab\0net".to_string()));
+        assert_eq!(access_point.ssid_lossy(), "lab\0net");
+        assert_eq!(access_point.ssid_bytes().len(), 7);
+    }
+
+    #[test]
+    fn test_access_point_max_bitrate() {
+        let mut access_point = access_point_with_ssid(b"CoffeeShop");
+        assert_eq!(access_point.max_bitrate_mbps(), 54.0);
+
+        access_point.max_bitrate_kbps = 866_700;
+        assert_eq!(access_point.max_bitrate_mbps(), 866.7);
+    }
+
+    #[test]
+    fn test_security_display_from_str() {
+        assert_eq!((Security::WPA | Security::WPA2).to_string(), "wpa wpa2");
+        assert_eq!(Security::NONE.to_string(), "none");
+
+        assert_eq!(
+            "WPA2, Enterprise".parse::<Security>().unwrap(),
+            Security::WPA2 | Security::ENTERPRISE
+        );
+        assert_eq!("none".parse::<Security>().unwrap(), Security::NONE);
+        assert!("wpa3".parse::<Security>().is_err());
+    }
+
+    #[test]
+    fn test_security_from_flags_open() {
+        let security = security_from_flags(
+            NM80211ApFlags::AP_FLAGS_NONE,
+            NM80211ApSecurityFlags::AP_SEC_NONE,
+            NM80211ApSecurityFlags::AP_SEC_NONE,
+        );
+
+        assert_eq!(security, Security::NONE);
+    }
+
+    #[test]
+    fn test_security_from_flags_wep() {
+        let security = security_from_flags(
+            NM80211ApFlags::AP_FLAGS_PRIVACY,
+            NM80211ApSecurityFlags::AP_SEC_NONE,
+            NM80211ApSecurityFlags::AP_SEC_NONE,
+        );
+
+        assert_eq!(security, Security::WEP);
+    }
+
+    #[test]
+    fn test_security_from_flags_wpa2_psk() {
+        let rsn_flags = NM80211ApSecurityFlags::AP_SEC_PAIR_CCMP
+            | NM80211ApSecurityFlags::AP_SEC_GROUP_CCMP
+            | NM80211ApSecurityFlags::AP_SEC_KEY_MGMT_PSK;
+
+        let security = security_from_flags(
+            NM80211ApFlags::AP_FLAGS_PRIVACY,
+            NM80211ApSecurityFlags::AP_SEC_NONE,
+            rsn_flags,
+        );
+
+        assert_eq!(security, Security::WPA2);
+    }
+
+    #[test]
+    fn test_security_from_flags_wpa2_enterprise() {
+        let rsn_flags = NM80211ApSecurityFlags::AP_SEC_PAIR_CCMP
+            | NM80211ApSecurityFlags::AP_SEC_GROUP_CCMP
+            | NM80211ApSecurityFlags::AP_SEC_KEY_MGMT_802_1X;
+
+        let security = security_from_flags(
+            NM80211ApFlags::AP_FLAGS_PRIVACY,
+            NM80211ApSecurityFlags::AP_SEC_NONE,
+            rsn_flags,
+        );
+
+        assert_eq!(security, Security::WPA2 | Security::ENTERPRISE);
+    }
+
+    #[test]
+    fn test_get_access_point() {
+        const SERVICE: &str = "org.freedesktop.NetworkManager.Test.AccessPoint";
+
+        let service = spawn_access_point(SERVICE, b"caf\xe9", 0x1, 0x0, 0x188);
+        let dbus_manager = DBusNetworkManager::with_mock_service(SERVICE);
+
+        let calls = service.method_calls();
+        let access_point = get_access_point(&dbus_manager, AP_PATH).unwrap().unwrap();
+        assert_eq!(service.method_calls(), calls + 1);
+
+        assert_eq!(access_point.path, AP_PATH);
+        assert_eq!(access_point.ssid().as_bytes(), b"caf\xe9");
+        assert_eq!(access_point.strength, 72);
+        assert_eq!(access_point.frequency, 5180);
+        assert_eq!(access_point.hw_address, "A0:B1:C2:D3:E4:F5");
+        assert_eq!(access_point.security, Security::WPA2);
+        assert_eq!(access_point.mode, ApMode::Infra);
+        assert_eq!(access_point.max_bitrate_kbps, 54_000);
+        assert_eq!(access_point.max_bitrate_mbps(), 54.0);
+    }
+
+    #[test]
+    fn test_get_ap_security() {
+        for &(service, flags, wpa_flags, rsn_flags, security) in &[
+            ("org.freedesktop.NetworkManager.Test.OpenSecurity", 0x0, 0x0, 0x0, Security::NONE),
+            ("org.freedesktop.NetworkManager.Test.PskSecurity", 0x1, 0x0, 0x188, Security::WPA2),
+            (
+                "org.freedesktop.NetworkManager.Test.EnterpriseSecurity",
+                0x1,
+                0x0,
+                0x288,
+                Security::WPA2 | Security::ENTERPRISE,
+            ),
+        ] {
+            let _service = spawn_access_point(service, b"CoffeeShop", flags, wpa_flags, rsn_flags);
+            let dbus_manager = DBusNetworkManager::with_mock_service(service);
+
+            assert_eq!(dbus_manager.get_ap_security(AP_PATH).unwrap(), security);
+        }
+    }
+
+    #[test]
+    fn test_access_point_from_path() {
+        const SERVICE: &str = "org.freedesktop.NetworkManager.Test.AccessPointFromPath";
+
+        let _service = spawn_access_point(SERVICE, b"caf\xe9", 0x1, 0x0, 0x188);
+        let dbus_manager = DBusNetworkManager::with_mock_service(SERVICE);
+
+        let access_point = access_point_from_path(&dbus_manager, AP_PATH).unwrap();
+        assert_eq!(access_point.path, AP_PATH);
+        assert_eq!(access_point.ssid_bytes(), b"caf\xe9".to_vec());
+        assert_eq!(access_point.ssid_lossy(), "caf\u{fffd}");
+        assert_eq!(access_point.strength, 72);
+        assert_eq!(access_point.frequency, 5180);
+        assert_eq!(access_point.hw_address, "A0:B1:C2:D3:E4:F5");
+        assert_eq!(access_point.security, Security::WPA2);
+
+        let missing = "/org/freedesktop/NetworkManager/AccessPoint/2";
+        assert!(access_point_from_path(&dbus_manager, missing).is_err());
+        assert!(get_access_point(&dbus_manager, missing).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_ap_mode_from_u32() {
+        for &(code, mode) in &[
+            (0, ApMode::Unknown),
+            (1, ApMode::Adhoc),
+            (2, ApMode::Infra),
+            (3, ApMode::Ap),
+            (4, ApMode::Mesh),
+            (5, ApMode::Unknown),
+        ] {
+            assert_eq!(ApMode::from_u32(code), mode, "mode {}", code);
+        }
+    }
 }